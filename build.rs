@@ -1,5 +1,6 @@
 //! Set up linker scripts for the rp235x-hal examples
 
+use std::collections::HashMap;
 use std::env;
 use std::fs::File;
 use std::io::Write;
@@ -46,29 +47,113 @@ fn main() {
     let boot_rom_path = std::env::var("BOOT_ROM_PATH").unwrap_or("dmg_boot.bin".to_string());
     println!("cargo:rustc-env=BOOT_ROM_PATH={}", boot_rom_path);
 
+    let display_bus = std::env::var("DISPLAY_BUS").unwrap_or("SPI".to_string());
+    let parallel_bus = match display_bus.as_str() {
+        "SPI" => false,
+        "PARALLEL" => true,
+        _ => panic!("DISPLAY_BUS has to be one of (SPI, PARALLEL)"),
+    };
+    if parallel_bus {
+        println!("cargo:rustc-cfg=feature=\"screen_parallel\"");
+    }
+    let dvi_output = load_pin_mapping(parallel_bus);
+    if dvi_output && rom_location == "FLASH" {
+        // Core 1 streams the DVI signal while running from flash, it cannot be written to
+        panic!("DVI output needs ROM_LOCATION to be RAM or PSRAM");
+    }
+
+    // The DVI output keeps a Game Boy sized image and scales it to 640x480 in hardware
+    let (display_width, display_height) = if dvi_output {
+        ("160".to_string(), "144".to_string())
+    } else {
+        (
+            std::env::var("DISPLAY_WIDTH").expect("DISPLAY_WIDTH needs to be set"),
+            std::env::var("DISPLAY_HEIGHT").expect("DISPLAY_HEIGHT needs to be set"),
+        )
+    };
+    println!("cargo:rustc-env=DISPLAY_WIDTH={}", display_width);
+    println!("cargo:rustc-env=DISPLAY_HEIGHT={}", display_height);
+
     println!(
-        "cargo:rustc-env=DISPLAY_WIDTH={}",
-        std::env::var("DISPLAY_WIDTH").expect("DISPLAY_WIDTH needs to be set")
+        "cargo:rustc-env=FRAME_RATE={}",
+        std::env::var("FRAME_RATE").unwrap_or("30".to_string())
     );
     println!(
-        "cargo:rustc-env=DISPLAY_HEIGHT={}",
-        std::env::var("DISPLAY_HEIGHT").expect("DISPLAY_HEIGHT needs to be set")
+        "cargo:rustc-env=FRAME_BLEND={}",
+        std::env::var("FRAME_BLEND").unwrap_or("0".to_string())
     );
+    let color_correction = std::env::var("COLOR_CORRECTION").unwrap_or("NONE".to_string());
+    let gbc_lcd = match color_correction.as_str() {
+        "NONE" => false,
+        "GBC_LCD" => true,
+        _ => panic!("COLOR_CORRECTION has to be one of (NONE, GBC_LCD)"),
+    };
+    println!("cargo:rustc-env=COLOR_CORRECTION_GBC_LCD={}", gbc_lcd);
     println!(
-        "cargo:rustc-env=GAMEBOY_RENDER_WIDTH={}",
-        std::env::var("GAMEBOY_RENDER_WIDTH").unwrap_or("160".to_string())
+        "cargo:rustc-env=COLOR_SATURATION={}",
+        std::env::var("COLOR_SATURATION").unwrap_or("100".to_string())
     );
+    let gamma = std::env::var("COLOR_GAMMA")
+        .unwrap_or("1.0".to_string())
+        .parse::<f32>()
+        .ok()
+        .filter(|gamma| *gamma > 0.0 && *gamma <= 5.0)
+        .expect("COLOR_GAMMA has to be a number between 0 and 5, such as 1.0 or 2.2");
     println!(
-        "cargo:rustc-env=GAMEBOY_RENDER_HEIGHT={}",
-        std::env::var("GAMEBOY_RENDER_HEIGHT").unwrap_or("144".to_string())
+        "cargo:rustc-env=COLOR_GAMMA={}",
+        (gamma * 100.0).round() as u16
     );
-
     println!(
-        "cargo:rustc-env=FRAME_RATE={}",
-        std::env::var("FRAME_RATE").unwrap_or("30".to_string())
+        "cargo:rustc-env=DISPLAY_SKIP_UNCHANGED_LINES={}",
+        std::env::var("DISPLAY_SKIP_UNCHANGED_LINES").unwrap_or("true".to_string())
+    );
+    println!(
+        "cargo:rustc-env=SCREENSHOT_PNG={}",
+        std::env::var("SCREENSHOT_PNG").unwrap_or("false".to_string())
+    );
+    let audio_sample_rate = std::env::var("AUDIO_SAMPLE_RATE").unwrap_or("32000".to_string());
+    match audio_sample_rate.as_str() {
+        "22050" | "32000" | "44100" | "48000" => {}
+        _ => panic!("AUDIO_SAMPLE_RATE has to be one of (22050, 32000, 44100, 48000)"),
+    }
+    println!("cargo:rustc-env=AUDIO_SAMPLE_RATE={}", audio_sample_rate);
+    let audio_resampler = std::env::var("AUDIO_RESAMPLER").unwrap_or("SINC".to_string());
+    let resampler_sinc = match audio_resampler.as_str() {
+        "LINEAR" => false,
+        "SINC" => true,
+        _ => panic!("AUDIO_RESAMPLER has to be one of (LINEAR, SINC)"),
+    };
+    println!("cargo:rustc-env=AUDIO_RESAMPLER_SINC={}", resampler_sinc);
+    println!(
+        "cargo:rustc-env=AUDIO_MASTER={}",
+        std::env::var("AUDIO_MASTER").unwrap_or("true".to_string())
+    );
+    println!(
+        "cargo:rustc-env=AUDIO_PWM_NOISE_SHAPING={}",
+        std::env::var("AUDIO_PWM_NOISE_SHAPING").unwrap_or("true".to_string())
+    );
+    println!(
+        "cargo:rustc-env=OSD_STATS={}",
+        std::env::var("OSD_STATS").unwrap_or("false".to_string())
+    );
+    println!(
+        "cargo:rustc-env=INPUT_DEBOUNCE_MS={}",
+        std::env::var("INPUT_DEBOUNCE_MS").unwrap_or("10".to_string())
+    );
+    println!(
+        "cargo:rustc-env=HOTKEY_LONG_PRESS_MS={}",
+        std::env::var("HOTKEY_LONG_PRESS_MS").unwrap_or("1000".to_string())
+    );
+    println!(
+        "cargo:rustc-env=HOTKEY_CHORD_WINDOW_MS={}",
+        std::env::var("HOTKEY_CHORD_WINDOW_MS").unwrap_or("250".to_string())
     );
 
-    let display_orientation = std::env::var("DISPLAY_ROTATION").unwrap_or("0".to_string());
+    let display_orientation = if dvi_output {
+        "0".to_string()
+    } else {
+        std::env::var("DISPLAY_ROTATION").unwrap_or("0".to_string())
+    };
     let rotation = match display_orientation.as_str() {
         "0" => 0,
         "90" => 90,
@@ -77,6 +162,15 @@ fn main() {
         _ => panic!("DISPLAY_ROTATION has to be one of (0, 90, 180, 270)"),
     };
     println!("cargo:rustc-env=DISPLAY_ROTATION={}", rotation);
+    load_render_size(
+        display_width
+            .parse()
+            .expect("DISPLAY_WIDTH has to be a number"),
+        display_height
+            .parse()
+            .expect("DISPLAY_HEIGHT has to be a number"),
+        rotation,
+    );
     println!(
         "cargo:rustc-env=DISPLAY_MIRRORED={}",
         std::env::var("DISPLAY_MIRRORED").unwrap_or("false".to_string())
@@ -85,14 +179,82 @@ fn main() {
         "cargo:rustc-env=DISPLAY_COLOR_INVERT={}",
         std::env::var("DISPLAY_COLOR_INVERT").unwrap_or("false".to_string())
     );
-    load_pin_mapping();
-    load_display_driver();
+    let color_order = std::env::var("DISPLAY_COLOR_ORDER").unwrap_or("BGR".to_string());
+    let bgr = match color_order.as_str() {
+        "RGB" => false,
+        "BGR" => true,
+        _ => panic!("DISPLAY_COLOR_ORDER has to be one of (RGB, BGR)"),
+    };
+    println!("cargo:rustc-env=DISPLAY_COLOR_BGR={}", bgr);
+    let clock_divider = std::env::var("DISPLAY_CLOCK_DIVIDER").unwrap_or("3".to_string());
+    let (divider, divider_frac) = clock_divider
+        .parse::<f32>()
+        .ok()
+        .filter(|divider| *divider >= 1.0 && *divider < 65536.0)
+        .map(|divider| (divider as u16, (divider.fract() * 256.0) as u8))
+        .expect("DISPLAY_CLOCK_DIVIDER has to be a number such as 3 or 2.5");
+    println!("cargo:rustc-env=DISPLAY_CLOCK_DIVIDER={}", divider);
+    println!(
+        "cargo:rustc-env=DISPLAY_CLOCK_DIVIDER_FRAC={}",
+        divider_frac
+    );
+    let te_mode = std::env::var("DISPLAY_TE_MODE").unwrap_or("VBLANK".to_string());
+    let te_scanline = match te_mode.as_str() {
+        "VBLANK" => -1,
+        "SCANLINE" => std::env::var("DISPLAY_TE_SCANLINE")
+            .unwrap_or("0".to_string())
+            .parse::<u16>()
+            .expect("DISPLAY_TE_SCANLINE has to be a line number") as i32,
+        _ => panic!("DISPLAY_TE_MODE has to be one of (VBLANK, SCANLINE)"),
+    };
+    println!("cargo:rustc-env=DISPLAY_TE_SCANLINE={}", te_scanline);
+    if !dvi_output {
+        load_display_driver();
+    }
 
     println!("cargo:rerun-if-changed=pin_mapping.env");
     println!("cargo:rerun-if-changed=.env");
 }
 
-fn load_pin_mapping() {
+/// Size of the scaled Game Boy image, either set directly with GAMEBOY_RENDER_WIDTH and
+/// GAMEBOY_RENDER_HEIGHT or computed from the GAMEBOY_SCALE preset.
+fn load_render_size(display_width: u32, display_height: u32, rotation: u16) {
+    let (render_width, render_height) = if rotation == 90 || rotation == 270 {
+        (display_height, display_width)
+    } else {
+        (display_width, display_height)
+    };
+
+    let scale = std::env::var("GAMEBOY_SCALE").unwrap_or("NONE".to_string());
+    let (preset_width, preset_height) = match scale.as_str() {
+        "NONE" => (160, 144),
+        "INTEGER" => {
+            let factor = (render_width / 160).min(render_height / 144).max(1);
+            (160 * factor, 144 * factor)
+        }
+        "FIT_HEIGHT" => {
+            let width = render_height * 160 / 144;
+            if width <= render_width {
+                (width, render_height)
+            } else {
+                (render_width, render_width * 144 / 160)
+            }
+        }
+        "STRETCH" => (render_width, render_height),
+        _ => panic!("GAMEBOY_SCALE has to be one of (NONE, INTEGER, FIT_HEIGHT, STRETCH)"),
+    };
+    println!(
+        "cargo:rustc-env=GAMEBOY_RENDER_WIDTH={}",
+        std::env::var("GAMEBOY_RENDER_WIDTH").unwrap_or(preset_width.to_string())
+    );
+    println!(
+        "cargo:rustc-env=GAMEBOY_RENDER_HEIGHT={}",
+        std::env::var("GAMEBOY_RENDER_HEIGHT").unwrap_or(preset_height.to_string())
+    );
+}
+
+/// Emits the pins as `PIN_<NAME>`, returns whether the mapping selects the DVI output.
+fn load_pin_mapping(parallel_bus: bool) -> bool {
     let mut env_map = dotenvy::EnvLoader::with_path("pin_mapping.env")
         .load()
         .unwrap();
@@ -116,14 +278,249 @@ fn load_pin_mapping() {
         }
         None => {}
     }
+    // A key left empty in the custom mapping removes it, such as the buttons replaced by an
+    // input backend
+    env_map.retain(|_, value| !value.is_empty());
+
+    let dvi_output = env_map.contains_key("DVI_CLK_P");
+    if dvi_output {
+        println!("cargo:rustc-cfg=feature=\"dvi_output\"");
+        // HSTX is only routed to GPIO 12 to 19
+        for key in [
+            "DVI_CLK_P",
+            "DVI_CLK_N",
+            "DVI_D0_P",
+            "DVI_D0_N",
+            "DVI_D1_P",
+            "DVI_D1_N",
+            "DVI_D2_P",
+            "DVI_D2_N",
+        ] {
+            let pin = env_map
+                .get(key)
+                .unwrap_or_else(|| panic!("{} needs to be set in the pin mapping for DVI", key))
+                .parse::<u8>()
+                .unwrap_or_else(|_| panic!("{} has to be a pin number", key));
+            if !(12..=19).contains(&pin) {
+                panic!("{} has to be one of the HSTX pins (12 to 19)", key);
+            }
+        }
+    } else if env_map.contains_key("SCREEN_TE") {
+        println!("cargo:rustc-cfg=feature=\"screen_te\"");
+    }
+
+    if env_map.contains_key("AUDIO_AMP_SD") {
+        println!("cargo:rustc-cfg=feature=\"audio_amp_sd\"");
+    }
+    if let Some(battery) = env_map.get("BATTERY_ADC") {
+        let battery = battery
+            .parse::<u8>()
+            .expect("BATTERY_ADC has to be a pin number");
+        if !matches!(battery, 26..=29 | 40..=47) {
+            panic!("BATTERY_ADC has to be an ADC pin (26 to 29 or 40 to 47)");
+        }
+        println!("cargo:rustc-cfg=feature=\"battery\"");
+        for (key, default) in [
+            ("BATTERY_DIVIDER", "2"),
+            ("BATTERY_EMPTY_MV", "3300"),
+            ("BATTERY_FULL_MV", "4200"),
+        ] {
+            let value = env_map.get(key).map(String::as_str).unwrap_or(default);
+            value
+                .parse::<u32>()
+                .unwrap_or_else(|_| panic!("{} has to be a number", key));
+            println!("cargo:rustc-env={}={}", key, value);
+        }
+    }
+    for extra in 1..=4 {
+        if env_map.contains_key(&format!("EXTRA_BUTTON_{}", extra)) {
+            println!("cargo:rustc-cfg=feature=\"extra_button_{}\"", extra);
+        }
+    }
+    load_input_backends(&env_map);
+    if let Some(left) = env_map.get("PWM_AUDIO_L") {
+        println!("cargo:rustc-cfg=feature=\"audio_pwm\"");
+        let left = left
+            .parse::<u8>()
+            .expect("PWM_AUDIO_L has to be a pin number");
+        // Both channels of a stereo output have to come from the same slice. GPIO 32 and up
+        // use slices 8 to 11, which repeat every 8 pins like the first 8 do every 16
+        let slice = if left < 32 {
+            (left >> 1) & 7
+        } else {
+            8 + (((left - 32) >> 1) & 3)
+        };
+        println!("cargo:rustc-env=PWM_AUDIO_SLICE={}", slice);
+        if let Some(right) = env_map.get("PWM_AUDIO_R") {
+            let right = right
+                .parse::<u8>()
+                .expect("PWM_AUDIO_R has to be a pin number");
+            if left % 2 != 0 || right != left + 1 {
+                panic!("PWM_AUDIO_L has to be an even pin and PWM_AUDIO_R the pin after it");
+            }
+            println!("cargo:rustc-cfg=feature=\"audio_pwm_stereo\"");
+        }
+    }
+
+    if parallel_bus {
+        // The PIO program drives the data bus as 8 consecutive pins starting at SCREEN_D0
+        for key in ["SCREEN_D0", "SCREEN_WR"] {
+            if !env_map.contains_key(key) {
+                panic!(
+                    "{} needs to be set in the pin mapping for DISPLAY_BUS=PARALLEL",
+                    key
+                );
+            }
+        }
+        let data_base = env_map
+            .get("SCREEN_D0")
+            .unwrap()
+            .parse::<u8>()
+            .expect("SCREEN_D0 has to be a pin number");
+        for n in 1..8 {
+            env_map.insert(format!("SCREEN_D{}", n), (data_base + n).to_string());
+        }
+    }
 
     for (key, value) in env_map {
         println!("cargo:rustc-env=PIN_{}={}", key, value);
     }
+    dvi_output
+}
+
+/// Selects the sources the buttons are read from, any number of them can be combined.
+fn load_input_backends(env_map: &HashMap<String, String>) {
+    let pin = |key: &str| -> Option<u8> {
+        env_map.get(key).map(|value| {
+            value
+                .parse::<u8>()
+                .unwrap_or_else(|_| panic!("{} has to be a pin number", key))
+        })
+    };
+    let mut backends = 0;
+
+    // The D-pad pins can be left out on their own, for an analog stick to replace them
+    let button_groups = [
+        (
+            "gpio_buttons",
+            ["A_BUTTON", "B_BUTTON", "SELECT_BUTTON", "START_BUTTON"],
+        ),
+        (
+            "gpio_dpad",
+            ["UP_BUTTON", "DOWN_BUTTON", "LEFT_BUTTON", "RIGHT_BUTTON"],
+        ),
+    ];
+    for (feature, keys) in button_groups {
+        let buttons = keys
+            .iter()
+            .filter(|key| env_map.contains_key(**key))
+            .count();
+        if buttons == keys.len() {
+            println!("cargo:rustc-cfg=feature=\"{}\"", feature);
+            backends += 1;
+        } else if buttons != 0 {
+            panic!("Either all or none of {} have to be set", keys.join(", "));
+        }
+    }
+
+    if let Some(load) = pin("SHIFT_LOAD") {
+        // SH/LD and CLK are driven together through the side-set of the PIO program
+        if pin("SHIFT_CLK") != Some(load + 1) || pin("SHIFT_DATA").is_none() {
+            panic!("SHIFT_DATA has to be set and SHIFT_CLK has to be the pin after SHIFT_LOAD");
+        }
+        let bits = env_map
+            .get("SHIFT_BITS")
+            .map(|bits| bits.parse::<u8>().expect("SHIFT_BITS has to be a number"))
+            .unwrap_or(8);
+        if !(1..=16).contains(&bits) {
+            panic!("SHIFT_BITS has to be between 1 and 16");
+        }
+        println!("cargo:rustc-cfg=feature=\"input_shift_register\"");
+        println!("cargo:rustc-env=INPUT_SHIFT_REGISTER_BITS={}", bits);
+        backends += 1;
+    }
+
+    if let Some(sda) = pin("EXPANDER_SDA") {
+        let scl = pin("EXPANDER_SCL").expect("EXPANDER_SCL needs to be set for EXPANDER_SDA");
+        // SDA is on the even and SCL on the odd pins, the blocks alternate every two pins
+        if sda % 2 != 0 || scl % 2 != 1 || (sda / 2) % 2 != (scl / 2) % 2 {
+            panic!(
+                "EXPANDER_SDA and EXPANDER_SCL have to be the SDA and SCL of the same I2C block"
+            );
+        }
+        if (sda / 2) % 2 == 1 {
+            println!("cargo:rustc-cfg=feature=\"input_expander_i2c1\"");
+        }
+        match env_map.get("EXPANDER_TYPE").map(String::as_str) {
+            Some("PCF8574") | None => {}
+            Some("MCP23017") => println!("cargo:rustc-cfg=feature=\"input_expander_mcp23017\""),
+            _ => panic!("EXPANDER_TYPE has to be one of (PCF8574, MCP23017)"),
+        }
+        let address = env_map
+            .get("EXPANDER_ADDRESS")
+            .map(|address| {
+                u8::from_str_radix(address.trim_start_matches("0x"), 16)
+                    .expect("EXPANDER_ADDRESS has to be a hexadecimal address")
+            })
+            .unwrap_or(0x20);
+        println!("cargo:rustc-cfg=feature=\"input_expander\"");
+        println!("cargo:rustc-env=INPUT_EXPANDER_ADDRESS={}", address);
+        backends += 1;
+    }
+
+    if env_map.contains_key("MATRIX_ROW_1") && env_map.contains_key("MATRIX_COL_1") {
+        println!("cargo:rustc-cfg=feature=\"input_matrix\"");
+        for n in 2..=4 {
+            if env_map.contains_key(&format!("MATRIX_ROW_{}", n)) {
+                println!("cargo:rustc-cfg=feature=\"matrix_row_{}\"", n);
+            }
+            if env_map.contains_key(&format!("MATRIX_COL_{}", n)) {
+                println!("cargo:rustc-cfg=feature=\"matrix_col_{}\"", n);
+            }
+        }
+        backends += 1;
+    }
+
+    if let Some(x) = pin("JOYSTICK_X") {
+        let y = pin("JOYSTICK_Y").expect("JOYSTICK_Y needs to be set for JOYSTICK_X");
+        // GPIO 26 to 29 on the RP2350A, 40 to 47 on the RP2350B
+        for (key, pin) in [("JOYSTICK_X", x), ("JOYSTICK_Y", y)] {
+            if !matches!(pin, 26..=29 | 40..=47) {
+                panic!("{} has to be an ADC pin (26 to 29 or 40 to 47)", key);
+            }
+        }
+        println!("cargo:rustc-cfg=feature=\"joystick\"");
+        for key in ["JOYSTICK_INVERT_X", "JOYSTICK_INVERT_Y"] {
+            println!(
+                "cargo:rustc-env={}={}",
+                key,
+                env_map.get(key).map(String::as_str).unwrap_or("false")
+            );
+        }
+        backends += 1;
+    }
+
+    if backends == 0 {
+        panic!("The pin mapping has no buttons, set the *_BUTTON pins or an input backend");
+    }
 }
 
 fn load_display_driver() {
     let display_driver = std::env::var("DISPLAY_DRIVER").expect("DISPLAY_DRIVER needs to be set");
+    let pixel_format = std::env::var("DISPLAY_PIXEL_FORMAT").unwrap_or(
+        if display_driver.ends_with("Rgb666") {
+            "RGB666"
+        } else {
+            "RGB565"
+        }
+        .to_string(),
+    );
+    let rgb666 = match pixel_format.as_str() {
+        "RGB565" => false,
+        "RGB666" => true,
+        _ => panic!("DISPLAY_PIXEL_FORMAT has to be one of (RGB565, RGB666)"),
+    };
+    println!("cargo:rustc-env=DISPLAY_RGB666={}", rgb666);
     let code = format!(
         "
         use {display_driver} as DisplayDriver;
@@ -0,0 +1,12 @@
+/// Requests made through button combinations that are handled by the emulator itself.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EmulatorAction {
+    Screenshot,
+    VolumeUp,
+    VolumeDown,
+    ToggleMute,
+    ToggleRecording,
+    /// Pauses the game and shows the in-game menu.
+    OpenMenu,
+}
@@ -0,0 +1,111 @@
+use crate::input::joystick::ADC_MAX;
+
+/// Reference voltage of the ADC in millivolts.
+const ADC_REFERENCE_MV: u32 = 3300;
+
+/// Turns readings of an ADC pin behind a voltage divider into a charge level. The level is
+/// linear between the empty and full voltage, which is close enough for a LiPo cell between
+/// 3.3V and 4.2V.
+pub struct BatteryGauge {
+    divider: u32,
+    empty_mv: u32,
+    full_mv: u32,
+    /// Smoothed battery voltage, `None` until the first reading.
+    millivolts: Option<u32>,
+}
+
+impl BatteryGauge {
+    /// `divider` is the ratio between the battery voltage and the voltage on the pin.
+    pub fn new(divider: u32, empty_mv: u32, full_mv: u32) -> Self {
+        Self {
+            divider: divider.max(1),
+            empty_mv,
+            full_mv: full_mv.max(empty_mv + 1),
+            millivolts: None,
+        }
+    }
+
+    /// Adds an ADC reading and returns the charge level in percent. Readings are averaged
+    /// so the level doesn't jump with the load of the display and audio.
+    pub fn update(&mut self, reading: u16) -> u8 {
+        let millivolts =
+            reading.min(ADC_MAX) as u32 * ADC_REFERENCE_MV * self.divider / ADC_MAX as u32;
+        let millivolts = match self.millivolts {
+            Some(previous) => (previous * 3 + millivolts) / 4,
+            None => millivolts,
+        };
+        self.millivolts = Some(millivolts);
+        self.percent()
+    }
+
+    /// Charge level of the averaged voltage in percent, 0 before the first reading.
+    pub fn percent(&self) -> u8 {
+        let Some(millivolts) = self.millivolts else {
+            return 0;
+        };
+        let level = millivolts.clamp(self.empty_mv, self.full_mv) - self.empty_mv;
+        (level * 100 / (self.full_mv - self.empty_mv)) as u8
+    }
+
+    /// Averaged battery voltage in millivolts, `None` before the first reading.
+    pub fn millivolts(&self) -> Option<u32> {
+        self.millivolts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ADC reading for a battery voltage behind a 1:1 divider.
+    fn reading(millivolts: u32) -> u16 {
+        (millivolts / 2 * ADC_MAX as u32 / ADC_REFERENCE_MV) as u16
+    }
+
+    #[test]
+    fn first_reading_sets_the_level() {
+        let mut gauge = BatteryGauge::new(2, 3300, 4200);
+        assert_eq!(gauge.percent(), 0);
+        assert_eq!(gauge.millivolts(), None);
+        assert_eq!(gauge.update(reading(3750)), 49);
+        assert!(gauge.millivolts().unwrap().abs_diff(3750) < 5);
+    }
+
+    #[test]
+    fn level_is_clamped_to_the_range() {
+        let mut gauge = BatteryGauge::new(2, 3300, 4200);
+        assert_eq!(gauge.update(reading(3000)), 0);
+        let mut gauge = BatteryGauge::new(2, 3300, 4200);
+        assert_eq!(gauge.update(ADC_MAX), 100);
+        let mut gauge = BatteryGauge::new(2, 3300, 4200);
+        assert_eq!(gauge.update(u16::MAX), 100);
+    }
+
+    #[test]
+    fn divider_scales_the_pin_voltage() {
+        let mut direct = BatteryGauge::new(1, 0, 3300);
+        assert_eq!(direct.update(ADC_MAX / 2), 49);
+        let mut halved = BatteryGauge::new(2, 0, 6600);
+        assert_eq!(halved.update(ADC_MAX / 2), 49);
+    }
+
+    #[test]
+    fn readings_are_averaged() {
+        let mut gauge = BatteryGauge::new(2, 3300, 4200);
+        gauge.update(reading(4200));
+        // A single dip under load only moves the level a quarter of the way
+        let level = gauge.update(reading(3300));
+        assert!((70..=76).contains(&level), "{}", level);
+        for _ in 0..20 {
+            gauge.update(reading(3300));
+        }
+        assert!(gauge.percent() <= 1);
+    }
+
+    #[test]
+    fn invalid_ranges_do_not_divide_by_zero() {
+        assert_eq!(BatteryGauge::new(0, 4000, 4000).update(0), 0);
+        assert_eq!(BatteryGauge::new(0, 4000, 4000).update(ADC_MAX), 0);
+        assert_eq!(BatteryGauge::new(2, 4000, 4000).update(ADC_MAX), 100);
+    }
+}
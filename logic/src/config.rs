@@ -0,0 +1,286 @@
+//! Parsing of the `KEY=value` settings files on the SD card.
+
+/// Iterates the `KEY=value` lines of a config file, blank lines and `#` comments are skipped
+/// and quotes around the value are removed.
+pub fn config_entries(text: &str) -> impl Iterator<Item = (&str, &str)> {
+    text.lines().filter_map(|line| {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (key, value) = line.split_once('=')?;
+        Some((key.trim(), value.trim().trim_matches('"')))
+    })
+}
+
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" | "1" => Some(true),
+        "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Name of the display configuration file in the root of the SD card.
+pub const DISPLAY_CONFIG_FILE: &str = "display.cfg";
+
+/// Largest width or height `display.cfg` accepts, well above any panel the firmware can drive.
+const MAX_DISPLAY_SIZE: u16 = 1024;
+
+/// Panel settings read at boot, values missing from `display.cfg` keep the compiled defaults.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DisplayConfig {
+    pub width: u16,
+    pub height: u16,
+    pub rotation: u16,
+    pub mirrored: bool,
+    pub invert_colors: bool,
+    pub bgr: bool,
+    /// Integer and fractional (1/256) part of the PIO clock divider of the display bus.
+    pub clock_divider: (u16, u8),
+}
+
+impl DisplayConfig {
+    /// Applies a `display.cfg` entry, returns `None` when the key is not a display setting and
+    /// `Some(false)` when the value is invalid, which keeps the current setting.
+    pub fn apply_entry(&mut self, key: &str, value: &str) -> Option<bool> {
+        let valid = match key {
+            "WIDTH" => parse_size(value).map(|width| self.width = width).is_some(),
+            "HEIGHT" => parse_size(value)
+                .map(|height| self.height = height)
+                .is_some(),
+            "ROTATION" => match value.parse::<u16>() {
+                Ok(rotation @ (0 | 90 | 180 | 270)) => {
+                    self.rotation = rotation;
+                    true
+                }
+                _ => false,
+            },
+            "MIRRORED" => parse_bool(value)
+                .map(|mirrored| self.mirrored = mirrored)
+                .is_some(),
+            "COLOR_INVERT" => parse_bool(value)
+                .map(|invert| self.invert_colors = invert)
+                .is_some(),
+            "COLOR_ORDER" => match value {
+                "RGB" => {
+                    self.bgr = false;
+                    true
+                }
+                "BGR" => {
+                    self.bgr = true;
+                    true
+                }
+                _ => false,
+            },
+            "CLOCK_DIVIDER" => parse_divider(value)
+                .map(|divider| self.clock_divider = divider)
+                .is_some(),
+            _ => return None,
+        };
+        Some(valid)
+    }
+
+    /// Size of the screen once rotated.
+    pub fn render_size(&self) -> (u16, u16) {
+        if self.rotation == 90 || self.rotation == 270 {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        }
+    }
+
+    /// Whether the rotated screen has room for an image of the given size.
+    pub fn fits(&self, width: u16, height: u16) -> bool {
+        let (render_width, render_height) = self.render_size();
+        render_width >= width && render_height >= height
+    }
+}
+
+fn parse_size(value: &str) -> Option<u16> {
+    match value.parse::<u16>() {
+        Ok(size) if (1..=MAX_DISPLAY_SIZE).contains(&size) => Some(size),
+        _ => None,
+    }
+}
+
+/// Parses a divider such as `3`, `3.` or `2.5` into its integer and 1/256 fractional parts.
+/// The PIO can't divide by less than 1 and fractions finer than 4 digits are rejected rather
+/// than silently rounded.
+pub fn parse_divider(value: &str) -> Option<(u16, u8)> {
+    let (integer, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let is_number = |digits: &str| digits.bytes().all(|c| c.is_ascii_digit());
+    if integer.is_empty() || !is_number(integer) || !is_number(fraction) || fraction.len() > 4 {
+        return None;
+    }
+    let integer: u16 = integer.parse().ok()?;
+    if integer == 0 {
+        return None;
+    }
+    let scale = 10u32.pow(fraction.len() as u32);
+    let fraction: u32 = if fraction.is_empty() {
+        0
+    } else {
+        fraction.parse().ok()?
+    };
+    Some((integer, (fraction * 256 / scale) as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn entries_skip_comments_and_blank_lines() {
+        let text = "# comment\n\nVOLUME=5\n  # indented comment\nMUTED = true \n";
+        let entries: Vec<_> = config_entries(text).collect();
+        assert_eq!(entries, [("VOLUME", "5"), ("MUTED", "true")]);
+    }
+
+    #[test]
+    fn entries_remove_quotes_and_keep_empty_values() {
+        let text = "DISPLAY_TE_MODE = \"SCANLINE\"\nMAP_A=\nNOT_AN_ENTRY\n";
+        let entries: Vec<_> = config_entries(text).collect();
+        assert_eq!(entries, [("DISPLAY_TE_MODE", "SCANLINE"), ("MAP_A", "")]);
+    }
+
+    #[test]
+    fn entries_split_on_the_first_equals_sign() {
+        let entries: Vec<_> = config_entries("KEY=a=b\r\n").collect();
+        assert_eq!(entries, [("KEY", "a=b")]);
+    }
+
+    #[test]
+    fn bools() {
+        assert_eq!(parse_bool("true"), Some(true));
+        assert_eq!(parse_bool("1"), Some(true));
+        assert_eq!(parse_bool("false"), Some(false));
+        assert_eq!(parse_bool("0"), Some(false));
+        assert_eq!(parse_bool("yes"), None);
+        assert_eq!(parse_bool("TRUE"), None);
+        assert_eq!(parse_bool(""), None);
+    }
+
+    const DEFAULTS: DisplayConfig = DisplayConfig {
+        width: 240,
+        height: 320,
+        rotation: 0,
+        mirrored: false,
+        invert_colors: false,
+        bgr: true,
+        clock_divider: (3, 0),
+    };
+
+    /// Applies a whole file, returns the config and the entries that were ignored.
+    fn display_config(text: &str) -> (DisplayConfig, Vec<&str>) {
+        let mut config = DEFAULTS;
+        let ignored = config_entries(text)
+            .filter(|(key, value)| config.apply_entry(key, value) != Some(true))
+            .map(|(key, _)| key)
+            .collect();
+        (config, ignored)
+    }
+
+    #[test]
+    fn display_rotations() {
+        for rotation in [0, 90, 180, 270] {
+            let text = alloc::format!("ROTATION={}", rotation);
+            let (config, ignored) = display_config(&text);
+            assert_eq!(config.rotation, rotation);
+            assert!(ignored.is_empty());
+        }
+        for rotation in ["45", "360", "-90", "", "ninety"] {
+            let text = alloc::format!("ROTATION={}", rotation);
+            let (config, ignored) = display_config(&text);
+            assert_eq!(config.rotation, 0, "{}", rotation);
+            assert_eq!(ignored, ["ROTATION"]);
+        }
+    }
+
+    #[test]
+    fn display_flags() {
+        let (config, ignored) = display_config("MIRRORED=true\nCOLOR_INVERT=1\nCOLOR_ORDER=RGB\n");
+        assert!(config.mirrored);
+        assert!(config.invert_colors);
+        assert!(!config.bgr);
+        assert!(ignored.is_empty());
+
+        let (config, _) = display_config("MIRRORED=1\nMIRRORED=false\nCOLOR_ORDER=BGR\n");
+        assert!(!config.mirrored);
+        assert!(config.bgr);
+
+        let (config, ignored) =
+            display_config("MIRRORED=yes\nCOLOR_INVERT=on\nCOLOR_ORDER=bgr\nCOLOR_ORDER=GRB\n");
+        assert_eq!(config, DEFAULTS);
+        assert_eq!(
+            ignored,
+            ["MIRRORED", "COLOR_INVERT", "COLOR_ORDER", "COLOR_ORDER"]
+        );
+    }
+
+    #[test]
+    fn display_size() {
+        let (config, ignored) = display_config("WIDTH=480\nHEIGHT=320\n");
+        assert_eq!((config.width, config.height), (480, 320));
+        assert!(ignored.is_empty());
+
+        let (config, ignored) =
+            display_config("WIDTH=0\nHEIGHT=1025\nWIDTH=70000\nHEIGHT=-1\nWIDTH=1.5\n");
+        assert_eq!((config.width, config.height), (240, 320));
+        assert_eq!(ignored, ["WIDTH", "HEIGHT", "WIDTH", "HEIGHT", "WIDTH"]);
+
+        let (config, _) = display_config("WIDTH=1\nHEIGHT=1024\n");
+        assert_eq!((config.width, config.height), (1, 1024));
+    }
+
+    #[test]
+    fn display_size_follows_the_rotation() {
+        let (config, _) = display_config("ROTATION=90");
+        assert_eq!(config.render_size(), (320, 240));
+        assert!(config.fits(320, 240));
+        assert!(!DEFAULTS.fits(320, 240));
+        assert!(DEFAULTS.fits(240, 320));
+    }
+
+    #[test]
+    fn display_dividers() {
+        assert_eq!(parse_divider("3"), Some((3, 0)));
+        assert_eq!(parse_divider("3."), Some((3, 0)));
+        assert_eq!(parse_divider("2.5"), Some((2, 128)));
+        assert_eq!(parse_divider("1.25"), Some((1, 64)));
+        assert_eq!(parse_divider("1.9999"), Some((1, 255)));
+        assert_eq!(parse_divider("65535"), Some((65535, 0)));
+        assert_eq!(parse_divider("1.99999"), None);
+        assert_eq!(parse_divider("0"), None);
+        assert_eq!(parse_divider("0.5"), None);
+        assert_eq!(parse_divider(".5"), None);
+        assert_eq!(parse_divider("65536"), None);
+        assert_eq!(parse_divider("+3"), None);
+        assert_eq!(parse_divider("3.-5"), None);
+        assert_eq!(parse_divider("3.5.1"), None);
+        assert_eq!(parse_divider(""), None);
+        assert_eq!(parse_divider("fast"), None);
+    }
+
+    #[test]
+    fn bad_display_lines_keep_the_compiled_defaults() {
+        let text = "WIDTH=320\nCLOCK_DIVIDER=0\nBRIGHTNESS=50\nROTATION=91\nCLOCK_DIVIDER=2.5\n";
+        let (config, ignored) = display_config(text);
+        assert_eq!(
+            config,
+            DisplayConfig {
+                width: 320,
+                clock_divider: (2, 128),
+                ..DEFAULTS
+            }
+        );
+        assert_eq!(ignored, ["CLOCK_DIVIDER", "BRIGHTNESS", "ROTATION"]);
+
+        let mut config = DEFAULTS;
+        assert_eq!(config.apply_entry("BRIGHTNESS", "50"), None);
+        assert_eq!(config.apply_entry("ROTATION", "91"), Some(false));
+        assert_eq!(config, DEFAULTS);
+    }
+}
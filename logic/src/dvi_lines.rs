@@ -0,0 +1,283 @@
+//! Builds the HSTX command lists of every DVI scanline.
+
+use crate::tmds::{control_symbol, CONTROL_SYMBOLS};
+
+/// HSTX command expander opcodes, the low 12 bits hold the count.
+pub const HSTX_CMD_RAW_REPEAT: u32 = 0x1 << 12;
+pub const HSTX_CMD_TMDS: u32 = 0x2 << 12;
+pub const HSTX_CMD_NOP: u32 = 0xF << 12;
+
+/// Words of the command list in front of the pixels of an active line.
+const ACTIVE_HEADER_WORDS: usize = 9;
+/// Words of the command list of a blanking line.
+const BLANK_LINE_WORDS: usize = 7;
+
+/// Raw word with the control symbols of the three lanes, both syncs are active low.
+const fn sync_word(vsync: bool, hsync: bool) -> u32 {
+    control_symbol(!vsync, !hsync) as u32
+        | (CONTROL_SYMBOLS[0] as u32) << 10
+        | (CONTROL_SYMBOLS[0] as u32) << 20
+}
+
+pub struct DviTiming {
+    pub h_front_porch: u32,
+    pub h_sync_width: u32,
+    pub h_back_porch: u32,
+    pub h_active_pixels: u32,
+    pub v_front_porch: u32,
+    pub v_sync_width: u32,
+    pub v_back_porch: u32,
+    pub v_active_lines: u32,
+}
+
+/// 640x480 at 60Hz, a 25.2MHz pixel clock.
+pub const DVI_640X480: DviTiming = DviTiming {
+    h_front_porch: 16,
+    h_sync_width: 96,
+    h_back_porch: 48,
+    h_active_pixels: 640,
+    v_front_porch: 10,
+    v_sync_width: 2,
+    v_back_porch: 33,
+    v_active_lines: 480,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LineKind {
+    Blank,
+    VSync,
+    /// Index of the line within the visible area.
+    Active(u32),
+}
+
+impl DviTiming {
+    pub const fn total_lines(&self) -> u32 {
+        self.v_front_porch + self.v_sync_width + self.v_back_porch + self.v_active_lines
+    }
+
+    /// Size of a buffer that fits the command list of any line, pixels are packed two per word.
+    pub const fn line_words(&self) -> usize {
+        ACTIVE_HEADER_WORDS + self.h_active_pixels as usize / 2
+    }
+
+    /// Lines are counted from the start of the vertical front porch.
+    pub fn line_kind(&self, line: u32) -> LineKind {
+        let sync_start = self.v_front_porch;
+        let back_porch_start = sync_start + self.v_sync_width;
+        let active_start = back_porch_start + self.v_back_porch;
+        if line < sync_start {
+            LineKind::Blank
+        } else if line < back_porch_start {
+            LineKind::VSync
+        } else if line < active_start {
+            LineKind::Blank
+        } else {
+            LineKind::Active(line - active_start)
+        }
+    }
+
+    fn write_blank_line(&self, vsync: bool, out: &mut [u32]) -> usize {
+        out[..BLANK_LINE_WORDS].copy_from_slice(&[
+            HSTX_CMD_RAW_REPEAT | self.h_front_porch,
+            sync_word(vsync, false),
+            HSTX_CMD_RAW_REPEAT | self.h_sync_width,
+            sync_word(vsync, true),
+            HSTX_CMD_RAW_REPEAT | (self.h_back_porch + self.h_active_pixels),
+            sync_word(vsync, false),
+            HSTX_CMD_NOP,
+        ]);
+        BLANK_LINE_WORDS
+    }
+
+    fn write_active_header(&self, out: &mut [u32]) -> usize {
+        out[..ACTIVE_HEADER_WORDS].copy_from_slice(&[
+            HSTX_CMD_RAW_REPEAT | self.h_front_porch,
+            sync_word(false, false),
+            HSTX_CMD_NOP,
+            HSTX_CMD_RAW_REPEAT | self.h_sync_width,
+            sync_word(false, true),
+            HSTX_CMD_NOP,
+            HSTX_CMD_RAW_REPEAT | self.h_back_porch,
+            sync_word(false, false),
+            HSTX_CMD_TMDS | self.h_active_pixels,
+        ]);
+        ACTIVE_HEADER_WORDS
+    }
+}
+
+/// Scales a small RGB565 image by the largest integer factor that fits the mode and centres it
+/// on a black border, so only the source image has to be kept in RAM.
+pub struct ScaledLineGenerator {
+    timing: DviTiming,
+    source_width: u32,
+    source_height: u32,
+    scale: u32,
+    x_offset: u32,
+    y_offset: u32,
+}
+
+impl ScaledLineGenerator {
+    pub fn new(timing: DviTiming, source_width: u32, source_height: u32) -> Self {
+        let scale = (timing.h_active_pixels / source_width)
+            .min(timing.v_active_lines / source_height)
+            .max(1);
+        Self {
+            x_offset: timing.h_active_pixels.saturating_sub(source_width * scale) / 2,
+            y_offset: timing.v_active_lines.saturating_sub(source_height * scale) / 2,
+            timing,
+            source_width,
+            source_height,
+            scale,
+        }
+    }
+
+    /// Row of the source image shown on the given visible line, `None` on the border.
+    pub fn source_row(&self, active_line: u32) -> Option<u32> {
+        let row = active_line.checked_sub(self.y_offset)? / self.scale;
+        (row < self.source_height).then_some(row)
+    }
+
+    /// Writes the command list of `line` into `out` and returns its length in words.
+    /// `pixel(x, y)` returns the RGB565 colour of the source image.
+    #[inline(always)]
+    pub fn write_line(&self, line: u32, out: &mut [u32], pixel: impl Fn(u32, u32) -> u16) -> usize {
+        let row = match self.timing.line_kind(line) {
+            LineKind::Blank => return self.timing.write_blank_line(false, out),
+            LineKind::VSync => return self.timing.write_blank_line(true, out),
+            LineKind::Active(active_line) => self.source_row(active_line),
+        };
+        let header = self.timing.write_active_header(out);
+        let pixels = &mut out[header..header + self.timing.h_active_pixels as usize / 2];
+        match row {
+            None => pixels.fill(0),
+            Some(row) => {
+                let image_end = self.x_offset + self.source_width * self.scale;
+                let colour = |x: u32| {
+                    if x < self.x_offset || x >= image_end {
+                        0
+                    } else {
+                        pixel((x - self.x_offset) / self.scale, row) as u32
+                    }
+                };
+                for (pair, word) in pixels.iter_mut().enumerate() {
+                    let x = pair as u32 * 2;
+                    // The expander encodes the low half first
+                    *word = colour(x) | (colour(x + 1) << 16);
+                }
+            }
+        }
+        header + pixels.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NO_SYNC: u32 = 0x2AB | 0x354 << 10 | 0x354 << 20;
+    const HSYNC: u32 = 0x154 | 0x354 << 10 | 0x354 << 20;
+
+    /// The Game Boy screen, 3 times its size fits 640x480 with an 80x24 border.
+    fn generator() -> ScaledLineGenerator {
+        ScaledLineGenerator::new(DVI_640X480, 160, 144)
+    }
+
+    fn line(line: u32) -> Vec<u32> {
+        let mut out = vec![0xDEAD_BEEF; DVI_640X480.line_words()];
+        let len = generator().write_line(line, &mut out, |x, y| (y * 160 + x) as u16);
+        out.truncate(len);
+        out
+    }
+
+    #[test]
+    fn frame_has_the_640x480_line_sequence() {
+        assert_eq!(DVI_640X480.total_lines(), 525);
+        assert_eq!(DVI_640X480.line_kind(0), LineKind::Blank);
+        assert_eq!(DVI_640X480.line_kind(9), LineKind::Blank);
+        assert_eq!(DVI_640X480.line_kind(10), LineKind::VSync);
+        assert_eq!(DVI_640X480.line_kind(11), LineKind::VSync);
+        assert_eq!(DVI_640X480.line_kind(12), LineKind::Blank);
+        assert_eq!(DVI_640X480.line_kind(44), LineKind::Blank);
+        assert_eq!(DVI_640X480.line_kind(45), LineKind::Active(0));
+        assert_eq!(DVI_640X480.line_kind(524), LineKind::Active(479));
+    }
+
+    #[test]
+    fn blank_line_is_made_of_control_symbols() {
+        assert_eq!(
+            line(0),
+            [
+                HSTX_CMD_RAW_REPEAT | 16,
+                NO_SYNC,
+                HSTX_CMD_RAW_REPEAT | 96,
+                HSYNC,
+                HSTX_CMD_RAW_REPEAT | (48 + 640),
+                NO_SYNC,
+                HSTX_CMD_NOP,
+            ]
+        );
+    }
+
+    #[test]
+    fn vsync_line_holds_vsync_through_the_whole_line() {
+        let vsync = 0x0AB | 0x354 << 10 | 0x354 << 20;
+        let vsync_and_hsync = 0x354 | 0x354 << 10 | 0x354 << 20;
+        let words = line(10);
+        assert_eq!(words[1], vsync);
+        assert_eq!(words[3], vsync_and_hsync);
+        assert_eq!(words[5], vsync);
+    }
+
+    #[test]
+    fn active_line_has_the_sync_header_and_one_word_per_two_pixels() {
+        let words = line(45 + 24);
+        assert_eq!(words.len(), DVI_640X480.line_words());
+        assert_eq!(
+            words[..ACTIVE_HEADER_WORDS],
+            [
+                HSTX_CMD_RAW_REPEAT | 16,
+                NO_SYNC,
+                HSTX_CMD_NOP,
+                HSTX_CMD_RAW_REPEAT | 96,
+                HSYNC,
+                HSTX_CMD_NOP,
+                HSTX_CMD_RAW_REPEAT | 48,
+                NO_SYNC,
+                HSTX_CMD_TMDS | 640,
+            ]
+        );
+    }
+
+    #[test]
+    fn image_is_scaled_and_centred_on_a_black_border() {
+        let generator = generator();
+        assert_eq!(generator.source_row(23), None);
+        assert_eq!(generator.source_row(24), Some(0));
+        assert_eq!(generator.source_row(26), Some(0));
+        assert_eq!(generator.source_row(27), Some(1));
+        assert_eq!(generator.source_row(455), Some(143));
+        assert_eq!(generator.source_row(456), None);
+
+        // Top border
+        let words = line(45);
+        assert!(words[ACTIVE_HEADER_WORDS..].iter().all(|word| *word == 0));
+
+        // Second row of the image, pixels 80 to 559 show the 160 source pixels 3 times each
+        let pixels = &line(45 + 27)[ACTIVE_HEADER_WORDS..];
+        let pixel = |x: usize| {
+            let word = pixels[x / 2];
+            if x % 2 == 0 {
+                word & 0xFFFF
+            } else {
+                word >> 16
+            }
+        };
+        assert_eq!(pixel(79), 0);
+        assert_eq!(pixel(80), 160);
+        assert_eq!(pixel(82), 160);
+        assert_eq!(pixel(83), 161);
+        assert_eq!(pixel(559), 160 + 159);
+        assert_eq!(pixel(560), 0);
+        assert_eq!(pixel(639), 0);
+    }
+}
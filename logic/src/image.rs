@@ -0,0 +1,342 @@
+//! Image files written from captured Game Boy frames, streamed so the whole file never has
+//! to be held in memory.
+
+use alloc::vec::Vec;
+use embedded_graphics_core::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics_core::prelude::IntoStorage;
+
+/// Size of the Game Boy frames the encoders take.
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+/// Nearest neighbour sample of the frame at the given output pixel.
+#[inline(always)]
+fn sample(frame: &[Rgb565], width: usize, height: usize, x: usize, y: usize) -> Rgb565 {
+    let source_x = x * SCREEN_WIDTH / width;
+    let source_y = y * SCREEN_HEIGHT / height;
+    frame[source_y * SCREEN_WIDTH + source_x]
+}
+
+/// Writes a top-down 16 bit RGB565 bitmap.
+pub fn encode_bmp<E>(
+    frame: &[Rgb565],
+    width: usize,
+    height: usize,
+    write: &mut dyn FnMut(&[u8]) -> Result<(), E>,
+) -> Result<(), E> {
+    const HEADER_SIZE: u32 = 14 + 40 + 12;
+    let row_size = (width * 2 + 3) & !3;
+    let image_size = (row_size * height) as u32;
+
+    let mut header: Vec<u8> = Vec::with_capacity(HEADER_SIZE as usize);
+    header.extend_from_slice(b"BM");
+    header.extend_from_slice(&(HEADER_SIZE + image_size).to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(&HEADER_SIZE.to_le_bytes());
+    header.extend_from_slice(&40u32.to_le_bytes());
+    header.extend_from_slice(&(width as i32).to_le_bytes());
+    // A negative height stores the rows top to bottom, the order the frame is in
+    header.extend_from_slice(&(-(height as i32)).to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&16u16.to_le_bytes());
+    // BI_BITFIELDS, the channel masks follow the header
+    header.extend_from_slice(&3u32.to_le_bytes());
+    header.extend_from_slice(&image_size.to_le_bytes());
+    header.extend_from_slice(&2835u32.to_le_bytes());
+    header.extend_from_slice(&2835u32.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(&0xF800u32.to_le_bytes());
+    header.extend_from_slice(&0x07E0u32.to_le_bytes());
+    header.extend_from_slice(&0x001Fu32.to_le_bytes());
+    write(&header)?;
+
+    let mut row: Vec<u8> = alloc::vec![0u8; row_size];
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = sample(frame, width, height, x, y).into_storage();
+            row[x * 2..x * 2 + 2].copy_from_slice(&pixel.to_le_bytes());
+        }
+        write(&row)?;
+    }
+    Ok(())
+}
+
+/// Writes an 8 bit RGB PNG, every row is its own uncompressed deflate block and IDAT chunk so
+/// the image can be streamed without holding it in memory.
+pub fn encode_png<E>(
+    frame: &[Rgb565],
+    width: usize,
+    height: usize,
+    write: &mut dyn FnMut(&[u8]) -> Result<(), E>,
+) -> Result<(), E> {
+    write(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'])?;
+
+    let mut ihdr: Vec<u8> = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+    // Bit depth 8, truecolor, deflate, adaptive filtering, no interlace
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_png_chunk(b"IHDR", &ihdr, write)?;
+
+    let row_size = 1 + width * 3;
+    let mut adler = Adler32::new();
+    let mut data: Vec<u8> = Vec::with_capacity(2 + 5 + row_size);
+    for y in 0..height {
+        data.clear();
+        if y == 0 {
+            // zlib header, deflate with a 32K window and no preset dictionary
+            data.extend_from_slice(&[0x78, 0x01]);
+        }
+        let is_last = y == height - 1;
+        data.push(is_last as u8);
+        data.extend_from_slice(&(row_size as u16).to_le_bytes());
+        data.extend_from_slice(&(!(row_size as u16)).to_le_bytes());
+        let row_start = data.len();
+        // Filter type none
+        data.push(0);
+        for x in 0..width {
+            let pixel = sample(frame, width, height, x, y);
+            data.push((pixel.r() << 3) | (pixel.r() >> 2));
+            data.push((pixel.g() << 2) | (pixel.g() >> 4));
+            data.push((pixel.b() << 3) | (pixel.b() >> 2));
+        }
+        adler.update(&data[row_start..]);
+        if is_last {
+            data.extend_from_slice(&adler.finish().to_be_bytes());
+        }
+        write_png_chunk(b"IDAT", &data, write)?;
+    }
+    write_png_chunk(b"IEND", &[], write)
+}
+
+fn write_png_chunk<E>(
+    kind: &[u8; 4],
+    data: &[u8],
+    write: &mut dyn FnMut(&[u8]) -> Result<(), E>,
+) -> Result<(), E> {
+    write(&(data.len() as u32).to_be_bytes())?;
+    write(kind)?;
+    write(data)?;
+    let crc = !crc32(crc32(0xFFFF_FFFF, kind), data);
+    write(&crc.to_be_bytes())
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+};
+
+fn crc32(mut crc: u32, data: &[u8]) -> u32 {
+    for byte in data {
+        crc = CRC_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Adler32 {
+    const MODULO: u32 = 65521;
+
+    fn new() -> Self {
+        Self { a: 1, b: 0 }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.a = (self.a + *byte as u32) % Self::MODULO;
+            self.b = (self.b + self.a) % Self::MODULO;
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A frame with a different colour in every pixel.
+    fn test_frame() -> Vec<Rgb565> {
+        (0..SCREEN_WIDTH * SCREEN_HEIGHT)
+            .map(|index| {
+                Rgb565::new(
+                    (index % 32) as u8,
+                    (index % 64) as u8,
+                    (index / 5 % 32) as u8,
+                )
+            })
+            .collect()
+    }
+
+    type Encoder =
+        fn(&[Rgb565], usize, usize, &mut dyn FnMut(&[u8]) -> Result<(), ()>) -> Result<(), ()>;
+
+    fn encode(encoder: Encoder, frame: &[Rgb565], width: usize, height: usize) -> Vec<u8> {
+        let mut file = Vec::new();
+        encoder(frame, width, height, &mut |bytes| {
+            file.extend_from_slice(bytes);
+            Ok(())
+        })
+        .unwrap();
+        file
+    }
+
+    fn u32_le(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn u32_be(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn checksums_match_the_reference_values() {
+        assert_eq!(!crc32(0xFFFF_FFFF, b"123456789"), 0xCBF4_3926);
+        let mut adler = Adler32::new();
+        adler.update(b"Wiki");
+        adler.update(b"pedia");
+        assert_eq!(adler.finish(), 0x11E6_0398);
+    }
+
+    #[test]
+    fn bmp_is_a_top_down_rgb565_bitmap() {
+        let frame = test_frame();
+        let file = encode(encode_bmp, &frame, SCREEN_WIDTH, SCREEN_HEIGHT);
+        let data_offset = u32_le(&file, 10) as usize;
+        assert_eq!(&file[..2], b"BM");
+        assert_eq!(u32_le(&file, 2) as usize, file.len());
+        assert_eq!(u32_le(&file, 18), SCREEN_WIDTH as u32);
+        assert_eq!(u32_le(&file, 22) as i32, -(SCREEN_HEIGHT as i32));
+        assert_eq!(file.len(), data_offset + SCREEN_WIDTH * 2 * SCREEN_HEIGHT);
+        for (index, pixel) in frame.iter().enumerate() {
+            let offset = data_offset + index * 2;
+            let stored = u16::from_le_bytes([file[offset], file[offset + 1]]);
+            assert_eq!(stored, pixel.into_storage());
+        }
+    }
+
+    #[test]
+    fn bmp_rows_are_padded_to_four_bytes() {
+        let frame = test_frame();
+        let file = encode(encode_bmp, &frame, 3, 2);
+        let data_offset = u32_le(&file, 10) as usize;
+        assert_eq!(file.len(), data_offset + 8 * 2);
+        assert_eq!(&file[data_offset + 6..data_offset + 8], &[0, 0]);
+    }
+
+    /// Checks the chunks of a PNG and returns the rows inflated from its stored deflate blocks.
+    fn png_rows(file: &[u8]) -> (u32, u32, Vec<Vec<u8>>) {
+        assert_eq!(
+            &file[..8],
+            &[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n']
+        );
+        let mut offset = 8;
+        let mut size = (0, 0);
+        let mut zlib = Vec::new();
+        loop {
+            let length = u32_be(file, offset) as usize;
+            let kind = &file[offset + 4..offset + 8];
+            let data = &file[offset + 8..offset + 8 + length];
+            let crc = u32_be(file, offset + 8 + length);
+            assert_eq!(crc, !crc32(crc32(0xFFFF_FFFF, kind), data));
+            offset += 12 + length;
+            match kind {
+                b"IHDR" => size = (u32_be(data, 0), u32_be(data, 4)),
+                b"IDAT" => zlib.extend_from_slice(data),
+                b"IEND" => break,
+                _ => panic!("unexpected chunk"),
+            }
+        }
+        assert_eq!(offset, file.len());
+
+        assert_eq!(&zlib[..2], &[0x78, 0x01]);
+        let mut position = 2;
+        let mut inflated = Vec::new();
+        loop {
+            let last = zlib[position] & 1 != 0;
+            assert_eq!(zlib[position] & 6, 0, "only stored blocks");
+            let length = u16::from_le_bytes([zlib[position + 1], zlib[position + 2]]);
+            let inverse = u16::from_le_bytes([zlib[position + 3], zlib[position + 4]]);
+            assert_eq!(inverse, !length);
+            position += 5;
+            inflated.extend_from_slice(&zlib[position..position + length as usize]);
+            position += length as usize;
+            if last {
+                break;
+            }
+        }
+        let mut adler = Adler32::new();
+        adler.update(&inflated);
+        assert_eq!(u32_be(&zlib, position), adler.finish());
+        assert_eq!(position + 4, zlib.len());
+
+        let row_size = 1 + size.0 as usize * 3;
+        let rows = inflated.chunks(row_size).map(<[u8]>::to_vec).collect();
+        (size.0, size.1, rows)
+    }
+
+    #[test]
+    fn png_holds_the_frame_as_8_bit_rgb() {
+        let frame = test_frame();
+        let file = encode(encode_png, &frame, SCREEN_WIDTH, SCREEN_HEIGHT);
+        let (width, height, rows) = png_rows(&file);
+        assert_eq!(
+            (width as usize, height as usize),
+            (SCREEN_WIDTH, SCREEN_HEIGHT)
+        );
+        assert_eq!(rows.len(), SCREEN_HEIGHT);
+        for (y, row) in rows.iter().enumerate() {
+            assert_eq!(row[0], 0, "filter type");
+            for x in 0..SCREEN_WIDTH {
+                let pixel = frame[y * SCREEN_WIDTH + x];
+                let rgb = &row[1 + x * 3..4 + x * 3];
+                assert_eq!(rgb[0] >> 3, pixel.r());
+                assert_eq!(rgb[1] >> 2, pixel.g());
+                assert_eq!(rgb[2] >> 3, pixel.b());
+            }
+        }
+        // Full intensity stays full intensity
+        let white = [Rgb565::WHITE; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let (_, _, rows) = png_rows(&encode(encode_png, &white, SCREEN_WIDTH, SCREEN_HEIGHT));
+        assert!(rows
+            .iter()
+            .all(|row| row[1..].iter().all(|value| *value == 255)));
+    }
+
+    #[test]
+    fn scaled_output_repeats_the_nearest_pixel() {
+        let frame = test_frame();
+        let width = SCREEN_WIDTH * 2;
+        let height = SCREEN_HEIGHT * 3;
+        let file = encode(encode_bmp, &frame, width, height);
+        let data_offset = u32_le(&file, 10) as usize;
+        for (y, x) in [(0, 0), (2, 1), (3, 2), (height - 1, width - 1)] {
+            let offset = data_offset + (y * width + x) * 2;
+            let stored = u16::from_le_bytes([file[offset], file[offset + 1]]);
+            let source = frame[y / 3 * SCREEN_WIDTH + x / 2];
+            assert_eq!(stored, source.into_storage(), "pixel {},{}", x, y);
+        }
+    }
+}
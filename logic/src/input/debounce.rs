@@ -0,0 +1,76 @@
+use super::INPUT_COUNT;
+
+/// Debounces the merged inputs.
+///
+/// A change is taken on its first edge so presses don't lag, the bouncing that follows is
+/// ignored for the debounce time.
+pub struct DebounceFilter {
+    debounce_us: u64,
+    /// Debounced inputs, one bit per `INPUT_*`.
+    stable: u32,
+    /// When every input last changed, in microseconds.
+    changed_at: [u64; INPUT_COUNT],
+}
+
+impl DebounceFilter {
+    pub fn new(debounce_ms: u64) -> Self {
+        Self {
+            debounce_us: debounce_ms * 1000,
+            stable: 0,
+            changed_at: [0; INPUT_COUNT],
+        }
+    }
+
+    /// Takes the raw inputs read at `now_us`, returns the debounced ones.
+    #[inline(always)]
+    pub fn update(&mut self, raw: u32, now_us: u64) -> u32 {
+        let mut changed = raw ^ self.stable;
+        while changed != 0 {
+            let input = changed.trailing_zeros() as usize;
+            changed &= changed - 1;
+            if now_us - self.changed_at[input] >= self.debounce_us {
+                self.stable ^= 1 << input;
+                self.changed_at[input] = now_us;
+            }
+        }
+        self.stable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1000;
+    const A: u32 = 1 << 0;
+    const B: u32 = 1 << 1;
+
+    #[test]
+    fn first_edge_is_taken_straight_away() {
+        let mut filter = DebounceFilter::new(10);
+        assert_eq!(filter.update(A, 100 * MS), A);
+    }
+
+    #[test]
+    fn bouncing_is_ignored_for_the_debounce_time() {
+        let mut filter = DebounceFilter::new(10);
+        assert_eq!(filter.update(A, 100 * MS), A);
+        assert_eq!(filter.update(0, 101 * MS), A);
+        assert_eq!(filter.update(A, 102 * MS), A);
+        assert_eq!(filter.update(0, 109 * MS), A);
+        assert_eq!(filter.update(0, 110 * MS), 0);
+        assert_eq!(filter.update(A, 115 * MS), 0);
+        assert_eq!(filter.update(A, 120 * MS), A);
+    }
+
+    #[test]
+    fn inputs_are_debounced_separately() {
+        let mut filter = DebounceFilter::new(10);
+        assert_eq!(filter.update(A, 100 * MS), A);
+        assert_eq!(filter.update(A | B, 105 * MS), A | B);
+        assert_eq!(filter.update(B, 106 * MS), A | B);
+        assert_eq!(filter.update(B, 110 * MS), B);
+        assert_eq!(filter.update(0, 112 * MS), B);
+        assert_eq!(filter.update(0, 115 * MS), 0);
+    }
+}
@@ -0,0 +1,127 @@
+/// How long a button is held before it counts as held and starts repeating.
+const HOLD_MS: u64 = 400;
+/// Time between the repeats of a held button.
+const REPEAT_MS: u64 = 100;
+
+/// What happened to the buttons since the last update, one bit per button in every mask.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct ButtonEvents {
+    pub held: u32,
+    pub pressed: u32,
+    pub released: u32,
+    /// Buttons held for `HOLD_MS` on this update.
+    pub long_pressed: u32,
+    /// Pressed buttons and held buttons every `REPEAT_MS`, for scrolling through lists.
+    pub repeated: u32,
+}
+
+/// Turns the held buttons into press, hold and repeat events.
+#[derive(Default)]
+pub struct ButtonTracker {
+    held: u32,
+    /// When a button was last pressed, all held buttons repeat together from then on.
+    pressed_at: u64,
+    next_repeat: u64,
+    long_pressed: bool,
+}
+
+impl ButtonTracker {
+    pub fn update(&mut self, held: u32, now_us: u64) -> ButtonEvents {
+        let pressed = held & !self.held;
+        let released = self.held & !held;
+        self.held = held;
+        if pressed != 0 {
+            self.pressed_at = now_us;
+            self.next_repeat = now_us + HOLD_MS * 1000;
+            self.long_pressed = false;
+        }
+
+        let mut events = ButtonEvents {
+            held,
+            pressed,
+            released,
+            long_pressed: 0,
+            repeated: pressed,
+        };
+        if held == 0 {
+            return events;
+        }
+        if !self.long_pressed && now_us - self.pressed_at >= HOLD_MS * 1000 {
+            self.long_pressed = true;
+            events.long_pressed = held;
+        }
+        if now_us >= self.next_repeat {
+            self.next_repeat = now_us + REPEAT_MS * 1000;
+            events.repeated |= held;
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1000;
+    const A: u32 = 1 << 0;
+    const B: u32 = 1 << 1;
+
+    #[test]
+    fn press_and_release() {
+        let mut tracker = ButtonTracker::default();
+        let events = tracker.update(A, 1000 * MS);
+        assert_eq!(
+            events,
+            ButtonEvents {
+                held: A,
+                pressed: A,
+                released: 0,
+                long_pressed: 0,
+                repeated: A,
+            }
+        );
+        let events = tracker.update(A, 1010 * MS);
+        assert_eq!((events.pressed, events.repeated), (0, 0));
+        let events = tracker.update(0, 1020 * MS);
+        assert_eq!(
+            events,
+            ButtonEvents {
+                released: A,
+                ..ButtonEvents::default()
+            }
+        );
+    }
+
+    #[test]
+    fn held_button_long_presses_once_and_repeats() {
+        let mut tracker = ButtonTracker::default();
+        tracker.update(A, 0);
+        assert_eq!(tracker.update(A, (HOLD_MS - 1) * MS).repeated, 0);
+        let events = tracker.update(A, HOLD_MS * MS);
+        assert_eq!((events.long_pressed, events.repeated), (A, A));
+        let events = tracker.update(A, (HOLD_MS + REPEAT_MS - 1) * MS);
+        assert_eq!((events.long_pressed, events.repeated), (0, 0));
+        let events = tracker.update(A, (HOLD_MS + REPEAT_MS) * MS);
+        assert_eq!((events.long_pressed, events.repeated), (0, A));
+    }
+
+    #[test]
+    fn new_press_restarts_the_hold_time() {
+        let mut tracker = ButtonTracker::default();
+        tracker.update(A, 0);
+        let events = tracker.update(A | B, 300 * MS);
+        assert_eq!((events.pressed, events.repeated), (B, B));
+        assert_eq!(tracker.update(A | B, HOLD_MS * MS).long_pressed, 0);
+        let events = tracker.update(A | B, (300 + HOLD_MS) * MS);
+        assert_eq!((events.long_pressed, events.repeated), (A | B, A | B));
+    }
+
+    #[test]
+    fn nothing_held_gives_no_hold_events() {
+        let mut tracker = ButtonTracker::default();
+        tracker.update(A, 0);
+        tracker.update(0, 10 * MS);
+        let events = tracker.update(0, 10_000 * MS);
+        assert_eq!(events, ButtonEvents::default());
+    }
+}
@@ -0,0 +1,290 @@
+use super::mapping::InputTarget;
+use crate::action::EmulatorAction;
+
+/// How long a press that turned out not to be a hotkey is shown to the game once released.
+const TAP_MS: u64 = 50;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyTrigger {
+    /// The second target pressed while the first one is held.
+    Chord(InputTarget, InputTarget),
+    /// The target held for the long press time of the [`HotkeyEngine`].
+    LongPress(InputTarget),
+}
+
+impl HotkeyTrigger {
+    /// The target the hotkey starts with, it is kept from the game until the hotkey is
+    /// recognised or ruled out.
+    fn first(self) -> InputTarget {
+        match self {
+            HotkeyTrigger::Chord(modifier, _) => modifier,
+            HotkeyTrigger::LongPress(target) => target,
+        }
+    }
+}
+
+pub struct Hotkey {
+    pub trigger: HotkeyTrigger,
+    pub action: EmulatorAction,
+}
+
+pub const HOTKEYS: [Hotkey; 7] = [
+    Hotkey {
+        trigger: HotkeyTrigger::Chord(InputTarget::Select, InputTarget::Start),
+        action: EmulatorAction::OpenMenu,
+    },
+    Hotkey {
+        trigger: HotkeyTrigger::LongPress(InputTarget::Select),
+        action: EmulatorAction::OpenMenu,
+    },
+    Hotkey {
+        trigger: HotkeyTrigger::Chord(InputTarget::Select, InputTarget::B),
+        action: EmulatorAction::Screenshot,
+    },
+    Hotkey {
+        trigger: HotkeyTrigger::Chord(InputTarget::Select, InputTarget::Up),
+        action: EmulatorAction::VolumeUp,
+    },
+    Hotkey {
+        trigger: HotkeyTrigger::Chord(InputTarget::Select, InputTarget::Down),
+        action: EmulatorAction::VolumeDown,
+    },
+    Hotkey {
+        trigger: HotkeyTrigger::Chord(InputTarget::Select, InputTarget::Left),
+        action: EmulatorAction::ToggleMute,
+    },
+    Hotkey {
+        trigger: HotkeyTrigger::Chord(InputTarget::Select, InputTarget::Right),
+        action: EmulatorAction::ToggleRecording,
+    },
+];
+
+/// Recognises the [`HOTKEYS`] in the held targets and hides the presses that belong to them
+/// from the game.
+///
+/// The first target of a hotkey is held back when pressed. It stays hidden for good once a
+/// hotkey completes, a quick press that didn't complete one is shown to the game as a short
+/// tap after its release, and a longer one is handed to the game after the chord window unless
+/// a long press can still follow.
+pub struct HotkeyEngine {
+    /// How long a hotkey target has to be held for a long press.
+    long_press_ms: u64,
+    /// How long the first target of a chord is kept from the game while waiting for the rest.
+    chord_window_ms: u64,
+    /// Targets held at the last update.
+    held: u32,
+    /// Targets held back while a hotkey may still follow.
+    pending: u32,
+    pending_since_us: u64,
+    /// Targets that completed a hotkey, hidden until released.
+    consumed: u32,
+    /// Released pending targets shown to the game for `TAP_MS`.
+    tap: u32,
+    tap_since_us: u64,
+}
+
+impl HotkeyEngine {
+    pub fn new(long_press_ms: u64, chord_window_ms: u64) -> Self {
+        Self {
+            long_press_ms,
+            chord_window_ms,
+            held: 0,
+            pending: 0,
+            pending_since_us: 0,
+            consumed: 0,
+            tap: 0,
+            tap_since_us: 0,
+        }
+    }
+
+    /// True while the result of [`HotkeyEngine::update`] can change without the targets
+    /// changing.
+    #[inline(always)]
+    pub fn waiting(&self) -> bool {
+        self.pending != 0 || self.tap != 0
+    }
+
+    /// Takes the held targets, returns the targets the game should see and the recognised
+    /// hotkey, if any.
+    pub fn update(&mut self, targets: u32, now_us: u64) -> (u32, Option<EmulatorAction>) {
+        let pressed = targets & !self.held;
+        let released = self.held & !targets;
+        self.held = targets;
+        self.consumed &= targets;
+
+        let tapped = self.pending & released;
+        if tapped != 0 {
+            self.tap |= tapped;
+            self.tap_since_us = now_us;
+        }
+        self.pending &= targets;
+
+        let first_targets = HOTKEYS
+            .iter()
+            .fold(0, |mask, hotkey| mask | hotkey.trigger.first().bit());
+        let started = pressed & first_targets & !self.consumed;
+        if started != 0 {
+            if self.pending == 0 {
+                self.pending_since_us = now_us;
+            }
+            self.pending |= started;
+        }
+
+        let mut action = None;
+        let modifiers = self.pending | self.consumed;
+        for hotkey in HOTKEYS.iter() {
+            if let HotkeyTrigger::Chord(modifier, key) = hotkey.trigger {
+                if modifiers & modifier.bit() != 0 && pressed & key.bit() != 0 {
+                    action = Some(hotkey.action);
+                    self.complete(modifier.bit() | key.bit());
+                }
+            }
+        }
+
+        let waited_ms = now_us.saturating_sub(self.pending_since_us) / 1000;
+        if self.pending != 0 && waited_ms >= self.long_press_ms {
+            for hotkey in HOTKEYS.iter() {
+                if let HotkeyTrigger::LongPress(target) = hotkey.trigger {
+                    if self.pending & target.bit() != 0 {
+                        action = Some(hotkey.action);
+                        self.complete(target.bit());
+                    }
+                }
+            }
+        }
+        if self.pending != 0 && waited_ms >= self.chord_window_ms {
+            // Only a long press can still follow, everything else goes to the game
+            self.pending &= long_press_targets();
+        }
+        if self.tap != 0 && now_us.saturating_sub(self.tap_since_us) / 1000 >= TAP_MS {
+            self.tap = 0;
+        }
+
+        (
+            (targets & !self.pending & !self.consumed) | self.tap,
+            action,
+        )
+    }
+
+    fn complete(&mut self, targets: u32) {
+        self.consumed |= targets & self.held;
+        self.pending &= !targets;
+    }
+}
+
+fn long_press_targets() -> u32 {
+    HOTKEYS.iter().fold(0, |mask, hotkey| match hotkey.trigger {
+        HotkeyTrigger::LongPress(target) => mask | target.bit(),
+        _ => mask,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1000;
+    const SELECT: u32 = 1 << 2;
+    const START: u32 = 1 << 3;
+
+    fn engine() -> HotkeyEngine {
+        HotkeyEngine::new(1000, 250)
+    }
+
+    fn bits(targets: &[InputTarget]) -> u32 {
+        targets.iter().fold(0, |mask, target| mask | target.bit())
+    }
+
+    #[test]
+    fn other_targets_go_straight_to_the_game() {
+        let mut hotkeys = engine();
+        let held = bits(&[InputTarget::A, InputTarget::Up]);
+        assert_eq!(hotkeys.update(held, 0), (held, None));
+        assert!(!hotkeys.waiting());
+        assert_eq!(hotkeys.update(0, 10 * MS), (0, None));
+    }
+
+    #[test]
+    fn chord_runs_its_action_and_hides_both_targets() {
+        assert_eq!(InputTarget::Select.bit(), SELECT);
+        let mut hotkeys = engine();
+        assert_eq!(hotkeys.update(SELECT, 0), (0, None));
+        assert!(hotkeys.waiting());
+        let chord = SELECT | InputTarget::B.bit();
+        assert_eq!(
+            hotkeys.update(chord, 100 * MS),
+            (0, Some(EmulatorAction::Screenshot))
+        );
+        // Still hidden after the chord window and once one of them is released
+        assert_eq!(hotkeys.update(chord, 2000 * MS), (0, None));
+        assert_eq!(hotkeys.update(InputTarget::B.bit(), 2100 * MS), (0, None));
+        assert_eq!(hotkeys.update(0, 2200 * MS), (0, None));
+        assert!(!hotkeys.waiting());
+    }
+
+    #[test]
+    fn the_modifier_can_start_several_chords() {
+        let mut hotkeys = engine();
+        hotkeys.update(SELECT, 0);
+        let up = SELECT | InputTarget::Up.bit();
+        assert_eq!(
+            hotkeys.update(up, 50 * MS),
+            (0, Some(EmulatorAction::VolumeUp))
+        );
+        assert_eq!(hotkeys.update(SELECT, 100 * MS), (0, None));
+        assert_eq!(
+            hotkeys.update(up, 150 * MS),
+            (0, Some(EmulatorAction::VolumeUp))
+        );
+        assert_eq!(
+            hotkeys.update(SELECT | START, 200 * MS),
+            (0, Some(EmulatorAction::OpenMenu))
+        );
+    }
+
+    #[test]
+    fn quick_press_reaches_the_game_as_a_tap() {
+        let mut hotkeys = engine();
+        assert_eq!(hotkeys.update(SELECT, 0), (0, None));
+        assert_eq!(hotkeys.update(0, 100 * MS), (SELECT, None));
+        assert!(hotkeys.waiting());
+        assert_eq!(hotkeys.update(0, 149 * MS), (SELECT, None));
+        assert_eq!(hotkeys.update(0, 150 * MS), (0, None));
+        assert!(!hotkeys.waiting());
+    }
+
+    #[test]
+    fn long_press_waits_for_the_hold_time() {
+        let mut hotkeys = engine();
+        hotkeys.update(SELECT, 0);
+        // A long press can still follow, so SELECT stays hidden past the chord window
+        assert_eq!(hotkeys.update(SELECT, 500 * MS), (0, None));
+        assert_eq!(hotkeys.update(SELECT, 999 * MS), (0, None));
+        assert_eq!(
+            hotkeys.update(SELECT, 1000 * MS),
+            (0, Some(EmulatorAction::OpenMenu))
+        );
+        assert_eq!(hotkeys.update(SELECT, 3000 * MS), (0, None));
+        assert_eq!(hotkeys.update(0, 3100 * MS), (0, None));
+    }
+
+    #[test]
+    fn timings_come_from_the_constructor() {
+        let mut hotkeys = HotkeyEngine::new(200, 100);
+        hotkeys.update(SELECT, 0);
+        assert_eq!(hotkeys.update(SELECT, 199 * MS), (0, None));
+        assert_eq!(
+            hotkeys.update(SELECT, 200 * MS),
+            (0, Some(EmulatorAction::OpenMenu))
+        );
+    }
+
+    #[test]
+    fn game_buttons_held_with_the_modifier_are_not_hidden() {
+        let mut hotkeys = engine();
+        let a = InputTarget::A.bit();
+        assert_eq!(hotkeys.update(a, 0), (a, None));
+        assert_eq!(hotkeys.update(a | SELECT, 10 * MS), (a, None));
+        assert_eq!(hotkeys.update(a, 20 * MS), (a | SELECT, None));
+    }
+}
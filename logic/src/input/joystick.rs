@@ -0,0 +1,277 @@
+use alloc::string::String;
+
+use super::{INPUT_DOWN, INPUT_LEFT, INPUT_RIGHT, INPUT_UP};
+use crate::config::parse_bool;
+
+/// Highest reading of the 12 bit ADC.
+pub const ADC_MAX: u16 = 4095;
+const DEADZONE_MIN: u8 = 5;
+const DEADZONE_MAX: u8 = 90;
+const DEADZONE_STEP: u8 = 5;
+/// Smallest distance between the centre and an extent a calibration is kept with, a stick
+/// that barely moved while calibrating would turn the noise of the ADC into presses.
+const MIN_TRAVEL: u16 = 256;
+
+pub const AXIS_X: usize = 0;
+pub const AXIS_Y: usize = 1;
+
+/// Where the stick rests and how far it reaches, as ADC readings indexed by `AXIS_*`, and
+/// when its position counts as a direction. Low readings are UP and LEFT.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct JoystickCalibration {
+    pub center: [u16; 2],
+    pub min: [u16; 2],
+    pub max: [u16; 2],
+    /// Part of the travel around the centre that is ignored, in percent.
+    pub deadzone: u8,
+    /// Between the axes the stick holds two directions at once, otherwise only the closest
+    /// one.
+    pub diagonals: bool,
+}
+
+impl Default for JoystickCalibration {
+    fn default() -> Self {
+        Self {
+            center: [ADC_MAX / 2 + 1; 2],
+            min: [0; 2],
+            max: [ADC_MAX; 2],
+            deadzone: 30,
+            diagonals: true,
+        }
+    }
+}
+
+impl JoystickCalibration {
+    pub fn wider_deadzone(&mut self) {
+        self.deadzone = (self.deadzone + DEADZONE_STEP).min(DEADZONE_MAX);
+    }
+
+    pub fn narrower_deadzone(&mut self) {
+        self.deadzone = self
+            .deadzone
+            .saturating_sub(DEADZONE_STEP)
+            .max(DEADZONE_MIN);
+    }
+
+    /// Whether the stick travels far enough from the centre in every direction.
+    pub fn is_valid(&self) -> bool {
+        [AXIS_X, AXIS_Y].iter().all(|&axis| {
+            self.center[axis].saturating_sub(self.min[axis]) >= MIN_TRAVEL
+                && self.max[axis].saturating_sub(self.center[axis]) >= MIN_TRAVEL
+        })
+    }
+
+    /// Returns the directions the stick at `position` holds as a mask of `1 << INPUT_*` bits.
+    #[inline(always)]
+    pub fn directions(&self, position: [u16; 2]) -> u32 {
+        let x = self.deflection(AXIS_X, position[AXIS_X]);
+        let y = self.deflection(AXIS_Y, position[AXIS_Y]);
+        let (distance_x, distance_y) = (x.abs(), y.abs());
+        if distance_x.max(distance_y) <= self.deadzone as i32 {
+            return 0;
+        }
+        let (horizontal, vertical) = if self.diagonals {
+            // Eight sectors of 45 degrees, the other axis joins in once it's more than
+            // tan(22.5°) (about 2/5) of the main one
+            (
+                distance_x * 5 >= distance_y * 2,
+                distance_y * 5 >= distance_x * 2,
+            )
+        } else {
+            (distance_x >= distance_y, distance_y > distance_x)
+        };
+        let mut inputs = 0;
+        if horizontal {
+            inputs |= 1 << if x < 0 { INPUT_LEFT } else { INPUT_RIGHT };
+        }
+        if vertical {
+            inputs |= 1 << if y < 0 { INPUT_UP } else { INPUT_DOWN };
+        }
+        inputs
+    }
+
+    /// How far the stick is from the centre on an axis, from -100 to 100 percent of its
+    /// travel on that side.
+    #[inline(always)]
+    fn deflection(&self, axis: usize, reading: u16) -> i32 {
+        let center = self.center[axis] as i32;
+        let reading = reading as i32;
+        let extent = if reading >= center {
+            self.max[axis]
+        } else {
+            self.min[axis]
+        } as i32;
+        let travel = (extent - center).abs().max(1);
+        ((reading - center) * 100 / travel).clamp(-100, 100)
+    }
+
+    /// Applies a `JOYSTICK_*` setting, returns `None` when the key is not one and
+    /// `Some(false)` when the value is invalid. The centre and extents are `x,y` pairs.
+    pub fn apply(&mut self, key: &str, value: &str) -> Option<bool> {
+        let valid = match key.strip_prefix("JOYSTICK_")? {
+            "CENTER" => parse_axes(value).map(|axes| self.center = axes).is_some(),
+            "MIN" => parse_axes(value).map(|axes| self.min = axes).is_some(),
+            "MAX" => parse_axes(value).map(|axes| self.max = axes).is_some(),
+            "DEADZONE" => match value.parse::<u8>() {
+                Ok(deadzone) if (DEADZONE_MIN..=DEADZONE_MAX).contains(&deadzone) => {
+                    self.deadzone = deadzone;
+                    true
+                }
+                _ => false,
+            },
+            "DIAGONALS" => parse_bool(value)
+                .map(|diagonals| self.diagonals = diagonals)
+                .is_some(),
+            _ => false,
+        };
+        Some(valid)
+    }
+
+    /// `JOYSTICK_` lines, none when the stick was never calibrated or changed.
+    pub fn to_config(&self) -> String {
+        if *self == Self::default() {
+            return String::new();
+        }
+        alloc::format!(
+            "JOYSTICK_CENTER={},{}\nJOYSTICK_MIN={},{}\nJOYSTICK_MAX={},{}\nJOYSTICK_DEADZONE={}\nJOYSTICK_DIAGONALS={}\n",
+            self.center[AXIS_X],
+            self.center[AXIS_Y],
+            self.min[AXIS_X],
+            self.min[AXIS_Y],
+            self.max[AXIS_X],
+            self.max[AXIS_Y],
+            self.deadzone,
+            self.diagonals
+        )
+    }
+}
+
+fn parse_axes(value: &str) -> Option<[u16; 2]> {
+    let (x, y) = value.split_once(',')?;
+    let x = x.trim().parse::<u16>().ok().filter(|x| *x <= ADC_MAX)?;
+    let y = y.trim().parse::<u16>().ok().filter(|y| *y <= ADC_MAX)?;
+    Some([x, y])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UP: u32 = 1 << INPUT_UP;
+    const DOWN: u32 = 1 << INPUT_DOWN;
+    const LEFT: u32 = 1 << INPUT_LEFT;
+    const RIGHT: u32 = 1 << INPUT_RIGHT;
+
+    /// A stick resting off centre that doesn't reach the ends of the ADC range.
+    fn calibration() -> JoystickCalibration {
+        JoystickCalibration {
+            center: [2000, 2100],
+            min: [1000, 1100],
+            max: [3000, 3100],
+            deadzone: 30,
+            diagonals: true,
+        }
+    }
+
+    #[test]
+    fn centre_and_deadzone_hold_nothing() {
+        let calibration = calibration();
+        assert_eq!(calibration.directions([2000, 2100]), 0);
+        // 30% of the 1000 readings on either side
+        assert_eq!(calibration.directions([2300, 2100]), 0);
+        assert_eq!(calibration.directions([1700, 1800]), 0);
+        assert_eq!(calibration.directions([2310, 2100]), RIGHT);
+        assert_eq!(calibration.directions([1690, 2100]), LEFT);
+    }
+
+    #[test]
+    fn low_readings_are_up_and_left() {
+        let calibration = calibration();
+        assert_eq!(calibration.directions([2000, 1100]), UP);
+        assert_eq!(calibration.directions([2000, 3100]), DOWN);
+        assert_eq!(calibration.directions([1000, 2100]), LEFT);
+        assert_eq!(calibration.directions([3000, 2100]), RIGHT);
+        // Past the calibrated extents counts as fully deflected
+        assert_eq!(calibration.directions([0, 2100]), LEFT);
+        assert_eq!(calibration.directions([4095, 2100]), RIGHT);
+    }
+
+    #[test]
+    fn diagonals_start_past_22_5_degrees() {
+        let calibration = calibration();
+        assert_eq!(calibration.directions([3000, 3100]), RIGHT | DOWN);
+        assert_eq!(calibration.directions([1000, 1100]), LEFT | UP);
+        // 100% right and 40% up is just past tan(22.5°)
+        assert_eq!(calibration.directions([3000, 1700]), RIGHT | UP);
+        assert_eq!(calibration.directions([3000, 1710]), RIGHT);
+    }
+
+    #[test]
+    fn four_way_picks_the_closest_direction() {
+        let calibration = JoystickCalibration {
+            diagonals: false,
+            ..calibration()
+        };
+        assert_eq!(calibration.directions([3000, 3000]), RIGHT);
+        assert_eq!(calibration.directions([2900, 3100]), DOWN);
+        assert_eq!(calibration.directions([1000, 1500]), LEFT);
+        // Exactly between the axes goes horizontal
+        assert_eq!(calibration.directions([3000, 3100]), RIGHT);
+    }
+
+    #[test]
+    fn short_travel_is_not_a_valid_calibration() {
+        assert!(calibration().is_valid());
+        assert!(JoystickCalibration::default().is_valid());
+        let barely_moved = JoystickCalibration {
+            min: [1900, 1100],
+            ..calibration()
+        };
+        assert!(!barely_moved.is_valid());
+    }
+
+    #[test]
+    fn deadzone_steps_stay_in_range() {
+        let mut calibration = calibration();
+        calibration.wider_deadzone();
+        assert_eq!(calibration.deadzone, 35);
+        for _ in 0..20 {
+            calibration.wider_deadzone();
+        }
+        assert_eq!(calibration.deadzone, DEADZONE_MAX);
+        for _ in 0..20 {
+            calibration.narrower_deadzone();
+        }
+        assert_eq!(calibration.deadzone, DEADZONE_MIN);
+    }
+
+    #[test]
+    fn config_round_trips() {
+        assert_eq!(JoystickCalibration::default().to_config(), "");
+        let calibration = JoystickCalibration {
+            diagonals: false,
+            deadzone: 25,
+            ..calibration()
+        };
+        let config = calibration.to_config();
+        let mut loaded = JoystickCalibration::default();
+        for (key, value) in crate::config::config_entries(&config) {
+            assert_eq!(loaded.apply(key, value), Some(true), "{}", key);
+        }
+        assert_eq!(loaded, calibration);
+    }
+
+    #[test]
+    fn apply_rejects_invalid_settings() {
+        let mut calibration = JoystickCalibration::default();
+        assert_eq!(calibration.apply("VOLUME", "5"), None);
+        assert_eq!(calibration.apply("JOYSTICK_CENTER", "2000"), Some(false));
+        assert_eq!(calibration.apply("JOYSTICK_MAX", "4096,4000"), Some(false));
+        assert_eq!(calibration.apply("JOYSTICK_DEADZONE", "95"), Some(false));
+        assert_eq!(calibration.apply("JOYSTICK_DIAGONALS", "yes"), Some(false));
+        assert_eq!(calibration.apply("JOYSTICK_SPEED", "1"), Some(false));
+        assert_eq!(calibration, JoystickCalibration::default());
+        assert_eq!(calibration.apply("JOYSTICK_MIN", " 10 , 20 "), Some(true));
+        assert_eq!(calibration.min, [10, 20]);
+    }
+}
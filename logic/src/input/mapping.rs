@@ -0,0 +1,337 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::{input_from_name, INPUT_COUNT, INPUT_EXTRA, INPUT_NAMES};
+use crate::action::EmulatorAction;
+
+/// What a physical input can control.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InputTarget {
+    A,
+    B,
+    Select,
+    Start,
+    Up,
+    Down,
+    Left,
+    Right,
+    /// A and B pressed repeatedly at the turbo rate while held.
+    TurboA,
+    TurboB,
+    /// Runs the emulation as fast as it can while held.
+    FastForward,
+    Screenshot,
+    VolumeUp,
+    VolumeDown,
+    ToggleMute,
+    ToggleRecording,
+    Menu,
+}
+
+pub const TARGET_COUNT: usize = 17;
+
+pub const TARGETS: [InputTarget; TARGET_COUNT] = [
+    InputTarget::A,
+    InputTarget::B,
+    InputTarget::Select,
+    InputTarget::Start,
+    InputTarget::Up,
+    InputTarget::Down,
+    InputTarget::Left,
+    InputTarget::Right,
+    InputTarget::TurboA,
+    InputTarget::TurboB,
+    InputTarget::FastForward,
+    InputTarget::Screenshot,
+    InputTarget::VolumeUp,
+    InputTarget::VolumeDown,
+    InputTarget::ToggleMute,
+    InputTarget::ToggleRecording,
+    InputTarget::Menu,
+];
+
+impl InputTarget {
+    /// Name used in the settings files, after `MAP_`.
+    pub fn name(self) -> &'static str {
+        match self {
+            InputTarget::A => "A",
+            InputTarget::B => "B",
+            InputTarget::Select => "SELECT",
+            InputTarget::Start => "START",
+            InputTarget::Up => "UP",
+            InputTarget::Down => "DOWN",
+            InputTarget::Left => "LEFT",
+            InputTarget::Right => "RIGHT",
+            InputTarget::TurboA => "TURBO_A",
+            InputTarget::TurboB => "TURBO_B",
+            InputTarget::FastForward => "FAST_FORWARD",
+            InputTarget::Screenshot => "SCREENSHOT",
+            InputTarget::VolumeUp => "VOLUME_UP",
+            InputTarget::VolumeDown => "VOLUME_DOWN",
+            InputTarget::ToggleMute => "MUTE",
+            InputTarget::ToggleRecording => "RECORD",
+            InputTarget::Menu => "MENU",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            InputTarget::A => "A",
+            InputTarget::B => "B",
+            InputTarget::Select => "Select",
+            InputTarget::Start => "Start",
+            InputTarget::Up => "Up",
+            InputTarget::Down => "Down",
+            InputTarget::Left => "Left",
+            InputTarget::Right => "Right",
+            InputTarget::TurboA => "Turbo A",
+            InputTarget::TurboB => "Turbo B",
+            InputTarget::FastForward => "Fast fwd",
+            InputTarget::Screenshot => "Screenshot",
+            InputTarget::VolumeUp => "Volume +",
+            InputTarget::VolumeDown => "Volume -",
+            InputTarget::ToggleMute => "Mute",
+            InputTarget::ToggleRecording => "Record",
+            InputTarget::Menu => "Menu",
+        }
+    }
+
+    /// Bit of the target in the masks returned by [`ButtonMapping::targets`].
+    pub fn bit(self) -> u32 {
+        1 << TARGETS.iter().position(|target| *target == self).unwrap()
+    }
+
+    /// The action requested when the target is pressed.
+    pub fn action(self) -> Option<EmulatorAction> {
+        match self {
+            InputTarget::Screenshot => Some(EmulatorAction::Screenshot),
+            InputTarget::VolumeUp => Some(EmulatorAction::VolumeUp),
+            InputTarget::VolumeDown => Some(EmulatorAction::VolumeDown),
+            InputTarget::ToggleMute => Some(EmulatorAction::ToggleMute),
+            InputTarget::ToggleRecording => Some(EmulatorAction::ToggleRecording),
+            InputTarget::Menu => Some(EmulatorAction::OpenMenu),
+            _ => None,
+        }
+    }
+}
+
+/// Physical inputs of every target, any number of inputs can control the same target and one
+/// input can control several targets.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ButtonMapping {
+    /// Mask of `1 << INPUT_*` bits per entry of `TARGETS`.
+    inputs: [u32; TARGET_COUNT],
+}
+
+impl Default for ButtonMapping {
+    /// Every Game Boy button on the input with the same name, actions are left to the SELECT
+    /// hotkeys.
+    fn default() -> Self {
+        Self {
+            inputs: core::array::from_fn(|index| default_inputs(TARGETS[index])),
+        }
+    }
+}
+
+impl ButtonMapping {
+    pub fn inputs(&self, target: InputTarget) -> u32 {
+        self.inputs[index(target)]
+    }
+
+    /// Adds `input` to the target, or removes it when it was already there.
+    pub fn toggle(&mut self, target: InputTarget, input: u8) {
+        self.inputs[index(target)] ^= 1 << input;
+    }
+
+    pub fn clear(&mut self, target: InputTarget) {
+        self.inputs[index(target)] = 0;
+    }
+
+    pub fn reset(&mut self, target: InputTarget) {
+        self.inputs[index(target)] = default_inputs(target);
+    }
+
+    /// Turns the held inputs into the held targets, one bit per entry of `TARGETS`.
+    #[inline(always)]
+    pub fn targets(&self, inputs: u32) -> u32 {
+        let mut targets = 0;
+        for (index, mask) in self.inputs.iter().enumerate() {
+            if inputs & mask != 0 {
+                targets |= 1 << index;
+            }
+        }
+        targets
+    }
+
+    /// Names of the inputs of a target, such as `A, EXTRA1`.
+    pub fn describe(&self, target: InputTarget) -> String {
+        let names: Vec<&str> = input_names(self.inputs(target)).collect();
+        if names.is_empty() {
+            "-".into()
+        } else {
+            names.join(", ")
+        }
+    }
+
+    /// Applies a `MAP_<target>=<input>,<input>` setting, returns `None` when the key is not
+    /// a mapping and `Some(false)` when the value is invalid. An empty value unbinds the
+    /// target.
+    pub fn apply(&mut self, key: &str, value: &str) -> Option<bool> {
+        let name = key.strip_prefix("MAP_")?;
+        let Some(target) = TARGETS.iter().find(|target| target.name() == name) else {
+            return Some(false);
+        };
+        let mut inputs = 0;
+        for input in value
+            .split(',')
+            .map(str::trim)
+            .filter(|input| !input.is_empty())
+        {
+            match input_from_name(input) {
+                Some(input) => inputs |= 1 << input,
+                None => return Some(false),
+            }
+        }
+        self.inputs[index(*target)] = inputs;
+        Some(true)
+    }
+
+    /// `MAP_` lines of the targets that differ from the default mapping.
+    pub fn to_config(&self) -> String {
+        let mut config = String::new();
+        for target in TARGETS {
+            if self.inputs(target) != default_inputs(target) {
+                let names: Vec<&str> = input_names(self.inputs(target)).collect();
+                config += &alloc::format!("MAP_{}={}\n", target.name(), names.join(","));
+            }
+        }
+        config
+    }
+}
+
+fn index(target: InputTarget) -> usize {
+    TARGETS.iter().position(|other| *other == target).unwrap()
+}
+
+fn default_inputs(target: InputTarget) -> u32 {
+    // The first inputs are in the same order as the Game Boy buttons of `TARGETS`
+    match index(target) {
+        index if index < INPUT_EXTRA as usize => 1 << index,
+        _ => 0,
+    }
+}
+
+fn input_names(inputs: u32) -> impl Iterator<Item = &'static str> {
+    (0..INPUT_COUNT)
+        .filter(move |input| inputs & (1 << input) != 0)
+        .map(|input| INPUT_NAMES[input])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{INPUT_A, INPUT_B, INPUT_SELECT};
+
+    const EXTRA1: u8 = INPUT_EXTRA;
+    const EXTRA3: u8 = INPUT_EXTRA + 2;
+
+    #[test]
+    fn targets_match_their_names() {
+        for (index, target) in TARGETS.iter().enumerate() {
+            assert_eq!(target.bit(), 1 << index);
+            let found = TARGETS.iter().filter(|other| other.name() == target.name());
+            assert_eq!(found.count(), 1, "{} is not unique", target.name());
+        }
+    }
+
+    #[test]
+    fn default_maps_the_game_boy_buttons_to_their_inputs() {
+        let mapping = ButtonMapping::default();
+        for (input, target) in TARGETS[..INPUT_EXTRA as usize].iter().enumerate() {
+            assert_eq!(mapping.inputs(*target), 1 << input);
+        }
+        for target in &TARGETS[INPUT_EXTRA as usize..] {
+            assert_eq!(mapping.inputs(*target), 0);
+        }
+        assert_eq!(mapping.to_config(), "");
+    }
+
+    #[test]
+    fn apply_replaces_the_inputs_of_a_target() {
+        let mut mapping = ButtonMapping::default();
+        assert_eq!(mapping.apply("MAP_A", "B"), Some(true));
+        assert_eq!(mapping.apply("MAP_B", "a"), Some(true));
+        assert_eq!(
+            mapping.apply("MAP_FAST_FORWARD", " EXTRA1 , extra3 "),
+            Some(true)
+        );
+        assert_eq!(mapping.inputs(InputTarget::A), 1 << INPUT_B);
+        assert_eq!(mapping.inputs(InputTarget::B), 1 << INPUT_A);
+        assert_eq!(
+            mapping.inputs(InputTarget::FastForward),
+            1 << EXTRA1 | 1 << EXTRA3
+        );
+        assert_eq!(mapping.describe(InputTarget::FastForward), "EXTRA1, EXTRA3");
+    }
+
+    #[test]
+    fn apply_with_an_empty_value_unbinds() {
+        let mut mapping = ButtonMapping::default();
+        assert_eq!(mapping.apply("MAP_SELECT", ""), Some(true));
+        assert_eq!(mapping.inputs(InputTarget::Select), 0);
+        assert_eq!(mapping.describe(InputTarget::Select), "-");
+        assert_eq!(mapping.to_config(), "MAP_SELECT=\n");
+    }
+
+    #[test]
+    fn apply_rejects_invalid_settings() {
+        let mut mapping = ButtonMapping::default();
+        assert_eq!(mapping.apply("VOLUME", "5"), None);
+        assert_eq!(mapping.apply("MAP_JUMP", "A"), Some(false));
+        assert_eq!(mapping.apply("MAP_A", "B,EXTRA9"), Some(false));
+        assert_eq!(mapping, ButtonMapping::default());
+    }
+
+    #[test]
+    fn config_round_trips() {
+        let mut mapping = ButtonMapping::default();
+        mapping.toggle(InputTarget::Menu, EXTRA1);
+        mapping.toggle(InputTarget::TurboA, INPUT_A);
+        mapping.toggle(InputTarget::TurboA, EXTRA3);
+        mapping.clear(InputTarget::Start);
+        let config = mapping.to_config();
+        assert_eq!(
+            config,
+            "MAP_START=\nMAP_TURBO_A=A,EXTRA3\nMAP_MENU=EXTRA1\n"
+        );
+
+        let mut loaded = ButtonMapping::default();
+        for (key, value) in crate::config::config_entries(&config) {
+            assert_eq!(loaded.apply(key, value), Some(true));
+        }
+        assert_eq!(loaded, mapping);
+
+        loaded.reset(InputTarget::Start);
+        loaded.reset(InputTarget::TurboA);
+        loaded.reset(InputTarget::Menu);
+        assert_eq!(loaded, ButtonMapping::default());
+    }
+
+    #[test]
+    fn targets_follow_the_held_inputs() {
+        let mut mapping = ButtonMapping::default();
+        mapping.toggle(InputTarget::Screenshot, INPUT_SELECT);
+        let targets = mapping.targets(1 << INPUT_SELECT | 1 << INPUT_A);
+        assert_eq!(
+            targets,
+            InputTarget::Select.bit() | InputTarget::Screenshot.bit() | InputTarget::A.bit()
+        );
+        assert_eq!(mapping.targets(0), 0);
+        assert_eq!(
+            InputTarget::Screenshot.action(),
+            Some(EmulatorAction::Screenshot)
+        );
+        assert_eq!(InputTarget::A.action(), None);
+    }
+}
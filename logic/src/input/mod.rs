@@ -0,0 +1,35 @@
+//! The physical inputs and what they are mapped to, independent of how they are read.
+
+pub mod debounce;
+pub mod events;
+pub mod hotkeys;
+pub mod joystick;
+pub mod mapping;
+pub mod turbo;
+
+/// Physical inputs, each one is a bit of the masks the input sources of the firmware read. The
+/// first eight are the buttons named after the Game Boy buttons in the pin mapping, the
+/// Game Boy buttons they control are set by the [`mapping::ButtonMapping`].
+pub const INPUT_A: u8 = 0;
+pub const INPUT_B: u8 = 1;
+pub const INPUT_SELECT: u8 = 2;
+pub const INPUT_START: u8 = 3;
+pub const INPUT_UP: u8 = 4;
+pub const INPUT_DOWN: u8 = 5;
+pub const INPUT_LEFT: u8 = 6;
+pub const INPUT_RIGHT: u8 = 7;
+/// First of the spare inputs, `EXTRA1` to `EXTRA8`.
+pub const INPUT_EXTRA: u8 = 8;
+pub const INPUT_COUNT: usize = 16;
+
+pub const INPUT_NAMES: [&str; INPUT_COUNT] = [
+    "A", "B", "SELECT", "START", "UP", "DOWN", "LEFT", "RIGHT", "EXTRA1", "EXTRA2", "EXTRA3",
+    "EXTRA4", "EXTRA5", "EXTRA6", "EXTRA7", "EXTRA8",
+];
+
+pub fn input_from_name(name: &str) -> Option<u8> {
+    INPUT_NAMES
+        .iter()
+        .position(|input| input.eq_ignore_ascii_case(name))
+        .map(|index| index as u8)
+}
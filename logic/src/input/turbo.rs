@@ -0,0 +1,111 @@
+/// The DMG runs at ~59.73 frames per second, the turbo rates are counted against 60.
+const FRAMES_PER_SECOND: u8 = 60;
+pub const TURBO_RATE_MIN: u8 = 5;
+pub const TURBO_RATE_MAX: u8 = 30;
+const TURBO_RATE_STEP: u8 = 5;
+
+/// Autofire of the A and B buttons. The TURBO_A and TURBO_B targets always autofire, A and
+/// B themselves only when turned on here.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Turbo {
+    /// Presses per second, between `TURBO_RATE_MIN` and `TURBO_RATE_MAX`.
+    pub rate: u8,
+    pub a: bool,
+    pub b: bool,
+}
+
+impl Default for Turbo {
+    fn default() -> Self {
+        Self {
+            rate: 10,
+            a: false,
+            b: false,
+        }
+    }
+}
+
+impl Turbo {
+    pub fn faster(&mut self) {
+        self.rate = (self.rate + TURBO_RATE_STEP).min(TURBO_RATE_MAX);
+    }
+
+    pub fn slower(&mut self) {
+        self.rate = self
+            .rate
+            .saturating_sub(TURBO_RATE_STEP)
+            .max(TURBO_RATE_MIN);
+    }
+
+    /// Whether an autofiring button is down during an emulated frame. The rate is counted in
+    /// emulated frames so it stays the same at any emulation speed. A phase advances by the
+    /// rate every frame and the button is down for the first half of every turn, so rates
+    /// that don't divide 60 still press exactly `rate` times a second.
+    #[inline(always)]
+    pub fn pressed(&self, frame: u32) -> bool {
+        let rate = self.rate.clamp(TURBO_RATE_MIN, TURBO_RATE_MAX) as u32;
+        let frames = FRAMES_PER_SECOND as u32;
+        (frame % frames) * rate % frames < frames / 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turbo(rate: u8) -> Turbo {
+        Turbo {
+            rate,
+            ..Turbo::default()
+        }
+    }
+
+    /// Presses and frames held down during a second starting at `start`.
+    fn second(turbo: Turbo, start: u32) -> (u32, u32) {
+        let (mut presses, mut down) = (0, 0);
+        let mut was_pressed = turbo.pressed(start - 1);
+        for frame in start..start + 60 {
+            let pressed = turbo.pressed(frame);
+            presses += (pressed && !was_pressed) as u32;
+            down += pressed as u32;
+            was_pressed = pressed;
+        }
+        (presses, down)
+    }
+
+    #[test]
+    fn presses_rate_times_a_second_at_every_rate() {
+        for rate in (TURBO_RATE_MIN..=TURBO_RATE_MAX).step_by(TURBO_RATE_STEP as usize) {
+            for start in [60, 67, 60_000] {
+                let (presses, down) = second(turbo(rate), start);
+                assert_eq!(presses, rate as u32, "rate {}", rate);
+                // Periods of an odd number of frames can't be split in two halves
+                assert!((20..=40).contains(&down), "rate {} held {}", rate, down);
+            }
+        }
+    }
+
+    #[test]
+    fn fires_on_the_first_frame() {
+        assert!(turbo(10).pressed(0));
+        assert!(turbo(25).pressed(0));
+    }
+
+    #[test]
+    fn thirty_alternates_every_frame() {
+        let turbo = turbo(30);
+        for frame in 0..120 {
+            assert_eq!(turbo.pressed(frame), frame % 2 == 0);
+        }
+    }
+
+    #[test]
+    fn rate_stays_in_range() {
+        let mut turbo = turbo(TURBO_RATE_MAX);
+        turbo.faster();
+        assert_eq!(turbo.rate, TURBO_RATE_MAX);
+        for _ in 0..10 {
+            turbo.slower();
+        }
+        assert_eq!(turbo.rate, TURBO_RATE_MIN);
+    }
+}
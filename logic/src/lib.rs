@@ -0,0 +1,16 @@
+//! The parts of the firmware that don't touch the hardware. They only depend on `core`,
+//! `alloc` and a few `no_std` crates so their tests run on the host, see the README.
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod action;
+pub mod battery;
+pub mod config;
+pub mod dvi_lines;
+pub mod image;
+pub mod input;
+pub mod line_diff;
+pub mod resampler;
+pub mod tmds;
+pub mod wav;
@@ -0,0 +1,159 @@
+use core::ops::Range;
+
+use embedded_graphics_core::pixelcolor::Rgb565;
+use embedded_graphics_core::prelude::IntoStorage;
+
+const FNV_OFFSET_BASIS: u32 = 0x811C_9DC5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+/// Remembers a hash of every scanline of the previous frame so unchanged lines can be skipped.
+/// The lines are hashed as the Game Boy produced them, after the frame blend and the colour
+/// correction, anything drawn over them has to mark their rows with `invalidate_rows`.
+pub struct LineChangeTracker<const LINES: usize> {
+    hashes: [u32; LINES],
+    /// Lines that are sent with the next frame whatever their content.
+    dirty: [bool; LINES],
+    valid: bool,
+}
+
+impl<const LINES: usize> Default for LineChangeTracker<LINES> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const LINES: usize> LineChangeTracker<LINES> {
+    pub fn new() -> Self {
+        Self {
+            hashes: [0u32; LINES],
+            dirty: [false; LINES],
+            valid: false,
+        }
+    }
+
+    /// Records the content of a line, returns true if it differs from the same line of the
+    /// previous frame.
+    #[inline(always)]
+    pub fn update(&mut self, line: usize, pixels: &[Rgb565]) -> bool {
+        let mut hash = FNV_OFFSET_BASIS;
+        for pixel in pixels {
+            hash = (hash ^ pixel.into_storage() as u32).wrapping_mul(FNV_PRIME);
+        }
+        let dirty = core::mem::take(&mut self.dirty[line]);
+        let changed = dirty || !self.valid || self.hashes[line] != hash;
+        self.hashes[line] = hash;
+        if line == LINES - 1 {
+            self.valid = true;
+        }
+        changed
+    }
+
+    /// Forces every line of the next frame to be sent, used when something else was drawn
+    /// over the Game Boy image.
+    pub fn invalidate(&mut self) {
+        self.valid = false;
+    }
+
+    /// Forces the lines shown on the given output rows to be sent with the next frame, used
+    /// when an overlay on those rows changed. `output_row` returns the first output row of a
+    /// line, the last line reaches to the bottom.
+    pub fn invalidate_rows(&mut self, rows: Range<u16>, output_row: impl Fn(usize) -> u16) {
+        for line in 0..LINES {
+            let start = output_row(line);
+            let end = if line + 1 < LINES {
+                output_row(line + 1)
+            } else {
+                u16::MAX
+            };
+            if start < rows.end && rows.start < end {
+                self.dirty[line] = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics_core::pixelcolor::RgbColor;
+
+    const LINES: usize = 3;
+
+    fn frame(tracker: &mut LineChangeTracker<LINES>, lines: [[Rgb565; 4]; LINES]) -> [bool; LINES] {
+        core::array::from_fn(|line| tracker.update(line, &lines[line]))
+    }
+
+    #[test]
+    fn first_frame_sends_every_line() {
+        let mut tracker = LineChangeTracker::new();
+        assert_eq!(
+            frame(&mut tracker, [[Rgb565::BLACK; 4]; LINES]),
+            [true; LINES]
+        );
+        assert_eq!(
+            frame(&mut tracker, [[Rgb565::BLACK; 4]; LINES]),
+            [false; LINES]
+        );
+    }
+
+    #[test]
+    fn only_changed_lines_are_sent() {
+        let mut tracker = LineChangeTracker::new();
+        let mut lines = [[Rgb565::BLACK; 4]; LINES];
+        frame(&mut tracker, lines);
+        lines[1][3] = Rgb565::WHITE;
+        assert_eq!(frame(&mut tracker, lines), [false, true, false]);
+        assert_eq!(frame(&mut tracker, lines), [false; LINES]);
+    }
+
+    #[test]
+    fn swapped_pixels_count_as_a_change() {
+        let mut tracker = LineChangeTracker::new();
+        let mut lines = [[Rgb565::BLACK; 4]; LINES];
+        lines[0] = [Rgb565::RED, Rgb565::GREEN, Rgb565::BLACK, Rgb565::BLACK];
+        frame(&mut tracker, lines);
+        lines[0] = [Rgb565::GREEN, Rgb565::RED, Rgb565::BLACK, Rgb565::BLACK];
+        assert_eq!(frame(&mut tracker, lines), [true, false, false]);
+    }
+
+    #[test]
+    fn invalidate_sends_the_next_frame_again() {
+        let mut tracker = LineChangeTracker::new();
+        let lines = [[Rgb565::BLUE; 4]; LINES];
+        frame(&mut tracker, lines);
+        tracker.invalidate();
+        assert_eq!(frame(&mut tracker, lines), [true; LINES]);
+        assert_eq!(frame(&mut tracker, lines), [false; LINES]);
+    }
+
+    #[test]
+    fn an_interrupted_frame_stays_invalid() {
+        let mut tracker = LineChangeTracker::new();
+        let lines = [[Rgb565::BLUE; 4]; LINES];
+        // The LCD was turned on halfway, the last line was never seen
+        tracker.update(0, &lines[0]);
+        assert_eq!(frame(&mut tracker, lines), [true; LINES]);
+    }
+
+    #[test]
+    fn changed_overlay_invalidates_its_lines() {
+        let mut tracker = LineChangeTracker::new();
+        let lines = [[Rgb565::BLUE; 4]; LINES];
+        frame(&mut tracker, lines);
+        // Every line is scaled to 2 rows, a toast on rows 3 and 4 covers lines 1 and 2
+        tracker.invalidate_rows(3..5, |line| line as u16 * 2);
+        assert_eq!(frame(&mut tracker, lines), [false, true, true]);
+        assert_eq!(frame(&mut tracker, lines), [false; LINES]);
+    }
+
+    #[test]
+    fn overlay_below_the_last_line_start_invalidates_it() {
+        let mut tracker = LineChangeTracker::new();
+        let lines = [[Rgb565::BLUE; 4]; LINES];
+        frame(&mut tracker, lines);
+        tracker.invalidate_rows(20..22, |line| line as u16 * 2);
+        assert_eq!(frame(&mut tracker, lines), [false, false, true]);
+        tracker.invalidate_rows(0..0, |line| line as u16 * 2);
+        assert_eq!(frame(&mut tracker, lines), [false; LINES]);
+    }
+}
@@ -0,0 +1,285 @@
+use alloc::boxed::Box;
+// The tests build with std, which has the float functions built in
+#[cfg(not(test))]
+use num_traits::Float;
+
+/// Input frames kept for the interpolation, the output is computed between the two middle ones.
+const TAPS: usize = 8;
+/// Fractional positions with their own set of sinc coefficients.
+const PHASES: usize = 64;
+/// Sinc coefficients are fixed point with 14 fractional bits.
+const COEFFICIENT_BITS: u32 = 14;
+/// Positions are fixed point with 32 fractional bits, one input frame is `ONE`.
+const ONE: u64 = 1 << 32;
+/// Largest rate adjustment accepted by `set_rate_adjustment`, 0.5%.
+const MAX_RATE_ADJUSTMENT_PPM: i32 = 5000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ResamplerQuality {
+    /// Straight line between two input frames, cheapest but lets some aliasing through.
+    Linear,
+    /// 8 tap Blackman windowed sinc, band-limited to the lower of the two rates.
+    Sinc,
+}
+
+/// Converts interleaved stereo 16 bit samples from the rate gb-core generates them at to the
+/// rate of the audio output.
+///
+/// The state is kept between calls so buffers can be split anywhere.
+pub struct Resampler {
+    base_step: u64,
+    /// Input frames advanced per output frame.
+    step: u64,
+    /// Position of the next output frame after `history[TAPS / 2 - 1]`.
+    position: u64,
+    history: [[i32; TAPS]; 2],
+    sinc_table: Option<Box<[[i16; TAPS]; PHASES]>>,
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32, quality: ResamplerQuality) -> Self {
+        let base_step = ((input_rate as u64) << 32) / output_rate as u64;
+        Self {
+            base_step,
+            step: base_step,
+            position: 0,
+            history: [[0; TAPS]; 2],
+            sinc_table: match quality {
+                ResamplerQuality::Linear => None,
+                ResamplerQuality::Sinc => Some(Box::new(sinc_table(input_rate, output_rate))),
+            },
+        }
+    }
+
+    /// Speeds the output up (positive) or slows it down (negative) by the given parts per
+    /// million, used to keep the audio in step with the emulation without audible pitch changes.
+    pub fn set_rate_adjustment(&mut self, parts_per_million: i32) {
+        let ppm = parts_per_million.clamp(-MAX_RATE_ADJUSTMENT_PPM, MAX_RATE_ADJUSTMENT_PPM);
+        self.step =
+            (self.base_step as i64 + (self.base_step as i64 * ppm as i64) / 1_000_000) as u64;
+    }
+
+    /// Largest number of samples [`Resampler::process`] can write for `input_len` samples.
+    pub fn max_output_len(&self, input_len: usize) -> usize {
+        let min_step = self.base_step - self.base_step * MAX_RATE_ADJUSTMENT_PPM as u64 / 1_000_000;
+        ((input_len as u64 / 2 * ONE / min_step) as usize + 1) * 2
+    }
+
+    /// Resamples `input` into `output` and returns the number of samples written, frames that do
+    /// not fit into `output` are dropped.
+    pub fn process(&mut self, input: &[u16], output: &mut [u16]) -> usize {
+        let mut written = 0;
+        for frame in input.chunks_exact(2) {
+            for (channel, sample) in frame.iter().enumerate() {
+                let history = &mut self.history[channel];
+                history.copy_within(1.., 0);
+                history[TAPS - 1] = *sample as i16 as i32;
+            }
+            while self.position < ONE {
+                if written + 2 <= output.len() {
+                    output[written] = self.interpolate(0) as u16;
+                    output[written + 1] = self.interpolate(1) as u16;
+                    written += 2;
+                }
+                self.position += self.step;
+            }
+            self.position -= ONE;
+        }
+        written
+    }
+
+    #[inline(always)]
+    fn interpolate(&self, channel: usize) -> i16 {
+        let history = &self.history[channel];
+        let value = match &self.sinc_table {
+            Some(table) => {
+                let phase = (self.position >> (32 - PHASES.trailing_zeros())) as usize;
+                let sum: i32 = history
+                    .iter()
+                    .zip(table[phase].iter())
+                    .map(|(sample, coefficient)| sample * *coefficient as i32)
+                    .sum();
+                sum >> COEFFICIENT_BITS
+            }
+            None => {
+                let current = history[TAPS / 2 - 1];
+                let next = history[TAPS / 2];
+                let fraction = (self.position >> 16) as i64;
+                current + (((next - current) as i64 * fraction) >> 16) as i32
+            }
+        };
+        value.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+    }
+}
+
+/// Coefficients of every phase, normalised so each phase keeps the volume unchanged.
+fn sinc_table(input_rate: u32, output_rate: u32) -> [[i16; TAPS]; PHASES] {
+    use core::f32::consts::PI;
+    // Cut off at the Nyquist frequency of the lower rate, relative to the input rate
+    let cutoff = (output_rate as f32 / input_rate as f32).min(1.0) * 0.95;
+    let half_width = (TAPS / 2) as f32;
+    core::array::from_fn(|phase| {
+        let fraction = phase as f32 / PHASES as f32;
+        let weights: [f32; TAPS] = core::array::from_fn(|tap| {
+            let distance = tap as f32 - (TAPS / 2 - 1) as f32 - fraction;
+            let x = PI * cutoff * distance;
+            let sinc = if x.abs() < 1e-6 { 1.0 } else { x.sin() / x };
+            let window = 0.42
+                + 0.5 * (PI * distance / half_width).cos()
+                + 0.08 * (2.0 * PI * distance / half_width).cos();
+            sinc * window.max(0.0)
+        });
+        let total: f32 = weights.iter().sum();
+        core::array::from_fn(|tap| {
+            (weights[tap] / total * (1 << COEFFICIENT_BITS) as f32).round() as i16
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    const QUALITIES: [ResamplerQuality; 2] = [ResamplerQuality::Linear, ResamplerQuality::Sinc];
+
+    fn resample(resampler: &mut Resampler, input: &[u16]) -> Vec<u16> {
+        let mut output = vec![0; resampler.max_output_len(input.len())];
+        let written = resampler.process(input, &mut output);
+        output.truncate(written);
+        output
+    }
+
+    /// Interleaved stereo frames of a sine on the left and silence on the right.
+    fn sine(frequency: f32, rate: u32, frames: usize, amplitude: f32) -> Vec<u16> {
+        (0..frames)
+            .flat_map(|frame| {
+                let phase = 2.0 * core::f32::consts::PI * frequency * frame as f32 / rate as f32;
+                [(phase.sin() * amplitude) as i16 as u16, 0]
+            })
+            .collect()
+    }
+
+    fn peak(samples: &[u16]) -> i32 {
+        samples
+            .iter()
+            .step_by(2)
+            .map(|sample| (*sample as i16 as i32).abs())
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn output_follows_the_rate_ratio() {
+        for quality in QUALITIES {
+            for output_rate in [22050, 32000, 44100, 48000] {
+                let mut resampler = Resampler::new(16000, output_rate, quality);
+                let output = resample(&mut resampler, &vec![0; 16000 * 2]);
+                let frames = output.len() as i64 / 2;
+                assert!(
+                    (frames - output_rate as i64).abs() <= 1,
+                    "{} frames",
+                    frames
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn split_buffers_give_the_same_output() {
+        for quality in QUALITIES {
+            let input = sine(440.0, 16000, 1000, 20000.0);
+            let whole = resample(&mut Resampler::new(16000, 44100, quality), &input);
+            let mut resampler = Resampler::new(16000, 44100, quality);
+            let mut split = Vec::new();
+            for chunk in input.chunks(2 * 37) {
+                split.extend(resample(&mut resampler, chunk));
+            }
+            assert_eq!(split, whole);
+        }
+    }
+
+    #[test]
+    fn constant_input_stays_constant() {
+        for quality in QUALITIES {
+            let mut resampler = Resampler::new(16000, 48000, quality);
+            let input: Vec<u16> = [1000i16 as u16, -2000i16 as u16].repeat(500);
+            let output = resample(&mut resampler, &input);
+            // Skip the frames that still mix in the silent history
+            for frame in output[2 * 24..].chunks(2) {
+                assert!((frame[0] as i16 - 1000).abs() <= 2, "{:?}", quality);
+                assert!((frame[1] as i16 + 2000).abs() <= 2, "{:?}", quality);
+            }
+        }
+    }
+
+    #[test]
+    fn linear_lands_between_the_input_frames() {
+        let mut resampler = Resampler::new(16000, 32000, ResamplerQuality::Linear);
+        let input: Vec<u16> = (0..8).flat_map(|frame| [frame * 100, 0]).collect();
+        let output = resample(&mut resampler, &input);
+        let left: Vec<u16> = output.iter().step_by(2).copied().collect();
+        // Linear only looks at the two middle frames of the history, four frames behind
+        assert_eq!(&left[..8], &[0; 8]);
+        assert_eq!(&left[8..], &[0, 50, 100, 150, 200, 250, 300, 350]);
+    }
+
+    #[test]
+    fn tones_in_the_pass_band_keep_their_level() {
+        for quality in QUALITIES {
+            let mut resampler = Resampler::new(16000, 44100, quality);
+            let output = resample(&mut resampler, &sine(1000.0, 16000, 4000, 16000.0));
+            let level = peak(&output[output.len() / 2..]);
+            assert!(
+                (15000..=16500).contains(&level),
+                "{:?} peak {}",
+                quality,
+                level
+            );
+        }
+    }
+
+    #[test]
+    fn sinc_filters_what_the_output_rate_cant_hold() {
+        // 7kHz fits in 16kHz but not in 8kHz, the sinc table cuts off below 4kHz
+        let input = sine(7000.0, 16000, 4000, 16000.0);
+        let mut linear = Resampler::new(16000, 8000, ResamplerQuality::Linear);
+        let mut sinc = Resampler::new(16000, 8000, ResamplerQuality::Sinc);
+        let linear = resample(&mut linear, &input);
+        let sinc = resample(&mut sinc, &input);
+        assert!(peak(&linear[linear.len() / 2..]) > 4000);
+        assert!(peak(&sinc[sinc.len() / 2..]) < 1600);
+    }
+
+    #[test]
+    fn rate_adjustment_stretches_the_output() {
+        let input = vec![0; 16000 * 2];
+        let mut faster = Resampler::new(16000, 32000, ResamplerQuality::Linear);
+        faster.set_rate_adjustment(-5000);
+        let mut slower = Resampler::new(16000, 32000, ResamplerQuality::Linear);
+        slower.set_rate_adjustment(5000);
+        // A smaller step makes more output frames
+        assert_eq!(resample(&mut faster, &input).len() / 2, 32161);
+        assert_eq!(resample(&mut slower, &input).len() / 2, 31841);
+        // Beyond the limit is clamped
+        let mut clamped = Resampler::new(16000, 32000, ResamplerQuality::Linear);
+        clamped.set_rate_adjustment(-50_000);
+        assert_eq!(resample(&mut clamped, &input).len() / 2, 32161);
+    }
+
+    #[test]
+    fn max_output_len_covers_the_largest_adjustment() {
+        for input_frames in [1, 2, 3, 100, 267, 1000] {
+            let mut resampler = Resampler::new(16000, 48000, ResamplerQuality::Linear);
+            resampler.set_rate_adjustment(-MAX_RATE_ADJUSTMENT_PPM);
+            let input = vec![0; input_frames * 2];
+            let capacity = resampler.max_output_len(input.len());
+            for _ in 0..10 {
+                let mut output = vec![0; capacity + 8];
+                assert!(resampler.process(&input, &mut output) <= capacity);
+            }
+        }
+    }
+}
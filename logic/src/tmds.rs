@@ -0,0 +1,47 @@
+//! TMDS symbols used by DVI. The HSTX block encodes the pixel data itself, including the DC
+//! balancing, only the control symbols sent during blanking are built in software.
+
+/// Symbols sent on a lane during blanking, indexed by the two control bits `(C1 << 1) | C0`.
+/// Lane 0 carries VSYNC on C1 and HSYNC on C0, the other lanes send `CONTROL_SYMBOLS[0]`.
+pub const CONTROL_SYMBOLS: [u16; 4] = [0x354, 0x0AB, 0x154, 0x2AB];
+
+pub const fn control_symbol(c1: bool, c0: bool) -> u16 {
+    CONTROL_SYMBOLS[((c1 as usize) << 1) | c0 as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bit `n` of the symbol is the `n`th bit on the wire.
+    fn transitions(symbol: u16) -> u32 {
+        ((symbol ^ (symbol >> 1)) & 0x1FF).count_ones()
+    }
+
+    #[test]
+    fn control_symbols_match_the_dvi_specification() {
+        assert_eq!(control_symbol(false, false), 0b1101010100);
+        assert_eq!(control_symbol(false, true), 0b0010101011);
+        assert_eq!(control_symbol(true, false), 0b0101010100);
+        assert_eq!(control_symbol(true, true), 0b1010101011);
+    }
+
+    #[test]
+    fn control_symbols_have_more_transitions_than_any_data_symbol() {
+        // Sinks find the symbol boundaries from these, data symbols have 5 transitions at most
+        for symbol in CONTROL_SYMBOLS {
+            assert!(transitions(symbol) >= 7, "{:#05x}", symbol);
+        }
+    }
+
+    #[test]
+    fn control_symbols_stay_close_to_dc_balance() {
+        for symbol in CONTROL_SYMBOLS {
+            let disparity = 2 * symbol.count_ones() as i32 - 10;
+            assert!(disparity.abs() <= 2, "{:#05x}", symbol);
+        }
+        // The symbols without VSYNC are balanced, HSYNC alone doesn't skew the lane
+        assert_eq!(CONTROL_SYMBOLS[0].count_ones(), 5);
+        assert_eq!(CONTROL_SYMBOLS[1].count_ones(), 5);
+    }
+}
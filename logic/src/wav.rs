@@ -0,0 +1,99 @@
+//! WAV files of the recorded audio.
+
+use alloc::vec::Vec;
+
+/// The header fills a whole SD card block, padded with a `JUNK` chunk, so the samples start on
+/// a block boundary and every write of whole blocks stays aligned.
+pub const HEADER_SIZE: u32 = 512;
+
+/// Header of a 16 bit stereo PCM file holding `data_size` bytes of samples.
+pub fn wav_header(sample_rate: u32, data_size: u32) -> Vec<u8> {
+    const CHANNELS: u16 = 2;
+    const BITS_PER_SAMPLE: u16 = 16;
+    let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+    let mut header: Vec<u8> = Vec::with_capacity(HEADER_SIZE as usize);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&(HEADER_SIZE - 8 + data_size).to_le_bytes());
+    header.extend_from_slice(b"WAVE");
+    header.extend_from_slice(b"fmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    // PCM
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&CHANNELS.to_le_bytes());
+    header.extend_from_slice(&sample_rate.to_le_bytes());
+    header.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    header.extend_from_slice(&block_align.to_le_bytes());
+    header.extend_from_slice(&BITS_PER_SAMPLE.to_le_bytes());
+    // Padding up to the data chunk, players skip chunks they don't know
+    let junk_size = HEADER_SIZE as usize - header.len() - 16;
+    header.extend_from_slice(b"JUNK");
+    header.extend_from_slice(&(junk_size as u32).to_le_bytes());
+    header.resize(header.len() + junk_size, 0);
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_size.to_le_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    /// Walks the chunks after `WAVE`, returns their ids, offsets of their data and sizes.
+    fn chunks(header: &[u8]) -> Vec<([u8; 4], usize, u32)> {
+        let mut chunks = Vec::new();
+        let mut offset = 12;
+        while offset < header.len() {
+            let id = header[offset..offset + 4].try_into().unwrap();
+            let size = u32_at(header, offset + 4);
+            chunks.push((id, offset + 8, size));
+            if &id == b"data" {
+                break;
+            }
+            offset += 8 + size as usize + (size as usize & 1);
+        }
+        chunks
+    }
+
+    #[test]
+    fn samples_start_on_a_block_boundary() {
+        let header = wav_header(16000, 0);
+        assert_eq!(header.len(), HEADER_SIZE as usize);
+        assert_eq!(HEADER_SIZE % 512, 0);
+        let ids: Vec<_> = chunks(&header).iter().map(|(id, _, _)| *id).collect();
+        assert_eq!(ids, [*b"fmt ", *b"JUNK", *b"data"]);
+        let (_, data_offset, _) = chunks(&header)[2];
+        assert_eq!(data_offset, HEADER_SIZE as usize);
+    }
+
+    #[test]
+    fn format_is_16_bit_stereo_pcm() {
+        let header = wav_header(16000, 0);
+        assert_eq!(&header[..4], b"RIFF");
+        assert_eq!(&header[8..12], b"WAVE");
+        let (_, fmt, size) = chunks(&header)[0];
+        assert_eq!(size, 16);
+        assert_eq!(u16_at(&header, fmt), 1);
+        assert_eq!(u16_at(&header, fmt + 2), 2);
+        assert_eq!(u32_at(&header, fmt + 4), 16000);
+        assert_eq!(u32_at(&header, fmt + 8), 16000 * 4);
+        assert_eq!(u16_at(&header, fmt + 12), 4);
+        assert_eq!(u16_at(&header, fmt + 14), 16);
+    }
+
+    #[test]
+    fn sizes_cover_the_samples() {
+        let data_size = 2048 * 2 * 10;
+        let header = wav_header(32000, data_size);
+        assert_eq!(u32_at(&header, 4), HEADER_SIZE - 8 + data_size);
+        let (_, _, size) = chunks(&header)[2];
+        assert_eq!(size, data_size);
+    }
+}
@@ -79,6 +79,19 @@ pub const PLL_SYS_380MHZ: hal::pll::PLLConfig = hal::pll::PLLConfig {
     post_div1: 3,
     post_div2: 1,
 };
+/// Three times the 126MHz HSTX clock needed by 640x480 DVI.
+pub const PLL_SYS_378MHZ: hal::pll::PLLConfig = hal::pll::PLLConfig {
+    vco_freq: HertzU32::Hz(1512000000),
+    refdiv: 1,
+    post_div1: 4,
+    post_div2: 1,
+};
+
+#[cfg(not(feature = "dvi_output"))]
+const PLL_SYS_OVERCLOCK: hal::pll::PLLConfig = PLL_SYS_351MHZ;
+#[cfg(feature = "dvi_output")]
+const PLL_SYS_OVERCLOCK: hal::pll::PLLConfig = PLL_SYS_378MHZ;
+
 #[inline(always)]
 pub fn configure_overclock(
     xosc_crystal_freq: u32,
@@ -98,7 +111,7 @@ pub fn configure_overclock(
     let pll_sys = hal::pll::setup_pll_blocking(
         pll_sys_dev,
         xosc.operating_frequency(),
-        PLL_SYS_351MHZ,
+        PLL_SYS_OVERCLOCK,
         &mut clocks,
         resets,
     )
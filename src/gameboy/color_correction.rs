@@ -0,0 +1,119 @@
+use alloc::boxed::Box;
+use embedded_graphics::pixelcolor::raw::RawU16;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::IntoStorage;
+use num_traits::Float;
+
+use super::display::SCREEN_WIDTH;
+
+/// Entries of the colour cache, a frame rarely shows more colours than this.
+const CACHE_SIZE: usize = 256;
+
+/// Colour response of the Game Boy Color LCD, every row adds up to 32.
+const GBC_LCD_MATRIX: [[f32; 3]; 3] = [
+    [26.0 / 32.0, 4.0 / 32.0, 2.0 / 32.0],
+    [0.0, 24.0 / 32.0, 8.0 / 32.0],
+    [6.0 / 32.0, 4.0 / 32.0, 22.0 / 32.0],
+];
+const IDENTITY_MATRIX: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+const LUMA: [f32; 3] = [0.299, 0.587, 0.114];
+
+/// Maps every RGB565 colour through a 3x3 colour matrix (GBC LCD response and saturation) and
+/// per channel gamma tables.
+///
+/// A full table for the 65536 colours does not fit in RAM, so the results are kept in a small
+/// cache that fills in as colours show up. Frames only use a handful of colours, after the first
+/// frame almost every pixel is a single cached lookup.
+pub struct ColorCorrection {
+    matrix: [[f32; 3]; 3],
+    red_lut: [u8; 32],
+    green_lut: [u8; 64],
+    blue_lut: [u8; 32],
+    /// `(colour + 1) << 16 | corrected colour`, 0 marks an empty entry.
+    /// Raw colour and its corrected value, an entry only counts once its bit in `cache_valid`
+    /// is set so every one of the 65536 colours can be cached.
+    cache: Box<[(u16, u16); CACHE_SIZE]>,
+    cache_valid: [u32; CACHE_SIZE / 32],
+}
+
+impl ColorCorrection {
+    /// `saturation` is a percentage where 100 keeps the colours unchanged. `gamma` is the
+    /// exponent applied to every channel, times 100. Returns `None` when nothing would change.
+    pub fn new(gbc_lcd: bool, saturation: u16, gamma: u16) -> Option<Self> {
+        if !gbc_lcd && saturation == 100 && gamma == 100 {
+            return None;
+        }
+        let base = if gbc_lcd {
+            GBC_LCD_MATRIX
+        } else {
+            IDENTITY_MATRIX
+        };
+        // Saturation moves every channel away from or towards the luma of the pixel
+        let saturation = saturation as f32 / 100.0;
+        let mut saturation_matrix = [[0.0f32; 3]; 3];
+        for (row, output) in saturation_matrix.iter_mut().enumerate() {
+            for (column, value) in output.iter_mut().enumerate() {
+                let identity = if row == column { 1.0 } else { 0.0 };
+                *value = LUMA[column] * (1.0 - saturation) + identity * saturation;
+            }
+        }
+        let mut matrix = [[0.0f32; 3]; 3];
+        for row in 0..3 {
+            for column in 0..3 {
+                matrix[row][column] = (0..3)
+                    .map(|k| saturation_matrix[row][k] * base[k][column])
+                    .sum();
+            }
+        }
+
+        let gamma = gamma as f32 / 100.0;
+        Some(Self {
+            matrix,
+            red_lut: gamma_lut(gamma),
+            green_lut: gamma_lut(gamma),
+            blue_lut: gamma_lut(gamma),
+            cache: Box::new([(0, 0); CACHE_SIZE]),
+            cache_valid: [0; CACHE_SIZE / 32],
+        })
+    }
+
+    #[inline(always)]
+    pub fn correct_line(&mut self, line: &mut [Rgb565; SCREEN_WIDTH]) {
+        for pixel in line.iter_mut() {
+            let raw = pixel.into_storage();
+            let slot = ((raw ^ (raw >> 5) ^ (raw >> 11)) & (CACHE_SIZE as u16 - 1)) as usize;
+            let (key, value) = self.cache[slot];
+            let valid = self.cache_valid[slot / 32] & (1 << (slot % 32)) != 0;
+            let corrected = if valid && key == raw {
+                value
+            } else {
+                let corrected = self.correct(raw);
+                self.cache[slot] = (raw, corrected);
+                self.cache_valid[slot / 32] |= 1 << (slot % 32);
+                corrected
+            };
+            *pixel = Rgb565::from(RawU16::new(corrected));
+        }
+    }
+
+    fn correct(&self, raw: u16) -> u16 {
+        let input = [
+            (raw >> 11) as f32 / 31.0,
+            ((raw >> 5) & 0x3F) as f32 / 63.0,
+            (raw & 0x1F) as f32 / 31.0,
+        ];
+        let channel = |row: usize, max: f32| {
+            let value: f32 = (0..3).map(|k| self.matrix[row][k] * input[k]).sum();
+            (value.clamp(0.0, 1.0) * max).round() as usize
+        };
+        let red = self.red_lut[channel(0, 31.0)] as u16;
+        let green = self.green_lut[channel(1, 63.0)] as u16;
+        let blue = self.blue_lut[channel(2, 31.0)] as u16;
+        (red << 11) | (green << 5) | blue
+    }
+}
+
+fn gamma_lut<const N: usize>(gamma: f32) -> [u8; N] {
+    let max = (N - 1) as f32;
+    core::array::from_fn(|i| ((i as f32 / max).powf(gamma) * max).round() as u8)
+}
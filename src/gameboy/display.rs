@@ -5,10 +5,32 @@ use embedded_graphics::pixelcolor::raw::RawU16;
 use embedded_graphics::pixelcolor::Rgb565;
 use gb_core::hardware::Screen;
 
+use super::color_correction::ColorCorrection;
+use super::frame_blend::FrameBlender;
+
 const NANOS_IN_VSYNC: u64 = ((1.0 / 60.0) * 1000000000.0) as u64;
 
 #[const_env::from_env]
 const FRAME_RATE: u8 = 30;
+#[const_env::from_env]
+const FRAME_BLEND: u8 = 0;
+#[const_env::from_env]
+const COLOR_CORRECTION_GBC_LCD: bool = false;
+#[const_env::from_env]
+const COLOR_SATURATION: u16 = 100;
+#[const_env::from_env]
+const COLOR_GAMMA: u16 = 100;
+
+pub use gb_rp2350_logic::image::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// Copies the scanlines of one complete frame as they are produced.
+enum FrameCapture {
+    Idle,
+    /// Waiting for the next frame to start.
+    Armed,
+    Capturing(Box<[Rgb565]>),
+    Done(Box<[Rgb565]>),
+}
 
 pub struct GameboyLineBufferDisplay<D: TimerDevice> {
     pub line_buffer: Box<[Rgb565; 160]>,
@@ -16,6 +38,9 @@ pub struct GameboyLineBufferDisplay<D: TimerDevice> {
     pub turn_off: bool,
     time_counter: Instant,
     delay: crate::hal::Timer<D>,
+    capture: FrameCapture,
+    frame_blender: Option<FrameBlender>,
+    color_correction: Option<ColorCorrection>,
 }
 
 impl<D: TimerDevice> GameboyLineBufferDisplay<D> {
@@ -26,6 +51,66 @@ impl<D: TimerDevice> GameboyLineBufferDisplay<D> {
             turn_off: false,
             time_counter: delay.get_counter(),
             delay: delay,
+            capture: FrameCapture::Idle,
+            frame_blender: if FRAME_BLEND > 0 {
+                Some(FrameBlender::new(FRAME_BLEND))
+            } else {
+                None
+            },
+            color_correction: ColorCorrection::new(
+                COLOR_CORRECTION_GBC_LCD,
+                COLOR_SATURATION,
+                COLOR_GAMMA,
+            ),
+        }
+    }
+
+    /// Starts copying the next complete frame, it can be collected with `take_capture`.
+    pub fn capture_next_frame(&mut self) {
+        if let FrameCapture::Idle = self.capture {
+            self.capture = FrameCapture::Armed;
+        }
+    }
+
+    /// Returns the captured frame, `SCREEN_WIDTH` x `SCREEN_HEIGHT` pixels, once it is complete.
+    pub fn take_capture(&mut self) -> Option<Box<[Rgb565]>> {
+        match core::mem::replace(&mut self.capture, FrameCapture::Idle) {
+            FrameCapture::Done(frame) => Some(frame),
+            other => {
+                self.capture = other;
+                None
+            }
+        }
+    }
+
+    /// Copies a finished line into the capture. Lines the emulator skipped hold whatever the
+    /// buffer had before, a frame with one of them is dropped and the next one captured.
+    fn capture_line(&mut self, y: usize, skip: bool) {
+        if y >= SCREEN_HEIGHT {
+            return;
+        }
+        if skip {
+            if let FrameCapture::Capturing(_) = self.capture {
+                self.capture = FrameCapture::Armed;
+            }
+            return;
+        }
+        if y == 0 {
+            if let FrameCapture::Armed = self.capture {
+                self.capture = FrameCapture::Capturing(
+                    alloc::vec![Rgb565::default(); SCREEN_WIDTH * SCREEN_HEIGHT].into_boxed_slice(),
+                );
+            }
+        }
+        if let FrameCapture::Capturing(frame) = &mut self.capture {
+            frame[y * SCREEN_WIDTH..(y + 1) * SCREEN_WIDTH].copy_from_slice(&self.line_buffer[..]);
+            if y == SCREEN_HEIGHT - 1 {
+                if let FrameCapture::Capturing(frame) =
+                    core::mem::replace(&mut self.capture, FrameCapture::Idle)
+                {
+                    self.capture = FrameCapture::Done(frame);
+                }
+            }
         }
     }
 }
@@ -47,7 +132,21 @@ impl<D: TimerDevice> Screen for GameboyLineBufferDisplay<D> {
             + (color.blue as u16 >> 3);
         self.line_buffer[x as usize] = Rgb565::from(RawU16::new(encoded_color));
     }
-    fn scanline_complete(&mut self, _y: u8, _skip: bool) {
+    fn scanline_complete(&mut self, y: u8, skip: bool) {
+        if let Some(frame_blender) = &mut self.frame_blender {
+            if !skip {
+                frame_blender.blend_line(y as usize, &mut self.line_buffer);
+            }
+        }
+        if !matches!(self.capture, FrameCapture::Idle) {
+            self.capture_line(y as usize, skip);
+        }
+        // Screenshots keep the colours of the game, the correction is only for the panel
+        if let Some(color_correction) = &mut self.color_correction {
+            if !skip {
+                color_correction.correct_line(&mut self.line_buffer);
+            }
+        }
         self.line_complete = true;
     }
 
@@ -0,0 +1,52 @@
+use alloc::boxed::Box;
+use embedded_graphics::pixelcolor::raw::RawU16;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::IntoStorage;
+
+use super::display::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// The three channels of an RGB565 pixel spread over a u32 so each one has room to be
+/// multiplied by a 5 bit weight without overflowing into its neighbour.
+const SPREAD_MASK: u32 = 0x07E0_F81F;
+const WEIGHT_BITS: u32 = 5;
+const MAX_WEIGHT: u32 = 1 << WEIGHT_BITS;
+
+/// Mixes every scanline with the same line of the previous frame, emulating the slow response
+/// of the DMG LCD that games rely on to turn flickering sprites into transparency.
+pub struct FrameBlender {
+    history: Box<[u16]>,
+    weight: u32,
+}
+
+impl FrameBlender {
+    /// `strength` is the percentage of the previous frame in the output, 50 is an even mix.
+    pub fn new(strength: u8) -> Self {
+        Self {
+            history: alloc::vec![0u16; SCREEN_WIDTH * SCREEN_HEIGHT].into_boxed_slice(),
+            weight: strength.min(100) as u32 * MAX_WEIGHT / 100,
+        }
+    }
+
+    /// Blends the line in place and remembers its unblended content for the next frame.
+    #[inline(always)]
+    pub fn blend_line(&mut self, y: usize, line: &mut [Rgb565; SCREEN_WIDTH]) {
+        if y >= SCREEN_HEIGHT {
+            return;
+        }
+        let history = &mut self.history[y * SCREEN_WIDTH..(y + 1) * SCREEN_WIDTH];
+        let current_weight = MAX_WEIGHT - self.weight;
+        for (pixel, previous) in line.iter_mut().zip(history.iter_mut()) {
+            let raw = pixel.into_storage();
+            let mixed = ((spread(raw) * current_weight + spread(*previous) * self.weight)
+                >> WEIGHT_BITS)
+                & SPREAD_MASK;
+            *previous = raw;
+            *pixel = Rgb565::from(RawU16::new((mixed | (mixed >> 16)) as u16));
+        }
+    }
+}
+
+#[inline(always)]
+fn spread(raw: u16) -> u32 {
+    (raw as u32 | ((raw as u32) << 16)) & SPREAD_MASK
+}
@@ -1,28 +1,62 @@
-use core::{convert::Infallible, marker::PhantomData};
+use core::marker::PhantomData;
 
+use crate::hardware::display::LineChangeTracker;
+use crate::input::hotkeys::HotkeyEngine;
+use crate::input::mapping::{ButtonMapping, InputTarget, TARGETS};
+use crate::input::turbo::Turbo;
+use crate::input::InputSampler;
+use crate::ui::MenuButtons;
 use display::GameboyLineBufferDisplay;
 use embedded_graphics::pixelcolor::Rgb565;
-use embedded_hal::digital::InputPin;
-use gb_core::{gameboy::GameBoy, hardware::Screen};
+use gb_core::{gameboy::GameBoy, hardware::input::Button, hardware::Screen};
 use rp235x_hal::timer::TimerDevice;
 
 pub mod audio;
+pub mod color_correction;
 pub mod display;
+pub mod frame_blend;
+pub mod recording;
 pub mod rom;
+pub mod screenshot;
 pub mod static_rom;
 
+pub use gb_rp2350_logic::action::EmulatorAction;
+
 pub trait GameboyButtonHandler<'a> {
     fn handle_button_clicks<SC: Screen>(&mut self, gameboy: &mut GameBoy<'a, SC>);
+
+    /// Returns the last requested action, called once per frame.
+    fn take_action(&mut self) -> Option<EmulatorAction> {
+        None
+    }
+
+    /// True while the emulation should run as fast as it can.
+    fn fast_forward(&self) -> bool {
+        false
+    }
+
+    /// Called before every emulated frame.
+    fn next_frame(&mut self) {}
+
+    /// Buttons for the menus shown while the game is paused.
+    fn menu_buttons(&mut self) -> MenuButtons<'_>;
+
+    /// Replaces the mapping and the turbo settings after they were changed in game.
+    fn set_controls(&mut self, _mapping: ButtonMapping, _turbo: Turbo) {}
 }
 
 pub struct GameEmulationHandler<'a, 'b, 'c, BH: GameboyButtonHandler<'c>, D: TimerDevice> {
     gameboy: &'a mut GameBoy<'b, GameboyLineBufferDisplay<D>>,
     current_line_index: usize,
     button_handler: &'a mut BH,
+    interrupted: bool,
     _marker: PhantomData<&'c ()>,
 }
 impl<'a, 'b, 'c, BH: GameboyButtonHandler<'c>, D: TimerDevice>
     GameEmulationHandler<'a, 'b, 'c, BH, D>
+where
+    'b: 'c,
+    'c: 'b,
 {
     pub fn new(
         gameboy: &'a mut GameBoy<'b, GameboyLineBufferDisplay<D>>,
@@ -32,9 +66,43 @@ impl<'a, 'b, 'c, BH: GameboyButtonHandler<'c>, D: TimerDevice>
             gameboy: gameboy,
             current_line_index: 0,
             button_handler,
+            interrupted: false,
             _marker: PhantomData,
         }
     }
+
+    /// Runs the emulator until the current scanline is complete, returns false if the frame
+    /// was interrupted by the LCD being turned on.
+    #[inline(always)]
+    pub fn emulate_line(&mut self) -> bool {
+        if self.interrupted {
+            return false;
+        }
+        loop {
+            if self.gameboy.get_screen().turn_off {
+                self.gameboy.get_screen().turn_off = false;
+                self.interrupted = true;
+                return false;
+            }
+            if self.gameboy.get_screen().line_complete {
+                return true;
+            }
+            self.gameboy.tick();
+        }
+    }
+
+    #[inline(always)]
+    pub fn line(&mut self) -> &[Rgb565; 160] {
+        &self.gameboy.get_screen().line_buffer
+    }
+
+    /// Releases the current scanline so the emulator can continue with the next one.
+    #[inline(always)]
+    pub fn finish_line(&mut self) {
+        self.current_line_index = 0;
+        self.gameboy.get_screen().line_complete = false;
+        self.button_handler.handle_button_clicks(&mut self.gameboy);
+    }
 }
 
 impl<'a, 'b, 'c, BH: GameboyButtonHandler<'c>, D: TimerDevice> Iterator
@@ -46,176 +114,241 @@ where
     type Item = Rgb565;
 
     fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            if self.gameboy.get_screen().turn_off {
-                self.gameboy.get_screen().turn_off = false;
+        if self.current_line_index == 0 && !self.emulate_line() {
+            return None;
+        }
+        let pixel = self.gameboy.get_screen().line_buffer[self.current_line_index];
+        if self.current_line_index + 1 >= 160 {
+            self.finish_line();
+        } else {
+            self.current_line_index = self.current_line_index + 1;
+        }
+        Some(pixel)
+    }
+}
+
+/// Streams the pixels of consecutive scanlines that changed since the previous frame, it ends
+/// before the first unchanged line, leaving that line emulated but not yet finished.
+pub struct ChangedLinesIterator<
+    'h,
+    'a,
+    'b,
+    'c,
+    BH: GameboyButtonHandler<'c>,
+    D: TimerDevice,
+    const LINES: usize,
+> {
+    emulation: &'h mut GameEmulationHandler<'a, 'b, 'c, BH, D>,
+    tracker: &'h mut LineChangeTracker<LINES>,
+    line: &'h mut usize,
+}
+
+impl<'h, 'a, 'b, 'c, BH: GameboyButtonHandler<'c>, D: TimerDevice, const LINES: usize>
+    ChangedLinesIterator<'h, 'a, 'b, 'c, BH, D, LINES>
+{
+    /// The current line of `emulation` must already be complete and known to have changed.
+    pub fn new(
+        emulation: &'h mut GameEmulationHandler<'a, 'b, 'c, BH, D>,
+        tracker: &'h mut LineChangeTracker<LINES>,
+        line: &'h mut usize,
+    ) -> Self {
+        Self {
+            emulation,
+            tracker,
+            line,
+        }
+    }
+}
+
+impl<'h, 'a, 'b, 'c, BH: GameboyButtonHandler<'c>, D: TimerDevice, const LINES: usize> Iterator
+    for ChangedLinesIterator<'h, 'a, 'b, 'c, BH, D, LINES>
+where
+    'b: 'c,
+    'c: 'b,
+{
+    type Item = Rgb565;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.emulation.current_line_index == 0
+            && !self.emulation.gameboy.get_screen().line_complete
+        {
+            if *self.line >= LINES || !self.emulation.emulate_line() {
                 return None;
             }
-            if self.gameboy.get_screen().line_complete {
-                let pixel = self.gameboy.get_screen().line_buffer[self.current_line_index];
-                if self.current_line_index + 1 >= 160 {
-                    self.current_line_index = 0;
-                    self.gameboy.get_screen().line_complete = false;
-                    self.button_handler.handle_button_clicks(&mut self.gameboy);
-                } else {
-                    self.current_line_index = self.current_line_index + 1;
-                }
-                return Some(pixel);
-            } else {
-                self.gameboy.tick();
+            if !self.tracker.update(*self.line, self.emulation.line()) {
+                return None;
             }
         }
+        let pixel = self.emulation.next()?;
+        if self.emulation.current_line_index == 0 {
+            *self.line += 1;
+        }
+        Some(pixel)
+    }
+}
+
+/// The Game Boy buttons are the first entries of `TARGETS`.
+const GAME_BUTTONS: usize = 8;
+/// How long a hotkey button has to be held for a long press.
+#[const_env::from_env]
+const HOTKEY_LONG_PRESS_MS: u64 = 1000;
+/// How long a hotkey modifier is kept from the game while waiting for the rest of the chord.
+#[const_env::from_env]
+const HOTKEY_CHORD_WINDOW_MS: u64 = 250;
+
+/// The Game Boy button pressed while the target is held.
+fn game_button(target: InputTarget) -> Option<Button> {
+    match target {
+        InputTarget::A => Some(Button::A),
+        InputTarget::B => Some(Button::B),
+        InputTarget::Select => Some(Button::SELECT),
+        InputTarget::Start => Some(Button::START),
+        InputTarget::Up => Some(Button::UP),
+        InputTarget::Down => Some(Button::DOWN),
+        InputTarget::Left => Some(Button::LEFT),
+        InputTarget::Right => Some(Button::RIGHT),
+        InputTarget::TurboA => Some(Button::A),
+        InputTarget::TurboB => Some(Button::B),
+        _ => None,
     }
 }
 
+/// Feeds the physical inputs to the Game Boy through a [`ButtonMapping`], the presses that
+/// make up a hotkey are kept from the game.
 pub struct InputButtonMapper<'a> {
-    a_button: &'a mut dyn InputPin<Error = Infallible>,
-    b_button: &'a mut dyn InputPin<Error = Infallible>,
-    start_button: &'a mut dyn InputPin<Error = Infallible>,
-    select_button: &'a mut dyn InputPin<Error = Infallible>,
-    up_button: &'a mut dyn InputPin<Error = Infallible>,
-    down_button: &'a mut dyn InputPin<Error = Infallible>,
-    left_button: &'a mut dyn InputPin<Error = Infallible>,
-    right_button: &'a mut dyn InputPin<Error = Infallible>,
-    a_button_state: bool,
-    b_button_state: bool,
-    start_button_state: bool,
-    select_button_state: bool,
-    up_button_state: bool,
-    down_button_state: bool,
-    left_button_state: bool,
-    right_button_state: bool,
+    inputs: &'a mut dyn InputSampler,
+    mapping: ButtonMapping,
+    hotkeys: HotkeyEngine,
+    turbo: Turbo,
+    /// Inputs held at the last poll.
+    held_inputs: u32,
+    /// Targets seen by the game at the last poll, one bit per entry of `TARGETS`.
+    held_targets: u32,
+    /// Game Boy buttons down, one bit per entry of `TARGETS` up to `GAME_BUTTONS`.
+    game_buttons: u32,
+    /// Emulated frames so far, they clock the turbo buttons.
+    frame: u32,
+    /// Frame the autofiring buttons were first held on, so they fire straight away.
+    turbo_start: u32,
+    /// A turbo button is held and may have to change on the new frame.
+    turbo_update: bool,
+    action: Option<EmulatorAction>,
 }
 
 impl<'a, 'b> GameboyButtonHandler<'b> for InputButtonMapper<'a> {
     #[inline(always)]
     fn handle_button_clicks<SC: Screen>(&mut self, gameboy: &mut GameBoy<'b, SC>) {
-        ////
-        if self.b_button.is_low().unwrap() {
-            if self.b_button_state == false {
-                gameboy.key_pressed(gb_core::hardware::input::Button::B);
-                self.b_button_state = true;
-            }
-        } else {
-            if self.b_button_state == true {
-                gameboy.key_released(gb_core::hardware::input::Button::B);
-                self.b_button_state = false;
-            }
-        }
-        ////
-        if self.a_button.is_low().unwrap() {
-            if self.a_button_state == false {
-                gameboy.key_pressed(gb_core::hardware::input::Button::A);
-                self.a_button_state = true;
-            }
-        } else {
-            if self.a_button_state == true {
-                gameboy.key_released(gb_core::hardware::input::Button::A);
-                self.a_button_state = false;
-            }
+        let (inputs, now) = self.inputs.sample();
+        if inputs == self.held_inputs && !self.hotkeys.waiting() && !self.turbo_update {
+            return;
         }
-        ////
-        if self.select_button.is_low().unwrap() {
-            if self.select_button_state == false {
-                gameboy.key_pressed(gb_core::hardware::input::Button::SELECT);
-                self.select_button_state = true;
-            }
-        } else {
-            if self.select_button_state == true {
-                gameboy.key_released(gb_core::hardware::input::Button::SELECT);
-                self.select_button_state = false;
-            }
-        }
-        /////
-        if self.start_button.is_low().unwrap() {
-            if self.start_button_state == false {
-                gameboy.key_pressed(gb_core::hardware::input::Button::START);
-                self.start_button_state = true;
-            }
-        } else {
-            if self.start_button_state == true {
-                gameboy.key_released(gb_core::hardware::input::Button::START);
-                self.start_button_state = false;
-            }
+        self.turbo_update = false;
+        self.held_inputs = inputs;
+        let (targets, hotkey) = self.hotkeys.update(self.mapping.targets(inputs), now);
+        if hotkey.is_some() {
+            self.action = hotkey;
         }
-        /////
-        if self.up_button.is_low().unwrap() {
-            if self.up_button_state == false {
-                gameboy.key_pressed(gb_core::hardware::input::Button::UP);
-                self.up_button_state = true;
-            }
-        } else {
-            if self.up_button_state == true {
-                gameboy.key_released(gb_core::hardware::input::Button::UP);
-                self.up_button_state = false;
+        let pressed = targets & !self.held_targets;
+        for target in TARGETS {
+            if let Some(action) = target.action().filter(|_| pressed & target.bit() != 0) {
+                self.action = Some(action);
             }
         }
-        /////
-        if self.down_button.is_low().unwrap() {
-            if self.down_button_state == false {
-                gameboy.key_pressed(gb_core::hardware::input::Button::DOWN);
-                self.down_button_state = true;
-            }
-        } else {
-            if self.down_button_state == true {
-                gameboy.key_released(gb_core::hardware::input::Button::DOWN);
-                self.down_button_state = false;
-            }
+        if self.autofiring(self.held_targets) == 0 && self.autofiring(targets) != 0 {
+            self.turbo_start = self.frame;
         }
-        /////
-        if self.left_button.is_low().unwrap() {
-            if self.left_button_state == false {
-                gameboy.key_pressed(gb_core::hardware::input::Button::LEFT);
-                self.left_button_state = true;
-            }
-        } else {
-            if self.left_button_state == true {
-                gameboy.key_released(gb_core::hardware::input::Button::LEFT);
-                self.left_button_state = false;
-            }
-        }
-        /////
-        if self.right_button.is_low().unwrap() {
-            if self.right_button_state == false {
-                gameboy.key_pressed(gb_core::hardware::input::Button::RIGHT);
-                self.right_button_state = true;
+        self.held_targets = targets;
+
+        let buttons = self.game_buttons();
+        let changed = buttons ^ self.game_buttons;
+        self.game_buttons = buttons;
+        for (index, target) in TARGETS[..GAME_BUTTONS].iter().enumerate() {
+            if changed & (1 << index) == 0 {
+                continue;
             }
-        } else {
-            if self.right_button_state == true {
-                gameboy.key_released(gb_core::hardware::input::Button::RIGHT);
-                self.right_button_state = false;
+            let button = game_button(*target).unwrap();
+            if buttons & (1 << index) != 0 {
+                gameboy.key_pressed(button);
+            } else {
+                gameboy.key_released(button);
             }
         }
     }
+
+    fn next_frame(&mut self) {
+        self.frame = self.frame.wrapping_add(1);
+        self.turbo_update = self.autofiring(self.held_targets) != 0;
+    }
+
+    fn take_action(&mut self) -> Option<EmulatorAction> {
+        self.action.take()
+    }
+
+    fn fast_forward(&self) -> bool {
+        self.held_targets & InputTarget::FastForward.bit() != 0
+    }
+
+    fn menu_buttons(&mut self) -> MenuButtons<'_> {
+        MenuButtons::new(self.inputs)
+    }
+
+    fn set_controls(&mut self, mapping: ButtonMapping, turbo: Turbo) {
+        self.mapping = mapping;
+        self.turbo = turbo;
+        // The held inputs are mapped again on the next poll
+        self.turbo_update = true;
+    }
 }
+
 impl<'a> InputButtonMapper<'a> {
-    pub fn new(
-        a_button: &'a mut dyn InputPin<Error = Infallible>,
-        b_button: &'a mut dyn InputPin<Error = Infallible>,
-        start_button: &'a mut dyn InputPin<Error = Infallible>,
-        select_button: &'a mut dyn InputPin<Error = Infallible>,
-        up_button: &'a mut dyn InputPin<Error = Infallible>,
-        down_button: &'a mut dyn InputPin<Error = Infallible>,
-        left_button: &'a mut dyn InputPin<Error = Infallible>,
-        right_button: &'a mut dyn InputPin<Error = Infallible>,
-    ) -> Self {
+    pub fn new(inputs: &'a mut dyn InputSampler, mapping: ButtonMapping, turbo: Turbo) -> Self {
         Self {
-            b_button,
-            a_button,
-            select_button,
-            start_button,
-            up_button,
-            down_button,
-            left_button,
-            right_button,
-            a_button_state: false,
-            b_button_state: false,
-            select_button_state: false,
-            start_button_state: false,
-            up_button_state: false,
-            down_button_state: false,
-            left_button_state: false,
-            right_button_state: false,
+            inputs,
+            mapping,
+            hotkeys: HotkeyEngine::new(HOTKEY_LONG_PRESS_MS, HOTKEY_CHORD_WINDOW_MS),
+            turbo,
+            held_inputs: 0,
+            held_targets: 0,
+            game_buttons: 0,
+            frame: 0,
+            turbo_start: 0,
+            turbo_update: false,
+            action: None,
+        }
+    }
+
+    /// The held targets that autofire.
+    fn autofiring(&self, targets: u32) -> u32 {
+        let mut autofire = InputTarget::TurboA.bit() | InputTarget::TurboB.bit();
+        if self.turbo.a {
+            autofire |= InputTarget::A.bit();
+        }
+        if self.turbo.b {
+            autofire |= InputTarget::B.bit();
+        }
+        targets & autofire
+    }
+
+    /// The Game Boy buttons that are down on the current frame.
+    fn game_buttons(&self) -> u32 {
+        let autofiring = self.autofiring(self.held_targets);
+        let fire = self
+            .turbo
+            .pressed(self.frame.wrapping_sub(self.turbo_start));
+        let mut buttons = self.held_targets & !autofiring;
+        if fire {
+            buttons |= autofiring;
+        }
+        // The turbo targets press A and B
+        let turbo_buttons = [
+            (InputTarget::TurboA, InputTarget::A),
+            (InputTarget::TurboB, InputTarget::B),
+        ];
+        for (turbo, button) in turbo_buttons {
+            if buttons & turbo.bit() != 0 {
+                buttons |= button.bit();
+            }
         }
+        buttons & ((1 << GAME_BUTTONS) - 1)
     }
 }
@@ -0,0 +1,129 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use embedded_sdmmc::{Mode, RawFile};
+use gb_rp2350_logic::wav::wav_header;
+
+use super::screenshot::next_file_name;
+use crate::hardware::sdcard::SdStorage;
+
+const RECORDING_DIR: &str = "recordings";
+/// Samples in one 512 byte block of the SD card.
+pub const BLOCK_SAMPLES: usize = 256;
+/// Most blocks written in a frame. A frame records about 2 blocks, the extra room drains what
+/// piled up while the card was slow.
+pub const MAX_BLOCKS_PER_FRAME: usize = 4;
+
+/// What the emulator loop asks of the recording every frame.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RecordingRequest {
+    /// Write what was recorded so far.
+    Continue,
+    /// Start a recording, or stop the current one.
+    Toggle,
+    /// Stop the current recording, if any.
+    Stop,
+}
+
+/// 16 bit stereo WAV file being recorded to `/recordings/<game>/<n>.wav`.
+///
+/// The header is written with empty sizes and filled in by [`WavRecording::finish`].
+pub struct WavRecording {
+    file: RawFile,
+    file_name: String,
+    sample_rate: u32,
+    data_size: u32,
+}
+
+impl WavRecording {
+    pub fn start<
+        D: embedded_sdmmc::BlockDevice,
+        T: embedded_sdmmc::TimeSource,
+        DR: Fn(&mut D),
+        const MAX_DIRS: usize,
+        const MAX_FILES: usize,
+        const MAX_VOLUMES: usize,
+    >(
+        storage: &mut SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+        game_name: &str,
+        sample_rate: u32,
+    ) -> Result<Self, embedded_sdmmc::Error<D::Error>> {
+        storage.reset_device();
+        let (game_dir, file_name) = next_file_name(storage, RECORDING_DIR, game_name, "WAV")?;
+        defmt::info!(
+            "Recording audio: {}/{}",
+            defmt::Display2Format(&game_dir),
+            defmt::Display2Format(&file_name)
+        );
+        let file = storage.open_file(
+            &[RECORDING_DIR, game_dir.as_str()],
+            file_name.as_str(),
+            Mode::ReadWriteCreateOrTruncate,
+        )?;
+        let recording = Self {
+            file,
+            file_name: alloc::format!("{}/{}", game_dir, file_name),
+            sample_rate,
+            data_size: 0,
+        };
+        if let Err(error) = storage.volume_manager().write(file, &recording.header()) {
+            let _ = storage.close_file(file);
+            return Err(error);
+        }
+        Ok(recording)
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    /// Appends interleaved stereo samples.
+    pub fn write<
+        D: embedded_sdmmc::BlockDevice,
+        T: embedded_sdmmc::TimeSource,
+        DR: Fn(&mut D),
+        const MAX_DIRS: usize,
+        const MAX_FILES: usize,
+        const MAX_VOLUMES: usize,
+    >(
+        &mut self,
+        storage: &mut SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+        samples: &[u16],
+    ) -> Result<(), embedded_sdmmc::Error<D::Error>> {
+        let bytes: Vec<u8> = samples
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        storage.volume_manager().write(self.file, &bytes)?;
+        self.data_size += bytes.len() as u32;
+        Ok(())
+    }
+
+    /// Fills in the sizes of the header and closes the file.
+    pub fn finish<
+        D: embedded_sdmmc::BlockDevice,
+        T: embedded_sdmmc::TimeSource,
+        DR: Fn(&mut D),
+        const MAX_DIRS: usize,
+        const MAX_FILES: usize,
+        const MAX_VOLUMES: usize,
+    >(
+        self,
+        storage: &mut SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+    ) -> Result<String, embedded_sdmmc::Error<D::Error>> {
+        defmt::info!(
+            "Finished recording {}: {} bytes",
+            defmt::Display2Format(&self.file_name),
+            self.data_size
+        );
+        let volume_manager = storage.volume_manager();
+        let result = volume_manager
+            .file_seek_from_start(self.file, 0)
+            .and_then(|_| volume_manager.write(self.file, &self.header()));
+        storage.close_file(self.file)?;
+        result.map(|_| self.file_name)
+    }
+
+    fn header(&self) -> Vec<u8> {
+        wav_header(self.sample_rate, self.data_size)
+    }
+}
@@ -2,14 +2,23 @@ use crate::hal::timer::Instant;
 use core::cell::RefCell;
 
 use crate::hal::timer::TimerDevice;
+use crate::hardware::sdcard::SdStorage;
 use alloc::{
     boxed::Box,
+    rc::Rc,
     string::{String, ToString},
 };
 use const_lru::ConstLru;
 use defmt::{debug, info};
 use embedded_hal::delay::DelayNs;
-use embedded_sdmmc::{RawFile, RawVolume};
+use embedded_sdmmc::{Mode, RawFile};
+
+/// Name of the directory inside `saves` holding the ram banks of a game.
+pub fn save_directory_name(game_title: &str) -> String {
+    let mut game_dir_name = game_title.replace(" ", "").to_lowercase();
+    game_dir_name.truncate(game_dir_name.len().min(8));
+    game_dir_name
+}
 
 pub struct SdRomManager<
     D: embedded_sdmmc::BlockDevice,
@@ -22,14 +31,12 @@ pub struct SdRomManager<
     const MAX_VOLUMES: usize,
 > {
     _rom_name: String,
-    volume_manager: RefCell<embedded_sdmmc::VolumeManager<D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES>>,
+    storage: Rc<RefCell<SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>>>,
     raw_rom_file: RefCell<Option<RawFile>>,
-    raw_volume: RefCell<Option<RawVolume>>,
     bank_0: Box<[u8; 0x4000]>,
     bank_lru: RefCell<ConstLru<u8, Box<[u8; 0x4000]>, ROM_CACHE_SIZE, u8>>,
     start_time: Instant,
     timer: RefCell<crate::hal::Timer<DT>>,
-    device_reset: DR,
 }
 impl<
         D: embedded_sdmmc::BlockDevice,
@@ -44,24 +51,18 @@ impl<
 {
     pub fn new(
         rom_name: &str,
-        mut volume_manager: embedded_sdmmc::VolumeManager<D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+        storage: Rc<RefCell<SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>>>,
         timer: crate::hal::Timer<DT>,
-        device_reset: DR,
     ) -> Self {
-        let mut volume = volume_manager
-            .open_volume(embedded_sdmmc::VolumeIdx(0))
-            .unwrap();
-        let mut root_dir = volume.open_root_dir().unwrap();
-        let mut rom_file = root_dir
-            .open_file_in_dir(rom_name, embedded_sdmmc::Mode::ReadOnly)
-            .unwrap();
-
         let mut bank_0 = Box::new([0u8; 0x4000]);
-        rom_file.seek_from_start(0u32).unwrap();
-        rom_file.read(&mut *bank_0).unwrap();
-        let raw_rom_file = rom_file.to_raw_file();
-        root_dir.close().unwrap();
-        let raw_volume = volume.to_raw_volume();
+        let raw_rom_file = {
+            let mut storage = storage.borrow_mut();
+            let raw_rom_file = storage.open_file(&[], rom_name, Mode::ReadOnly).unwrap();
+            let mut rom_file = raw_rom_file.to_file(storage.volume_manager());
+            rom_file.seek_from_start(0u32).unwrap();
+            rom_file.read(&mut *bank_0).unwrap();
+            rom_file.to_raw_file()
+        };
 
         let lru = ConstLru::new();
 
@@ -69,13 +70,11 @@ impl<
             Self {
                 _rom_name: rom_name.to_string(),
                 bank_0: bank_0,
-                volume_manager: RefCell::new(volume_manager),
+                storage,
                 bank_lru: RefCell::new(lru),
-                raw_volume: RefCell::new(Some(raw_volume)),
                 raw_rom_file: RefCell::new(Some(raw_rom_file)),
                 start_time: timer.get_counter(),
                 timer: RefCell::new(timer),
-                device_reset,
             };
 
         result
@@ -86,10 +85,10 @@ impl<
         &self,
         bank_offset: usize,
     ) -> Result<Box<[u8; 0x4000]>, embedded_sdmmc::Error<D::Error>> {
-        let mut volume_manager = self.volume_manager.borrow_mut();
+        let mut storage = self.storage.borrow_mut();
 
         let raw_file = self.raw_rom_file.take().unwrap();
-        let mut file = raw_file.to_file(&mut volume_manager);
+        let mut file = raw_file.to_file(storage.volume_manager());
 
         let mut buffer: Box<[u8; 0x4000]> = Box::new([0u8; 0x4000]);
 
@@ -152,97 +151,39 @@ impl<
 
     fn save(&mut self, game_title: &str, bank_index: u8, bank: &[u8]) {
         self.timer.borrow_mut().delay_ms(10);
-        let mut volume_manager = self.volume_manager.borrow_mut();
-        (self.device_reset)(volume_manager.device());
-
-        let mut volume = self
-            .raw_volume
-            .take()
-            .unwrap()
-            .to_volume(&mut volume_manager);
-        let mut root_directory = volume.open_root_dir().unwrap();
-
-        if root_directory.find_directory_entry("saves").is_err() {
-            root_directory.make_dir_in_dir("saves").unwrap();
-        }
-        let mut game_dir_name = game_title.replace(" ", "").to_lowercase();
-        game_dir_name.truncate(game_dir_name.len().min(8));
-
-        let mut save_directory = root_directory.open_dir("saves").unwrap();
-        if save_directory
-            .find_directory_entry(game_dir_name.as_str())
-            .is_err()
-        {
-            save_directory
-                .make_dir_in_dir(game_dir_name.as_str())
-                .unwrap();
-        }
-        let mut game_directory = save_directory.open_dir(game_dir_name.as_str()).unwrap();
+        let mut storage = self.storage.borrow_mut();
+        storage.reset_device();
 
-        let mut bank_file = game_directory
-            .open_file_in_dir(
+        let game_dir_name = save_directory_name(game_title);
+        storage
+            .with_file(
+                &["saves", game_dir_name.as_str()],
                 alloc::format!("{}", bank_index).as_str(),
-                embedded_sdmmc::Mode::ReadWriteCreateOrTruncate,
+                Mode::ReadWriteCreateOrTruncate,
+                |bank_file| bank_file.write(bank),
             )
             .unwrap();
-
-        bank_file.write(bank).unwrap();
-        bank_file.close().unwrap();
-        game_directory.close().unwrap();
-        save_directory.close().unwrap();
-        root_directory.close().unwrap();
-
-        self.raw_volume.replace(Some(volume.to_raw_volume()));
     }
 
     fn load_to_bank(&mut self, game_title: &str, bank_index: u8, bank: &mut [u8]) {
         info!("Loading ram bank: {}", bank_index);
         self.timer.borrow_mut().delay_ms(10);
-        let mut volume_manager = self.volume_manager.borrow_mut();
-        (self.device_reset)(volume_manager.device());
-
-        let mut volume = self
-            .raw_volume
-            .take()
-            .unwrap()
-            .to_volume(&mut volume_manager);
-        let mut root_directory = volume.open_root_dir().unwrap();
-
-        if root_directory.find_directory_entry("saves").is_err() {
-            root_directory.make_dir_in_dir("saves").unwrap();
-        }
-        let mut game_dir_name = game_title.replace(" ", "").to_lowercase();
-        game_dir_name.truncate(game_dir_name.len().min(8));
-
-        let mut save_directory = root_directory.open_dir("saves").unwrap();
-        if save_directory
-            .find_directory_entry(game_dir_name.as_str())
-            .is_err()
-        {
-            save_directory
-                .make_dir_in_dir(game_dir_name.as_str())
-                .unwrap();
-        }
-        let mut game_directory = save_directory.open_dir(game_dir_name.as_str()).unwrap();
+        let mut storage = self.storage.borrow_mut();
+        storage.reset_device();
 
+        let game_dir_name = save_directory_name(game_title);
+        let save_path = ["saves", game_dir_name.as_str()];
         let bank_name = alloc::format!("{}", bank_index);
-        if game_directory
-            .find_directory_entry(bank_name.as_str())
-            .is_ok()
-        {
-            let mut bank_file = game_directory
-                .open_file_in_dir(bank_name.as_str(), embedded_sdmmc::Mode::ReadOnly)
+        if storage.exists(&save_path, bank_name.as_str()).unwrap() {
+            storage
+                .with_file(
+                    &save_path,
+                    bank_name.as_str(),
+                    Mode::ReadOnly,
+                    |bank_file| bank_file.read(bank),
+                )
                 .unwrap();
-
-            bank_file.read(bank).unwrap();
-            bank_file.close().unwrap();
         }
-
-        game_directory.close().unwrap();
-        save_directory.close().unwrap();
-        root_directory.close().unwrap();
-
-        self.raw_volume.replace(Some(volume.to_raw_volume()));
     }
 }
 impl<
@@ -0,0 +1,158 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_sdmmc::Mode;
+use gb_rp2350_logic::image::{encode_bmp, encode_png};
+
+use super::display::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::hardware::sdcard::SdStorage;
+
+const SCREENSHOT_DIR: &str = "screens";
+const SCALED_DIR: &str = "scaled";
+/// Files are written in chunks of this size, the SD card is much faster with whole blocks.
+const WRITE_CHUNK: usize = 4096;
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ScreenshotFormat {
+    Bmp,
+    Png,
+}
+
+impl ScreenshotFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ScreenshotFormat::Bmp => "BMP",
+            ScreenshotFormat::Png => "PNG",
+        }
+    }
+
+    fn encode<E>(
+        self,
+        frame: &[Rgb565],
+        width: usize,
+        height: usize,
+        write: &mut dyn FnMut(&[u8]) -> Result<(), E>,
+    ) -> Result<(), E> {
+        match self {
+            ScreenshotFormat::Bmp => encode_bmp(frame, width, height, write),
+            ScreenshotFormat::Png => encode_png(frame, width, height, write),
+        }
+    }
+}
+
+/// Saves a frame captured with `GameboyLineBufferDisplay::capture_next_frame` to
+/// `/screens/<game>/<n>`, a copy scaled to the on-screen size is saved with the same name in
+/// `/screens/<game>/scaled`. Returns the name of the files as `<game>/<n>`.
+pub fn save_screenshot<
+    D: embedded_sdmmc::BlockDevice,
+    T: embedded_sdmmc::TimeSource,
+    DR: Fn(&mut D),
+    const MAX_DIRS: usize,
+    const MAX_FILES: usize,
+    const MAX_VOLUMES: usize,
+>(
+    storage: &mut SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+    game_name: &str,
+    frame: &[Rgb565],
+    scaled_size: (usize, usize),
+    format: ScreenshotFormat,
+) -> Result<String, embedded_sdmmc::Error<D::Error>> {
+    storage.reset_device();
+    let (game_dir, file_name) =
+        next_file_name(storage, SCREENSHOT_DIR, game_name, format.extension())?;
+    let saved_name = alloc::format!("{}/{}", game_dir, file_name);
+    defmt::info!("Saving screenshot: {}", defmt::Display2Format(&saved_name));
+
+    let mut outputs = alloc::vec![(
+        &[SCREENSHOT_DIR, game_dir.as_str()][..],
+        SCREEN_WIDTH,
+        SCREEN_HEIGHT
+    )];
+    if scaled_size != (SCREEN_WIDTH, SCREEN_HEIGHT) {
+        outputs.push((
+            &[SCREENSHOT_DIR, game_dir.as_str(), SCALED_DIR][..],
+            scaled_size.0,
+            scaled_size.1,
+        ));
+    }
+    for (path, width, height) in outputs {
+        storage.with_file(
+            path,
+            file_name.as_str(),
+            Mode::ReadWriteCreateOrTruncate,
+            |file| {
+                let mut buffer: Vec<u8> = Vec::with_capacity(WRITE_CHUNK);
+                format.encode::<embedded_sdmmc::Error<D::Error>>(
+                    frame,
+                    width,
+                    height,
+                    &mut |bytes| {
+                        buffer.extend_from_slice(bytes);
+                        if buffer.len() >= WRITE_CHUNK {
+                            file.write(&buffer)?;
+                            buffer.clear();
+                        }
+                        Ok(())
+                    },
+                )?;
+                file.write(&buffer)
+            },
+        )?;
+    }
+    Ok(saved_name)
+}
+
+/// Picks the name of the next file in `<dir>/<game>`, every game has its own directory so
+/// games with similar names don't share a counter. The game name is shortened to an 8.3 directory
+/// name and files are numbered after the highest number already in it. Returns the game
+/// directory and the file name, or an error once every number is taken rather than overwriting
+/// a file.
+pub fn next_file_name<
+    D: embedded_sdmmc::BlockDevice,
+    T: embedded_sdmmc::TimeSource,
+    DR: Fn(&mut D),
+    const MAX_DIRS: usize,
+    const MAX_FILES: usize,
+    const MAX_VOLUMES: usize,
+>(
+    storage: &mut SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+    dir: &str,
+    game_name: &str,
+    extension: &str,
+) -> Result<(String, String), embedded_sdmmc::Error<D::Error>> {
+    const LAST_NUMBER: u32 = 99_999_999;
+    let base_name = game_name.split('.').next().unwrap_or(game_name);
+    let mut game_dir: String = base_name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .take(8)
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if game_dir.is_empty() {
+        game_dir.push_str("GAME");
+    }
+
+    let mut next = 0;
+    // The directory is created with the first file, until then the numbers start at 0
+    let listed = storage.iterate_dir(&[dir, game_dir.as_str()], |entry| {
+        if entry.attributes.is_directory() || entry.name.extension() != extension.as_bytes() {
+            return;
+        }
+        let number = core::str::from_utf8(entry.name.base_name())
+            .ok()
+            .filter(|name| name.bytes().all(|c| c.is_ascii_digit()))
+            .and_then(|name| name.parse::<u32>().ok());
+        if let Some(number) = number {
+            next = next.max(number.saturating_add(1));
+        }
+    });
+    match listed {
+        Ok(()) | Err(embedded_sdmmc::Error::NotFound) => {}
+        Err(error) => return Err(error),
+    }
+    if next > LAST_NUMBER {
+        return Err(embedded_sdmmc::Error::FileAlreadyExists);
+    }
+    let file_name = alloc::format!("{:08}.{}", next, extension);
+    Ok((game_dir, file_name))
+}
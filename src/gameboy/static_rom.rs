@@ -3,7 +3,12 @@ use embedded_hal::delay::DelayNs;
 
 use crate::hal::timer::Instant;
 use crate::hal::timer::TimerDevice;
+use crate::hardware::sdcard::SdStorage;
+use alloc::rc::Rc;
 use core::cell::RefCell;
+use embedded_sdmmc::Mode;
+
+use super::rom::save_directory_name;
 
 pub struct StaticRomManager<
     D: embedded_sdmmc::BlockDevice,
@@ -14,11 +19,10 @@ pub struct StaticRomManager<
     const MAX_FILES: usize,
     const MAX_VOLUMES: usize,
 > {
-    volume_manager: RefCell<embedded_sdmmc::VolumeManager<D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES>>,
+    storage: Rc<RefCell<SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>>>,
     rom: &'static [u8],
     start_time: Instant,
     timer: crate::hal::Timer<DT>,
-    device_reset: DR,
 }
 impl<
         D: embedded_sdmmc::BlockDevice,
@@ -32,16 +36,14 @@ impl<
 {
     pub fn new(
         rom: &'static [u8],
-        volume_manager: embedded_sdmmc::VolumeManager<D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+        storage: Rc<RefCell<SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>>>,
         timer: crate::hal::Timer<DT>,
-        device_reset: DR,
     ) -> Self {
         let result: StaticRomManager<D, T, DT, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES> = Self {
             rom,
-            volume_manager: RefCell::new(volume_manager),
+            storage,
             start_time: timer.get_counter(),
             timer,
-            device_reset,
         };
 
         result
@@ -55,34 +57,16 @@ impl<
         info!("Saving ram bank: {}", bank_index);
         self.timer.delay_ms(10);
 
-        let mut volume_manager = self.volume_manager.borrow_mut();
-        (self.device_reset)(volume_manager.device());
-
-        let mut volume = volume_manager.open_volume(embedded_sdmmc::VolumeIdx(0))?;
-        let mut root_directory = volume.open_root_dir()?;
-
-        if root_directory.find_directory_entry("saves").is_err() {
-            root_directory.make_dir_in_dir("saves")?;
-        }
-        let mut game_dir_name = game_title.replace(" ", "").to_lowercase();
-        game_dir_name.truncate(game_dir_name.len().min(8));
+        let mut storage = self.storage.borrow_mut();
+        storage.reset_device();
 
-        let mut save_directory = root_directory.open_dir("saves")?;
-        if save_directory
-            .find_directory_entry(game_dir_name.as_str())
-            .is_err()
-        {
-            save_directory.make_dir_in_dir(game_dir_name.as_str())?;
-        }
-        let mut game_directory = save_directory.open_dir(game_dir_name.as_str())?;
-
-        let mut bank_file = game_directory.open_file_in_dir(
+        let game_dir_name = save_directory_name(game_title);
+        storage.with_file(
+            &["saves", game_dir_name.as_str()],
             alloc::format!("{}", bank_index).as_str(),
-            embedded_sdmmc::Mode::ReadWriteCreateOrTruncate,
-        )?;
-
-        bank_file.write(bank)?;
-        Ok(())
+            Mode::ReadWriteCreateOrTruncate,
+            |bank_file| bank_file.write(bank),
+        )
     }
 }
 impl<
@@ -131,42 +115,21 @@ impl<
     fn load_to_bank(&mut self, game_title: &str, bank_index: u8, bank: &mut [u8]) {
         info!("Loading ram bank: {}", bank_index);
         self.timer.delay_ms(10);
-        let mut volume_manager = self.volume_manager.borrow_mut();
-        (self.device_reset)(volume_manager.device());
-
-        let mut volume = volume_manager
-            .open_volume(embedded_sdmmc::VolumeIdx(0))
-            .unwrap();
-        let mut root_directory = volume.open_root_dir().unwrap();
-
-        if root_directory.find_directory_entry("saves").is_err() {
-            root_directory.make_dir_in_dir("saves").unwrap();
-        }
-        let mut game_dir_name = game_title.replace(" ", "").to_lowercase();
-        game_dir_name.truncate(game_dir_name.len().min(8));
-
-        let mut save_directory = root_directory.open_dir("saves").unwrap();
-        if save_directory
-            .find_directory_entry(game_dir_name.as_str())
-            .is_err()
-        {
-            save_directory
-                .make_dir_in_dir(game_dir_name.as_str())
-                .unwrap();
-        }
-        let mut game_directory = save_directory.open_dir(game_dir_name.as_str()).unwrap();
+        let mut storage = self.storage.borrow_mut();
+        storage.reset_device();
 
+        let game_dir_name = save_directory_name(game_title);
+        let save_path = ["saves", game_dir_name.as_str()];
         let bank_name = alloc::format!("{}", bank_index);
-        if game_directory
-            .find_directory_entry(bank_name.as_str())
-            .is_ok()
-        {
-            let mut bank_file = game_directory
-                .open_file_in_dir(bank_name.as_str(), embedded_sdmmc::Mode::ReadOnly)
+        if storage.exists(&save_path, bank_name.as_str()).unwrap() {
+            storage
+                .with_file(
+                    &save_path,
+                    bank_name.as_str(),
+                    Mode::ReadOnly,
+                    |bank_file| bank_file.read(bank),
+                )
                 .unwrap();
-
-            bank_file.read(bank).unwrap();
-            bank_file.close().unwrap();
         }
     }
 }
@@ -0,0 +1,43 @@
+use embedded_hal_0_2::adc::{Channel, OneShot};
+use gb_rp2350_logic::battery::BatteryGauge;
+
+use super::SharedAdc;
+use crate::hal::Adc;
+
+/// Ratio between the battery voltage and the voltage on `BATTERY_ADC`.
+#[const_env::from_env]
+const BATTERY_DIVIDER: u32 = 2;
+#[const_env::from_env]
+const BATTERY_EMPTY_MV: u32 = 3300;
+#[const_env::from_env]
+const BATTERY_FULL_MV: u32 = 4200;
+
+/// Battery voltage on an ADC pin behind a voltage divider, shown in the stats corner.
+pub struct Battery<P> {
+    adc: SharedAdc,
+    pin: P,
+    gauge: BatteryGauge,
+}
+
+impl<P> Battery<P>
+where
+    P: Channel<Adc>,
+    Adc: OneShot<Adc, u16, P>,
+{
+    pub fn new(adc: SharedAdc, pin: P) -> Self {
+        Self {
+            adc,
+            pin,
+            gauge: BatteryGauge::new(BATTERY_DIVIDER, BATTERY_EMPTY_MV, BATTERY_FULL_MV),
+        }
+    }
+
+    /// Reads the pin and returns the charge level in percent, `None` if the ADC failed
+    /// before the first reading.
+    pub fn read_percent(&mut self) -> Option<u8> {
+        match OneShot::<Adc, u16, P>::read(&mut *self.adc.borrow_mut(), &mut self.pin) {
+            Ok(reading) => Some(self.gauge.update(reading)),
+            Err(_) => self.gauge.millivolts().map(|_| self.gauge.percent()),
+        }
+    }
+}
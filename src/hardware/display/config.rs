@@ -0,0 +1,54 @@
+use mipidsi::options::{ColorInversion, ColorOrder, Orientation, Rotation};
+
+pub use gb_rp2350_logic::config::{DisplayConfig, DISPLAY_CONFIG_FILE};
+
+use crate::util::config_entries;
+
+/// Applies the `KEY=value` entries of `display.cfg`, unknown keys and invalid values are
+/// reported and ignored.
+pub fn apply_display_config(config: &mut DisplayConfig, text: &str) {
+    for (key, value) in config_entries(text) {
+        match config.apply_entry(key, value) {
+            Some(true) => {}
+            Some(false) => defmt::warn!("Invalid value for display setting {}: {}", key, value),
+            None => defmt::warn!("Unknown display setting: {}", key),
+        }
+    }
+}
+
+/// The panel options of a `DisplayConfig` as the mipidsi builder takes them.
+pub trait PanelOptions {
+    fn orientation(&self) -> Orientation;
+    fn color_inversion(&self) -> ColorInversion;
+    fn color_order(&self) -> ColorOrder;
+}
+
+impl PanelOptions for DisplayConfig {
+    fn orientation(&self) -> Orientation {
+        Orientation {
+            rotation: match self.rotation {
+                90 => Rotation::Deg90,
+                180 => Rotation::Deg180,
+                270 => Rotation::Deg270,
+                _ => Rotation::Deg0,
+            },
+            mirrored: self.mirrored,
+        }
+    }
+
+    fn color_inversion(&self) -> ColorInversion {
+        if self.invert_colors {
+            ColorInversion::Inverted
+        } else {
+            ColorInversion::Normal
+        }
+    }
+
+    fn color_order(&self) -> ColorOrder {
+        if self.bgr {
+            ColorOrder::Bgr
+        } else {
+            ColorOrder::Rgb
+        }
+    }
+}
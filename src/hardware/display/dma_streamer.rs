@@ -58,6 +58,35 @@ where
 
         sm
     }
+    /// Streams RGB565 pixels as the three bytes of RGB666, for panels that only accept
+    /// 18 bit colour on their serial interface.
+    #[inline(always)]
+    pub fn stream_rgb666<TO>(&mut self, tx: TO, iterator: &mut dyn Iterator<Item = u16>) -> TO
+    where
+        TO: WriteTarget<TransmittedWord = u8> + EndlessWriteTarget,
+    {
+        let channel1 = core::mem::replace(&mut self.dma_channel1, None).unwrap();
+        let channel2 = core::mem::replace(&mut self.dma_channel2, None).unwrap();
+
+        let spare_buffer: &'static mut [u8] =
+            ToMutByteSlice::to_mut_byte_slice(self.spare_buffer.take().unwrap());
+        let spare_buffer2: &'static mut [u8] =
+            ToMutByteSlice::to_mut_byte_slice(self.spare_buffer2.take().unwrap());
+        let main_buffer: &'static mut [u8] =
+            ToMutByteSlice::to_mut_byte_slice(self.main_buffer.take().unwrap());
+        let stream = DmaTransfer::new(channel1, channel2, tx, main_buffer, spare_buffer2);
+
+        let (stream, spare_buffer) = Self::compute_line_rgb666(stream, spare_buffer, iterator);
+
+        let (channel1, channel2, sm, main_buffer, spare_buffer2) = stream.free();
+        self.main_buffer = Some(AsMutSliceOf::as_mut_slice_of::<u16>(main_buffer).unwrap());
+        self.spare_buffer = Some(AsMutSliceOf::as_mut_slice_of::<u16>(spare_buffer).unwrap());
+        self.spare_buffer2 = Some(AsMutSliceOf::as_mut_slice_of::<u16>(spare_buffer2).unwrap());
+        self.dma_channel1 = Some(channel1);
+        self.dma_channel2 = Some(channel2);
+
+        sm
+    }
     #[inline(always)]
     pub fn stream_16b<TO, F>(&mut self, tx: TO, iterator: &mut dyn Iterator<Item = u16>, f: F) -> TO
     where
@@ -137,4 +166,31 @@ where
         transfer.wait();
         (transfer, buffer)
     }
+
+    #[inline(always)]
+    fn compute_line_rgb666<T: LineTransfer<Item = u8>>(
+        mut transfer: T,
+        mut buffer: &'static mut [u8],
+        iterator: &mut dyn Iterator<Item = u16>,
+    ) -> (T, &'static mut [u8]) {
+        // Only whole pixels are sent in every transfer
+        let usable_length = buffer.len() / 3 * 3;
+        let mut width_position = 0;
+        for pixel in iterator {
+            buffer[width_position] = (pixel >> 8) as u8 & 0b1111_1000;
+            buffer[width_position + 1] = (pixel >> 3) as u8 & 0b1111_1100;
+            buffer[width_position + 2] = (pixel << 3) as u8;
+            width_position += 3;
+            if width_position == usable_length {
+                buffer = transfer.send_scanline(buffer, usable_length as u32);
+                width_position = 0;
+            }
+        }
+
+        if width_position > 0 {
+            buffer = transfer.send_scanline(buffer, width_position as u32);
+        }
+        transfer.wait();
+        (transfer, buffer)
+    }
 }
@@ -0,0 +1,264 @@
+use crate::rp_hal::hal;
+
+use core::convert::Infallible;
+use core::sync::atomic::{AtomicU16, Ordering};
+
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::{DrawTarget, IntoStorage, OriginDimensions, Pixel, Size};
+use gb_rp2350_logic::dvi_lines::{ScaledLineGenerator, DVI_640X480};
+use hal::dma::{double_buffer::Config, EndlessWriteTarget, ReadTarget, SingleChannel, WriteTarget};
+use hal::multicore::{Core, Stack};
+use hal::pac;
+
+use super::PixelSink;
+
+/// Size of the image kept in RAM, the line generator scales it to the DVI mode while streaming.
+pub const DVI_FRAMEBUFFER_WIDTH: usize = 160;
+pub const DVI_FRAMEBUFFER_HEIGHT: usize = 144;
+/// Words needed for the two scanline buffers handed to [`DviOutput::start`].
+pub const DVI_LINE_BUFFER_WORDS: usize = DVI_640X480.line_words() * 2;
+
+/// The TMDS bit clock is 10 times the 25.2MHz pixel clock and HSTX shifts two bits per cycle.
+const HSTX_CLOCK_HZ: u32 = 126_000_000;
+const HSTX_CTRL_BASE: usize = 0x400C_0000;
+const HSTX_CSR: usize = 0x00;
+const HSTX_BIT0: usize = 0x04;
+const HSTX_EXPAND_SHIFT: usize = 0x24;
+const HSTX_EXPAND_TMDS: usize = 0x28;
+const HSTX_FIFO_ADDRESS: u32 = 0x5060_0004;
+const DREQ_HSTX: u8 = 52;
+const CLOCKS_BASE: usize = 0x4001_0000;
+const CLK_HSTX_CTRL: usize = 0x54;
+const CLK_HSTX_DIV: usize = 0x58;
+
+static FRAMEBUFFER: [AtomicU16; DVI_FRAMEBUFFER_WIDTH * DVI_FRAMEBUFFER_HEIGHT] =
+    [const { AtomicU16::new(0) }; DVI_FRAMEBUFFER_WIDTH * DVI_FRAMEBUFFER_HEIGHT];
+static CORE1_STACK: Stack<4096> = Stack::new();
+
+/// GPIOs of the differential pairs as `(positive, negative)`, HSTX is only available on
+/// GPIO 12 to 19.
+pub struct DviPins {
+    pub clock: (u8, u8),
+    /// TMDS lanes 0 (blue), 1 (green) and 2 (red).
+    pub lanes: [(u8, u8); 3],
+}
+
+/// 640x480 DVI output through the HSTX block. Drawing only updates a 160x144 framebuffer, core 1
+/// builds every scanline from it and keeps the HSTX FIFO fed through two chained DMA channels.
+pub struct DviOutput {
+    _hstx: pac::HSTX_CTRL,
+}
+
+impl DviOutput {
+    pub fn start<CH1, CH2>(
+        hstx: pac::HSTX_CTRL,
+        resets: &mut pac::RESETS,
+        pins: DviPins,
+        system_clock_hz: u32,
+        channels: (CH1, CH2),
+        line_buffers: &'static mut [u32],
+        core1: &mut Core,
+    ) -> Self
+    where
+        CH1: SingleChannel + Send + 'static,
+        CH2: SingleChannel + Send + 'static,
+    {
+        configure_clock(system_clock_hz);
+        resets.reset().modify(|_, w| w.hstx().clear_bit());
+        while resets.reset_done().read().hstx().bit_is_clear() {
+            hal::arch::nop();
+        }
+        configure_hstx(&pins);
+
+        core1
+            .spawn(CORE1_STACK.take().unwrap(), move || {
+                scan_out(channels, line_buffers)
+            })
+            .unwrap();
+        Self { _hstx: hstx }
+    }
+
+    #[inline(always)]
+    fn store(x: usize, y: usize, color: Rgb565) {
+        FRAMEBUFFER[y * DVI_FRAMEBUFFER_WIDTH + x].store(color.into_storage(), Ordering::Relaxed);
+    }
+}
+
+impl OriginDimensions for DviOutput {
+    fn size(&self) -> Size {
+        Size::new(DVI_FRAMEBUFFER_WIDTH as u32, DVI_FRAMEBUFFER_HEIGHT as u32)
+    }
+}
+
+impl DrawTarget for DviOutput {
+    type Color = Rgb565;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if let Ok((x, y)) = <(u32, u32)>::try_from(point) {
+                if (x as usize) < DVI_FRAMEBUFFER_WIDTH && (y as usize) < DVI_FRAMEBUFFER_HEIGHT {
+                    Self::store(x as usize, y as usize, color);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PixelSink for DviOutput {
+    fn set_pixels<T>(
+        &mut self,
+        sx: u16,
+        sy: u16,
+        ex: u16,
+        ey: u16,
+        colors: T,
+    ) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = Rgb565>,
+    {
+        let ex = (ex as usize).min(DVI_FRAMEBUFFER_WIDTH - 1);
+        let ey = (ey as usize).min(DVI_FRAMEBUFFER_HEIGHT - 1);
+        let (mut x, mut y) = (sx as usize, sy as usize);
+        for color in colors {
+            if y > ey {
+                break;
+            }
+            Self::store(x, y, color);
+            x += 1;
+            if x > ex {
+                x = sx as usize;
+                y += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs on core 1, each line is generated while the previous one is being sent.
+fn scan_out<CH1, CH2>(channels: (CH1, CH2), line_buffers: &'static mut [u32]) -> !
+where
+    CH1: SingleChannel,
+    CH2: SingleChannel,
+{
+    let generator = ScaledLineGenerator::new(
+        DVI_640X480,
+        DVI_FRAMEBUFFER_WIDTH as u32,
+        DVI_FRAMEBUFFER_HEIGHT as u32,
+    );
+    let total_lines = DVI_640X480.total_lines();
+    let pixel = |x: u32, y: u32| {
+        FRAMEBUFFER[y as usize * DVI_FRAMEBUFFER_WIDTH + x as usize].load(Ordering::Relaxed)
+    };
+
+    let (first, second) = line_buffers.split_at_mut(line_buffers.len() / 2);
+    let mut first = LineBuffer {
+        words: first,
+        len: 0,
+    };
+    let mut second = LineBuffer {
+        words: second,
+        len: 0,
+    };
+    first.len = generator.write_line(0, first.words, pixel) as u32;
+    second.len = generator.write_line(1, second.words, pixel) as u32;
+
+    let mut transfer = Config::new(channels, first, HstxFifo)
+        .start()
+        .read_next(second);
+    let mut line = 2;
+    loop {
+        let (mut sent, running) = transfer.wait();
+        sent.len = generator.write_line(line, sent.words, pixel) as u32;
+        transfer = running.read_next(sent);
+        line = (line + 1) % total_lines;
+    }
+}
+
+fn configure_clock(system_clock_hz: u32) {
+    let divider = ((system_clock_hz + HSTX_CLOCK_HZ / 2) / HSTX_CLOCK_HZ).clamp(1, 3);
+    defmt::info!("DVI: HSTX clock {} Hz", system_clock_hz / divider);
+    // SAFETY: CLK_HSTX is not used by anything else, the clock is stopped while it is changed
+    unsafe {
+        let base = CLOCKS_BASE as *mut u32;
+        core::ptr::write_volatile(base.byte_add(CLK_HSTX_CTRL), 0);
+        core::ptr::write_volatile(base.byte_add(CLK_HSTX_DIV), divider << 16);
+        // ENABLE with clk_sys as the source
+        core::ptr::write_volatile(base.byte_add(CLK_HSTX_CTRL), 1 << 11);
+    }
+}
+
+fn configure_hstx(pins: &DviPins) {
+    let write = |offset: usize, value: u32| unsafe {
+        core::ptr::write_volatile((HSTX_CTRL_BASE as *mut u32).byte_add(offset), value)
+    };
+    const BIT_INV: u32 = 1 << 16;
+    const BIT_CLK: u32 = 1 << 17;
+    let bit = |gpio: u8| HSTX_BIT0 + (gpio as usize - 12) * 4;
+
+    write(HSTX_CSR, 0);
+    // Pixels come as two RGB565 values per word, control symbols as a whole raw word
+    write(HSTX_EXPAND_SHIFT, (2 << 24) | (16 << 16) | (1 << 8));
+    // Rotate each channel so its most significant bit lands on bit 7 of its lane
+    write(
+        HSTX_EXPAND_TMDS,
+        (4 << 21) | (8 << 16) | (5 << 13) | (3 << 8) | (4 << 5) | 29,
+    );
+
+    write(bit(pins.clock.0), BIT_CLK);
+    write(bit(pins.clock.1), BIT_CLK | BIT_INV);
+    for (lane, (positive, negative)) in pins.lanes.iter().enumerate() {
+        // Even bits go out in the first half of each HSTX cycle, odd bits in the second half
+        let select = (lane as u32 * 10) | ((lane as u32 * 10 + 1) << 8);
+        write(bit(*positive), select);
+        write(bit(*negative), select | BIT_INV);
+    }
+
+    // Clock period of 5 cycles, 5 shifts of 2 bits per word, expander and HSTX enabled
+    write(HSTX_CSR, (5 << 28) | (5 << 16) | (2 << 8) | (1 << 1) | 1);
+}
+
+struct LineBuffer {
+    words: &'static mut [u32],
+    len: u32,
+}
+
+unsafe impl ReadTarget for LineBuffer {
+    type ReceivedWord = u32;
+
+    fn rx_treq() -> Option<u8> {
+        None
+    }
+
+    fn rx_address_count(&self) -> (u32, u32) {
+        (self.words.as_ptr() as u32, self.len)
+    }
+
+    fn rx_increment(&self) -> bool {
+        true
+    }
+}
+
+struct HstxFifo;
+
+unsafe impl WriteTarget for HstxFifo {
+    type TransmittedWord = u32;
+
+    fn tx_treq() -> Option<u8> {
+        Some(DREQ_HSTX)
+    }
+
+    fn tx_address_count(&mut self) -> (u32, u32) {
+        (HSTX_FIFO_ADDRESS, u32::MAX)
+    }
+
+    fn tx_increment(&self) -> bool {
+        false
+    }
+}
+
+impl EndlessWriteTarget for HstxFifo {}
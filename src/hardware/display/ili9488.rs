@@ -16,7 +16,7 @@ pub struct ILI9488Rgb565;
 impl Model for ILI9488Rgb565 {
     type ColorFormat = Rgb565;
 
-    const FRAMEBUFFER_SIZE: (u16, u16) = (240, 320);
+    const FRAMEBUFFER_SIZE: (u16, u16) = (320, 480);
 
     fn init<RST, DELAY, DI>(
         &mut self,
@@ -55,6 +55,54 @@ impl Model for ILI9488Rgb565 {
     }
 }
 
+/// ILI9488 running in 18 bit colour, the only colour depth its SPI interface supports.
+///
+/// Pixels are still handed over as RGB565, the display interface has to be created with
+/// `BusPixelFormat::Rgb666` so they are expanded to RGB666 while they are streamed.
+pub struct ILI9488Rgb666;
+
+impl Model for ILI9488Rgb666 {
+    type ColorFormat = Rgb565;
+
+    const FRAMEBUFFER_SIZE: (u16, u16) = (320, 480);
+
+    fn init<RST, DELAY, DI>(
+        &mut self,
+        dcs: &mut mipidsi::dcs::Dcs<DI>,
+        delay: &mut DELAY,
+        options: &mipidsi::options::ModelOptions,
+        rst: &mut Option<RST>,
+    ) -> Result<mipidsi::dcs::SetAddressMode, mipidsi::error::InitError<RST::Error>>
+    where
+        RST: embedded_hal::digital::OutputPin,
+        DELAY: embedded_hal::delay::DelayNs,
+        DI: display_interface::WriteOnlyDataCommand,
+    {
+        match rst {
+            Some(ref mut rst) => self.hard_reset(rst, delay)?,
+            None => dcs.write_command(SoftReset)?,
+        }
+        let pf = PixelFormat::with_all(BitsPerPixel::Eighteen);
+        init_common(dcs, delay, options, pf).map_err(Into::into)
+    }
+
+    fn write_pixels<DI, I>(
+        &mut self,
+        dcs: &mut mipidsi::dcs::Dcs<DI>,
+        colors: I,
+    ) -> Result<(), mipidsi::error::Error>
+    where
+        DI: display_interface::WriteOnlyDataCommand,
+        I: IntoIterator<Item = Self::ColorFormat>,
+    {
+        dcs.write_command(WriteMemoryStart)?;
+        let mut iter = colors.into_iter().map(|c| c.into_storage());
+
+        let buf = DataFormat::U16BEIter(&mut iter);
+        dcs.di.send_data(buf)
+    }
+}
+
 /// Common init for all ILI934x controllers and color formats.
 pub fn init_common<DELAY, DI>(
     dcs: &mut Dcs<DI>,
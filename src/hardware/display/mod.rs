@@ -1,19 +1,77 @@
+mod config;
 mod dma_streamer;
 mod dma_transfer;
+#[cfg(feature = "dvi_output")]
+mod dvi;
 mod parallel_8bit_interface;
 mod scaler;
 mod spi_pio_interface;
+mod tearing;
 
 mod ili9488;
-pub use ili9488::ILI9488Rgb565;
+#[allow(unused_imports)]
+pub use ili9488::{ILI9488Rgb565, ILI9488Rgb666};
 
+pub use config::{apply_display_config, DisplayConfig, PanelOptions, DISPLAY_CONFIG_FILE};
 pub use dma_streamer::DmaStreamer;
 use dma_transfer::DmaTransfer;
+#[cfg(feature = "dvi_output")]
+pub use dvi::{DviOutput, DviPins, DVI_LINE_BUFFER_WORDS};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::DrawTarget;
+pub use gb_rp2350_logic::line_diff::LineChangeTracker;
 #[allow(unused_imports)]
 pub use parallel_8bit_interface::Parallel8BitDmaInterface;
 pub use scaler::ScreenScaler;
 #[allow(unused_imports)]
 pub use spi_pio_interface::SpiPioDmaInterface;
+pub use tearing::{FrameSync, NoFrameSync};
+#[allow(unused_imports)]
+pub use tearing::{TearingEffectSync, TearingMode};
+
+/// Encoding of the pixel data on the bus, pixels are always handed to the interfaces as RGB565.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum BusPixelFormat {
+    Rgb565,
+    /// Every pixel is expanded to three bytes while it is streamed.
+    Rgb666,
+}
+
+/// Destination of the emulated frames, implemented by `mipidsi` panels and the DVI output.
+pub trait PixelSink: DrawTarget<Color = Rgb565> {
+    /// Writes `colors` row by row into the window from `(sx, sy)` to `(ex, ey)` inclusive.
+    fn set_pixels<T>(
+        &mut self,
+        sx: u16,
+        sy: u16,
+        ex: u16,
+        ey: u16,
+        colors: T,
+    ) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = Rgb565>;
+}
+
+impl<DI, M, RST> PixelSink for mipidsi::Display<DI, M, RST>
+where
+    DI: display_interface::WriteOnlyDataCommand,
+    M: mipidsi::models::Model<ColorFormat = Rgb565>,
+    RST: embedded_hal::digital::OutputPin,
+{
+    fn set_pixels<T>(
+        &mut self,
+        sx: u16,
+        sy: u16,
+        ex: u16,
+        ey: u16,
+        colors: T,
+    ) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = Rgb565>,
+    {
+        mipidsi::Display::set_pixels(self, sx, sy, ex, ey, colors)
+    }
+}
 
 trait LineTransfer {
     type Item;
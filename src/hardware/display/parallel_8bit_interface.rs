@@ -12,7 +12,7 @@ use hal::dma::Byte;
 use hal::dma::SingleChannel;
 use hal::timer::TimerDevice;
 
-use super::DmaStreamer;
+use super::{BusPixelFormat, DmaStreamer};
 pub struct Parallel8BitDmaInterface<RS, P: PIOExt, SM: StateMachineIndex, CH1, CH2, TD: TimerDevice>
 {
     sm: StateMachine<(P, SM), Running>,
@@ -22,6 +22,7 @@ pub struct Parallel8BitDmaInterface<RS, P: PIOExt, SM: StateMachineIndex, CH1, C
     rs: RS,
     streamer: DmaStreamer<CH1, CH2>,
     timer: crate::hal::Timer<TD>,
+    pixel_format: BusPixelFormat,
 }
 
 impl<RS, P, SM, CH1, CH2, TD: TimerDevice> Parallel8BitDmaInterface<RS, P, SM, CH1, CH2, TD>
@@ -41,6 +42,7 @@ where
         pins: (u8, u8),
         streamer: DmaStreamer<CH1, CH2>,
         timer: crate::hal::Timer<TD>,
+        pixel_format: BusPixelFormat,
     ) -> Self {
         let video_program = pio_proc::pio_asm!(
             ".side_set 1 opt",
@@ -89,6 +91,7 @@ where
             labels: labels,
             streamer,
             timer,
+            pixel_format,
         }
     }
 
@@ -127,8 +130,28 @@ where
         (sm, self.rs)
     }
 
+    #[inline(always)]
+    fn send_rgb666(&mut self, iter: &mut dyn Iterator<Item = u16>) -> Result {
+        self.set_8bit_mode();
+        let tx = self.tx.take().unwrap();
+        let tx = self.streamer.stream_rgb666(tx.transfer_size(Byte), iter);
+        self.tx = Some(tx.transfer_size(HalfWord));
+        Ok(())
+    }
+
     #[inline(always)]
     fn send_data(&mut self, words: DataFormat<'_>) -> Result {
+        if self.pixel_format == BusPixelFormat::Rgb666 {
+            // 16 bit data is only used for pixels
+            match words {
+                DataFormat::U16(slice) => return self.send_rgb666(&mut slice.iter().cloned()),
+                DataFormat::U16BE(slice) => return self.send_rgb666(&mut slice.iter().cloned()),
+                DataFormat::U16LE(slice) => return self.send_rgb666(&mut slice.iter().cloned()),
+                DataFormat::U16BEIter(iter) => return self.send_rgb666(iter),
+                DataFormat::U16LEIter(iter) => return self.send_rgb666(iter),
+                _ => {}
+            }
+        }
         match words {
             DataFormat::U8(slice) => {
                 self.set_8bit_mode();
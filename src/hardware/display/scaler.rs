@@ -41,8 +41,41 @@ impl<
             iterator,
             &self.width_ceil_calcs,
             &self.height_ceil_calcs,
+            0,
+            0,
         );
     }
+
+    /// Scales an iterator whose first pixel belongs to the given input line instead of the
+    /// top of the frame.
+    #[inline(always)]
+    pub fn scale_iterator_from<'a, T, I>(
+        &'a self,
+        line: usize,
+        iterator: I,
+    ) -> impl Iterator<Item = T> + 'a
+    where
+        I: Iterator<Item = T> + 'a,
+        T: Default + Copy + 'a,
+    {
+        return ScalerIterator::<'a, T, IN_HEIGHT, IN_WIDTH, OUT_HEIGHT, OUT_WIDTH, I>::new(
+            iterator,
+            &self.width_ceil_calcs,
+            &self.height_ceil_calcs,
+            line as u16,
+            self.output_row(line),
+        );
+    }
+
+    /// First output row produced for the given input line.
+    #[inline(always)]
+    pub fn output_row(&self, line: usize) -> u16 {
+        if line == 0 {
+            0
+        } else {
+            self.height_ceil_calcs[line - 1]
+        }
+    }
 }
 
 struct ScalerIterator<
@@ -77,11 +110,17 @@ where
     I: Iterator<Item = T>,
     T: Default + Copy,
 {
-    pub fn new(iterator: I, width_ceil_calcs: &'a [u16], height_ceil_calcs: &'a [u16]) -> Self {
+    pub fn new(
+        iterator: I,
+        width_ceil_calcs: &'a [u16],
+        height_ceil_calcs: &'a [u16],
+        input_current_scan_line: u16,
+        output_current_scan_line: u16,
+    ) -> Self {
         Self {
             iterator: iterator,
-            input_current_scan_line: 0,
-            output_current_scan_line: 0,
+            input_current_scan_line,
+            output_current_scan_line,
             scaled_scan_line_buffer: alloc::vec![T::default(); OUT_WIDTH],
             scaled_line_buffer_repeat: 0,
             current_scaled_line_index: 0,
@@ -14,7 +14,7 @@ use hal::dma::SingleChannel;
 use hal::pio::Stopped;
 use rp235x_hal::timer::TimerDevice;
 
-use super::DmaStreamer;
+use super::{BusPixelFormat, DmaStreamer};
 
 pub struct SpiPioDmaInterface<
     RS,
@@ -29,6 +29,7 @@ pub struct SpiPioDmaInterface<
     mode: Option<PioMode<P, SM1, SM2>>,
     rs: RS,
     timer: crate::hal::Timer<TD>,
+    pixel_format: BusPixelFormat,
 }
 
 enum PioMode<P: PIOExt, SM1: StateMachineIndex, SM2: StateMachineIndex> {
@@ -70,6 +71,7 @@ where
         tx: u8,
         streamer: DmaStreamer<CH1, CH2>,
         timer: crate::hal::Timer<TD>,
+        pixel_format: BusPixelFormat,
     ) -> Self {
         let video_program =
             pio_proc::pio_asm!(".side_set 1 ", "out pins, 1 side 0 ", "nop side 1",);
@@ -120,6 +122,7 @@ where
             rs,
             mode: Some(PioMode::ByteMode((byte_sm, half_word_sm))),
             timer,
+            pixel_format,
         }
     }
 
@@ -186,6 +189,15 @@ where
         is_idle
     }
 
+    #[inline(always)]
+    fn send_rgb666(&mut self, iter: &mut dyn Iterator<Item = u16>) -> Result {
+        let pio_mode = core::mem::replace(&mut self.mode, None).unwrap();
+        let (mut byte_sm, half_byte_sm) = Self::set_8bit_mode(pio_mode);
+        byte_sm.tx = self.streamer.stream_rgb666(byte_sm.tx, iter);
+        self.mode = Some(PioMode::ByteMode((byte_sm, half_byte_sm)));
+        Ok(())
+    }
+
     #[inline(always)]
     fn send_data(&mut self, words: DataFormat<'_>) -> Result
     where
@@ -194,6 +206,17 @@ where
         SM2: StateMachineIndex,
         RS: OutputPin,
     {
+        if self.pixel_format == BusPixelFormat::Rgb666 {
+            // 16 bit data is only used for pixels
+            match words {
+                DataFormat::U16(slice) => return self.send_rgb666(&mut slice.iter().cloned()),
+                DataFormat::U16BE(slice) => return self.send_rgb666(&mut slice.iter().cloned()),
+                DataFormat::U16LE(slice) => return self.send_rgb666(&mut slice.iter().cloned()),
+                DataFormat::U16BEIter(iter) => return self.send_rgb666(iter),
+                DataFormat::U16LEIter(iter) => return self.send_rgb666(iter),
+                _ => {}
+            }
+        }
         match words {
             DataFormat::U8(slice) => {
                 let pio_mode = core::mem::replace(&mut self.mode, None).unwrap();
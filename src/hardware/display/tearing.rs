@@ -0,0 +1,99 @@
+use display_interface::WriteOnlyDataCommand;
+use embedded_hal::digital::{InputPin, OutputPin};
+use mipidsi::models::Model;
+use mipidsi::Display;
+use rp235x_hal::timer::TimerDevice;
+
+const SET_TEAR_OFF: u8 = 0x34;
+const SET_TEAR_ON: u8 = 0x35;
+const SET_TEAR_SCANLINE: u8 = 0x44;
+
+/// Longest a frame waits for the TE pulse before streaming anyway, a 60Hz panel pulses every ~16.7ms.
+const TE_TIMEOUT: fugit::MicrosDurationU64 = fugit::MicrosDurationU64::millis(40);
+
+pub trait FrameSync {
+    /// Blocks until the panel is ready for the next frame to be written.
+    fn wait_for_frame_start(&mut self);
+}
+
+/// Frames are written as soon as the emulator produces them.
+pub struct NoFrameSync;
+
+impl FrameSync for NoFrameSync {
+    #[inline(always)]
+    fn wait_for_frame_start(&mut self) {}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum TearingMode {
+    /// TE pulses when the panel enters vertical blanking, a frame has to be written before
+    /// the panel starts scanning it out again.
+    VBlank,
+    /// TE pulses once the panel has scanned out the given line, writes then trail the scan out.
+    /// Used when a full frame cannot be sent within the vertical blanking period, the panel has
+    /// to scan in the same direction the frame is written for this to be tear free.
+    Scanline(u16),
+}
+
+/// Synchronises the start of every frame with the tearing effect (TE) output of the panel.
+pub struct TearingEffectSync<P: InputPin, TD: TimerDevice> {
+    te_pin: P,
+    timer: crate::hal::Timer<TD>,
+    timed_out: bool,
+}
+
+impl<P: InputPin, TD: TimerDevice> TearingEffectSync<P, TD> {
+    pub fn new(te_pin: P, timer: crate::hal::Timer<TD>) -> Self {
+        Self {
+            te_pin,
+            timer,
+            timed_out: false,
+        }
+    }
+
+    /// Turns on the TE output of the panel.
+    pub fn enable<DI, M, RST>(
+        &mut self,
+        display: &mut Display<DI, M, RST>,
+        mode: TearingMode,
+    ) -> Result<(), mipidsi::error::Error>
+    where
+        DI: WriteOnlyDataCommand,
+        M: Model,
+        RST: OutputPin,
+    {
+        let dcs = unsafe { display.dcs() };
+        dcs.write_raw(SET_TEAR_OFF, &[])?;
+        // Mode 0, TE only reports the vertical blanking
+        dcs.write_raw(SET_TEAR_ON, &[0x00])?;
+        let scanline = match mode {
+            TearingMode::VBlank => 0,
+            TearingMode::Scanline(line) => line,
+        };
+        dcs.write_raw(SET_TEAR_SCANLINE, &scanline.to_be_bytes())?;
+        defmt::info!("Tearing effect sync enabled: {}", mode);
+        Ok(())
+    }
+}
+
+impl<P: InputPin, TD: TimerDevice> FrameSync for TearingEffectSync<P, TD> {
+    fn wait_for_frame_start(&mut self) {
+        let start = self.timer.get_counter();
+        // Wait for a rising edge so a frame never starts in the middle of a TE pulse
+        let mut was_low = false;
+        loop {
+            let is_high = self.te_pin.is_high().unwrap_or(false);
+            if is_high && was_low {
+                return;
+            }
+            was_low |= !is_high;
+            if self.timer.get_counter() - start >= TE_TIMEOUT {
+                if !self.timed_out {
+                    defmt::warn!("No tearing effect signal from the display, is TE connected?");
+                    self.timed_out = true;
+                }
+                return;
+            }
+        }
+    }
+}
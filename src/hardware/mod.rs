@@ -1,5 +1,11 @@
+#[cfg(feature = "battery")]
+pub mod battery;
 pub mod display;
 pub mod flash;
 pub mod psram;
 pub mod sdcard;
 pub mod sound;
+
+/// The ADC, shared between the analog stick and the battery pin.
+#[cfg(any(feature = "joystick", feature = "battery"))]
+pub type SharedAdc = alloc::rc::Rc<core::cell::RefCell<crate::hal::Adc>>;
@@ -1,3 +1,9 @@
+use alloc::vec::Vec;
+use embedded_sdmmc::{
+    BlockDevice, DirEntry, Error, File, Mode, RawDirectory, RawFile, RawVolume, TimeSource,
+    VolumeIdx, VolumeManager,
+};
+
 #[derive(Default)]
 pub struct DummyTimesource();
 
@@ -15,3 +21,176 @@ impl embedded_sdmmc::TimeSource for DummyTimesource {
         }
     }
 }
+
+/// Owns the SD card volume so the rom loaders, save games and any other feature writing to
+/// the card can share it.
+///
+/// The first volume is opened once and kept open, directories are addressed as a list of
+/// names relative to the root. They are only created when a file is created in them.
+pub struct SdStorage<
+    D: BlockDevice,
+    T: TimeSource,
+    DR: Fn(&mut D),
+    const MAX_DIRS: usize,
+    const MAX_FILES: usize,
+    const MAX_VOLUMES: usize,
+> {
+    volume_manager: VolumeManager<D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+    volume: RawVolume,
+    device_reset: DR,
+}
+
+impl<
+        D: BlockDevice,
+        T: TimeSource,
+        DR: Fn(&mut D),
+        const MAX_DIRS: usize,
+        const MAX_FILES: usize,
+        const MAX_VOLUMES: usize,
+    > SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>
+{
+    pub fn new(
+        mut volume_manager: VolumeManager<D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+        device_reset: DR,
+    ) -> Result<Self, Error<D::Error>> {
+        let volume = volume_manager.open_raw_volume(VolumeIdx(0))?;
+        Ok(Self {
+            volume_manager,
+            volume,
+            device_reset,
+        })
+    }
+
+    pub fn volume_manager(&mut self) -> &mut VolumeManager<D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES> {
+        &mut self.volume_manager
+    }
+
+    /// Forces the card to be initialised again before the next access, used before writes
+    /// that follow a long period of inactivity.
+    pub fn reset_device(&mut self) {
+        (self.device_reset)(self.volume_manager.device());
+    }
+
+    /// Whether `name` is in the directory at `path`, a missing directory counts as the file
+    /// missing. Nothing is created.
+    pub fn exists(&mut self, path: &[&str], name: &str) -> Result<bool, Error<D::Error>> {
+        let dir = match self.open_dir_path(path) {
+            Ok(dir) => dir,
+            Err(Error::NotFound) => return Ok(false),
+            Err(error) => return Err(error),
+        };
+        let found = match self.volume_manager.find_directory_entry(dir, name) {
+            Ok(_) => Ok(true),
+            Err(Error::NotFound) => Ok(false),
+            Err(error) => Err(error),
+        };
+        self.volume_manager.close_dir(dir)?;
+        found
+    }
+
+    /// Calls `func` for every entry of the directory at `path`, fails with `Error::NotFound`
+    /// if it doesn't exist.
+    pub fn iterate_dir<F>(&mut self, path: &[&str], func: F) -> Result<(), Error<D::Error>>
+    where
+        F: FnMut(&DirEntry),
+    {
+        let dir = self.open_dir_path(path)?;
+        let result = self.volume_manager.iterate_dir(dir, func);
+        self.volume_manager.close_dir(dir)?;
+        result
+    }
+
+    /// Opens a file that stays open until it is closed with `close_file`. Modes that create
+    /// the file also create the missing directories of `path`.
+    pub fn open_file(
+        &mut self,
+        path: &[&str],
+        name: &str,
+        mode: Mode,
+    ) -> Result<RawFile, Error<D::Error>> {
+        let creates = matches!(
+            mode,
+            Mode::ReadWriteCreate | Mode::ReadWriteCreateOrTruncate | Mode::ReadWriteCreateOrAppend
+        );
+        let dir = if creates {
+            self.create_dir_path(path)?
+        } else {
+            self.open_dir_path(path)?
+        };
+        let file = self.volume_manager.open_file_in_dir(dir, name, mode);
+        self.volume_manager.close_dir(dir)?;
+        file
+    }
+
+    pub fn close_file(&mut self, file: RawFile) -> Result<(), Error<D::Error>> {
+        self.volume_manager.close_file(file)
+    }
+
+    /// Reads a whole file, meant for small configuration files.
+    pub fn read_file(&mut self, path: &[&str], name: &str) -> Result<Vec<u8>, Error<D::Error>> {
+        self.with_file(path, name, Mode::ReadOnly, |file| {
+            let mut content = alloc::vec![0u8; file.length() as usize];
+            let mut read = 0;
+            while read < content.len() {
+                let bytes = file.read(&mut content[read..])?;
+                if bytes == 0 {
+                    break;
+                }
+                read += bytes;
+            }
+            content.truncate(read);
+            Ok(content)
+        })
+    }
+
+    /// Opens a file for the duration of `func`.
+    pub fn with_file<R, F>(
+        &mut self,
+        path: &[&str],
+        name: &str,
+        mode: Mode,
+        func: F,
+    ) -> Result<R, Error<D::Error>>
+    where
+        F: FnOnce(
+            &mut File<'_, D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+        ) -> Result<R, Error<D::Error>>,
+    {
+        let raw_file = self.open_file(path, name, mode)?;
+        let mut file = raw_file.to_file(&mut self.volume_manager);
+        let result = func(&mut file);
+        file.close()?;
+        result
+    }
+
+    /// Opens the directory at `path`, fails with `Error::NotFound` if any part is missing.
+    fn open_dir_path(&mut self, path: &[&str]) -> Result<RawDirectory, Error<D::Error>> {
+        let mut dir = self.volume_manager.open_root_dir(self.volume)?;
+        for name in path {
+            let child = self.volume_manager.open_dir(dir, *name);
+            self.volume_manager.close_dir(dir)?;
+            dir = child?;
+        }
+        Ok(dir)
+    }
+
+    /// Opens the directory at `path`, creating the parts that are missing.
+    fn create_dir_path(&mut self, path: &[&str]) -> Result<RawDirectory, Error<D::Error>> {
+        let mut dir = self.volume_manager.open_root_dir(self.volume)?;
+        for name in path {
+            let created = match self.volume_manager.find_directory_entry(dir, *name) {
+                Ok(_) => Ok(()),
+                Err(Error::NotFound) => self.volume_manager.make_dir_in_dir(dir, *name),
+                Err(error) => Err(error),
+            };
+            if let Err(error) = created {
+                self.volume_manager.close_dir(dir)?;
+                return Err(error);
+            }
+            let child = self.volume_manager.open_dir(dir, *name);
+            self.volume_manager.close_dir(dir)?;
+            dir = child?;
+        }
+        Ok(dir)
+    }
+}
@@ -0,0 +1,282 @@
+use crate::rp_hal::hal;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use critical_section::Mutex;
+use hal::dma::double_buffer::{Config as DConfig, ReadNext, Transfer as DTransfer};
+use hal::dma::{EndlessWriteTarget, ReadTarget, SingleChannel, Word, WriteTarget};
+use hal::pac::interrupt;
+
+use super::ring::AudioRing;
+use super::Resampler;
+use super::{SharedAudioStats, SharedVolume, UNITY_GAIN};
+
+/// Largest correction of the output rate, reached when the ring is empty or full.
+const RATE_CONTROL_PPM: i32 = 5000;
+/// Samples the ring holds between the emulation and the DMA interrupt.
+const RING_SAMPLES: usize = 8192;
+/// Fill level of the ring the rate control aims for, when the emulation is paced by the audio
+/// it waits while the ring holds more than this.
+const RING_TARGET_SAMPLES: u32 = RING_SAMPLES as u32 / 2;
+
+static AUDIO_RING: AudioRing<RING_SAMPLES> = AudioRing::new();
+/// Refills the DMA chunks from `AUDIO_RING`, run by the DMA interrupt.
+static AUDIO_REFILL: Mutex<RefCell<Option<Box<dyn Refill + Send>>>> =
+    Mutex::new(RefCell::new(None));
+/// Chunks that couldn't be filled from the ring and were padded with silence.
+static UNDERRUNS: AtomicU32 = AtomicU32::new(0);
+
+/// Audio output streamed by DMA from a lock free ring, it resamples and applies the volume to
+/// the samples of gb-core before the output converts them to its own format.
+///
+/// Playing only pushes to the ring, every time the DMA finishes a chunk its interrupt chains
+/// the next one, filled from the ring and padded with silence when the emulation fell behind.
+/// Only one output can exist, the ring and the interrupt are shared.
+///
+/// Samples are interleaved stereo 16 bit values, each DMA transfer of `W` carries
+/// `size_of::<W>() / 2` of them.
+pub struct DmaAudioOutput<W: Word> {
+    sample_rate: u32,
+    resampler: Resampler,
+    volume: SharedVolume,
+    stats: SharedAudioStats,
+    audio_master: bool,
+    channel_ids: [u8; 2],
+    /// Resampled samples before they are pushed to the ring.
+    scratch: Vec<u16>,
+    _word: PhantomData<W>,
+}
+
+impl<W: Word> DmaAudioOutput<W> {
+    /// `sample_rate` is the rate gb-core generates samples at, `resampler` converts them to the
+    /// rate the output consumes them at. `buffer` is split in the two chunks the DMA alternates
+    /// between, the output starts by playing them filled with `silence`.
+    ///
+    /// With `audio_master` the emulation is paced by the output clock, playing waits while the
+    /// ring holds more than its target level. Otherwise it never waits and samples that don't
+    /// fit in the ring are dropped.
+    pub fn new<CH1, CH2, TO>(
+        sample_rate: u32,
+        resampler: Resampler,
+        channels: (CH1, CH2),
+        target: TO,
+        buffer: &'static mut [u16],
+        silence: u16,
+        volume: SharedVolume,
+        stats: SharedAudioStats,
+        audio_master: bool,
+    ) -> Self
+    where
+        CH1: SingleChannel + Send + 'static,
+        CH2: SingleChannel + Send + 'static,
+        TO: WriteTarget<TransmittedWord = W> + EndlessWriteTarget + Send + 'static,
+        W: Send + 'static,
+    {
+        buffer.fill(silence);
+        let (buffer1, buffer2) = buffer.split_at_mut(buffer.len() / 2);
+        let (mut channel1, mut channel2) = channels;
+        channel1.enable_irq0();
+        channel2.enable_irq0();
+        let channel_ids = [channel1.id(), channel2.id()];
+        let transfer = DConfig::new((channel1, channel2), ChunkReadTarget::new(buffer1), target)
+            .start()
+            .read_next(ChunkReadTarget::new(buffer2));
+
+        critical_section::with(|cs| {
+            let mut refill = AUDIO_REFILL.borrow_ref_mut(cs);
+            assert!(refill.is_none(), "Only one audio output can be created");
+            *refill = Some(Box::new(DmaRefill {
+                transfer: Some(transfer),
+                channel_mask: (1 << channel_ids[0]) | (1 << channel_ids[1]),
+                silence,
+                primed: false,
+            }));
+        });
+        // SAFETY: the handler only touches the state installed above
+        unsafe { cortex_m::peripheral::NVIC::unmask(hal::pac::Interrupt::DMA_IRQ_0) };
+
+        Self {
+            sample_rate,
+            resampler,
+            volume,
+            stats,
+            audio_master,
+            channel_ids,
+            scratch: Vec::new(),
+            _word: PhantomData,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Queues `samples` in the ring, `convert` turns the resampled samples into the words sent
+    /// to the output, in place.
+    pub fn play<F: FnMut(&mut [u16])>(&mut self, samples: &[u16], mut convert: F) {
+        if self.audio_master {
+            while AUDIO_RING.len() as u32 > RING_TARGET_SAMPLES {
+                cortex_m::asm::wfi();
+            }
+        }
+        let pending = self.pending_samples();
+        let mut stats = self.stats.get();
+        stats.buffered_frames = pending / 2;
+        stats.underruns = UNDERRUNS.load(Ordering::Relaxed);
+        self.adjust_rate();
+
+        let capacity = self.resampler.max_output_len(samples.len());
+        self.scratch.resize(capacity, 0);
+        let written = self.resampler.process(samples, &mut self.scratch);
+        let output = &mut self.scratch[..written];
+        let gain = self.volume.get().gain();
+        if gain != UNITY_GAIN {
+            // Samples are signed 16 bit values
+            for sample in output.iter_mut() {
+                *sample = ((*sample as i16 as i32 * gain as i32) >> 15) as i16 as u16;
+            }
+        }
+        convert(output);
+
+        let pushed = AUDIO_RING.push(output);
+        stats.dropped_frames += (written - pushed) as u32 / 2;
+        self.stats.set(stats);
+    }
+
+    pub fn underflowed(&self) -> bool {
+        self.pending_samples() == 0
+    }
+
+    /// Samples in the ring plus the ones the DMA still has to send, read from the transfer
+    /// counters of the channels.
+    fn pending_samples(&self) -> u32 {
+        // SAFETY: only reads the status of the two channels owned by this output
+        let dma = unsafe { &*hal::pac::DMA::ptr() };
+        let in_flight: u32 = self
+            .channel_ids
+            .iter()
+            .map(|id| {
+                let channel = dma.ch(*id as usize);
+                if channel.ch_al1_ctrl().read().busy().bit_is_set() {
+                    channel.ch_trans_count().read().count().bits()
+                } else {
+                    0
+                }
+            })
+            .sum();
+        in_flight * samples_per_word::<W>() + AUDIO_RING.len() as u32
+    }
+
+    /// Nudges the resampler so the ring stays half full, it stretches the audio a little when
+    /// the emulation falls behind instead of letting the output run dry.
+    fn adjust_rate(&mut self) {
+        let target = RING_TARGET_SAMPLES as i32;
+        let error = (AUDIO_RING.len() as i32 - target).clamp(-target, target);
+        self.resampler
+            .set_rate_adjustment(error * RATE_CONTROL_PPM / target);
+    }
+}
+
+#[interrupt]
+fn DMA_IRQ_0() {
+    critical_section::with(|cs| {
+        if let Some(refill) = AUDIO_REFILL.borrow_ref_mut(cs).as_mut() {
+            refill.refill();
+        }
+    });
+}
+
+/// Type erased [`DmaRefill`], so the interrupt doesn't need to know the channels and target.
+trait Refill {
+    fn refill(&mut self);
+}
+
+struct DmaRefill<CH1, CH2, TO, W>
+where
+    CH1: SingleChannel,
+    CH2: SingleChannel,
+    TO: WriteTarget<TransmittedWord = W> + EndlessWriteTarget,
+    W: Word,
+{
+    transfer: Option<DTransfer<CH1, CH2, ChunkReadTarget<W>, TO, ReadNext<ChunkReadTarget<W>>>>,
+    channel_mask: u32,
+    silence: u16,
+    /// Set once the emulation pushed samples, the ring is empty until then.
+    primed: bool,
+}
+
+impl<CH1, CH2, TO, W> Refill for DmaRefill<CH1, CH2, TO, W>
+where
+    CH1: SingleChannel,
+    CH2: SingleChannel,
+    TO: WriteTarget<TransmittedWord = W> + EndlessWriteTarget,
+    W: Word,
+{
+    fn refill(&mut self) {
+        // SAFETY: only clears the interrupt flags of the two channels owned by this output
+        let dma = unsafe { &*hal::pac::DMA::ptr() };
+        dma.ints0().write(|w| unsafe { w.bits(self.channel_mask) });
+
+        let transfer = self.transfer.take().unwrap();
+        if !transfer.is_done() {
+            self.transfer = Some(transfer);
+            return;
+        }
+        // The next chunk is already playing, refill the one that just finished
+        let (chunk, transfer) = transfer.wait();
+        let popped = AUDIO_RING.pop_into(chunk.array);
+        if popped > 0 {
+            self.primed = true;
+        }
+        if popped < chunk.array.len() {
+            // Silence rather than replaying stale samples
+            chunk.array[popped..].fill(self.silence);
+            if self.primed {
+                UNDERRUNS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.transfer = Some(transfer.read_next(chunk));
+    }
+}
+
+fn samples_per_word<W: Word>() -> u32 {
+    (core::mem::size_of::<W>() / 2) as u32
+}
+
+/// One of the two chunks the DMA alternates between, always sent whole.
+struct ChunkReadTarget<W> {
+    array: &'static mut [u16],
+    _word: PhantomData<W>,
+}
+
+impl<W: Word> ChunkReadTarget<W> {
+    fn new(array: &'static mut [u16]) -> Self {
+        Self {
+            array,
+            _word: PhantomData,
+        }
+    }
+}
+
+unsafe impl<W: Word> ReadTarget for ChunkReadTarget<W> {
+    type ReceivedWord = W;
+
+    fn rx_treq() -> Option<u8> {
+        None
+    }
+
+    fn rx_address_count(&self) -> (u32, u32) {
+        (
+            self.array.as_ptr() as u32,
+            self.array.len() as u32 / samples_per_word::<W>(),
+        )
+    }
+
+    fn rx_increment(&self) -> bool {
+        true
+    }
+}
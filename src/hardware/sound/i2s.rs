@@ -0,0 +1,104 @@
+use crate::rp_hal::hal;
+
+use hal::dma::SingleChannel;
+use hal::pio::{PIOExt, StateMachineIndex, Tx, UninitStateMachine, PIO};
+
+use super::dma_output::DmaAudioOutput;
+use super::Resampler;
+use super::{SharedAudioStats, SharedVolume};
+
+type ToType<P, SM> = Tx<(P, SM), hal::dma::HalfWord>;
+
+/// Sends the samples to an I2S amplifier such as the MAX98357A through a PIO state machine.
+pub struct I2sPioInterface {
+    output: DmaAudioOutput<u16>,
+}
+
+impl I2sPioInterface {
+    /// `sample_rate` is the rate gb-core generates samples at, `resampler` converts them to the
+    /// rate set by `clock_divider`. See [`DmaAudioOutput::new`] for `audio_master`.
+    pub fn new<CH1, CH2, P, SM>(
+        sample_rate: u32,
+        resampler: Resampler,
+        channel: CH1,
+        channel2: CH2,
+        clock_divider: (u16, u8),
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_pin: (u8, u8),
+        data_pin: u8,
+        buffer: &'static mut [u16],
+        volume: SharedVolume,
+        stats: SharedAudioStats,
+        audio_master: bool,
+    ) -> Self
+    where
+        CH1: SingleChannel + Send + 'static,
+        CH2: SingleChannel + Send + 'static,
+        P: PIOExt,
+        SM: StateMachineIndex,
+        ToType<P, SM>: Send + 'static,
+    {
+        let audio_program = pio_proc::pio_asm!(
+            ".side_set 2",
+            "    set x, 14          side 0b01", // side 0bWB - W = Word Clock, B = Bit Clock
+            "left_data:",
+            "    out pins, 1        side 0b00",
+            "    jmp x-- left_data  side 0b01",
+            "    out pins 1         side 0b10",
+            "    set x, 14          side 0b11",
+            "right_data:",
+            "    out pins 1         side 0b10",
+            "    jmp x-- right_data side 0b11",
+            "    out pins 1         side 0b00",
+        );
+
+        let video_program_installed = pio.install(&audio_program.program).unwrap();
+        let (mut video_sm, _rx, vid_tx) =
+            hal::pio::PIOBuilder::from_installed_program(video_program_installed)
+                .out_pins(data_pin, 1)
+                .side_set_pin_base(clock_pin.0)
+                .out_shift_direction(hal::pio::ShiftDirection::Left)
+                .autopull(true)
+                .out_sticky(false)
+                .pull_threshold(16)
+                .buffers(hal::pio::Buffers::OnlyTx)
+                .clock_divisor_fixed_point(clock_divider.0, clock_divider.1)
+                .build(sm);
+        video_sm.set_pindirs((data_pin..data_pin + 1 as u8).map(|n| (n, hal::pio::PinDir::Output)));
+        video_sm.set_pindirs(
+            (clock_pin.0..clock_pin.1 + 1 as u8).map(|n| (n, hal::pio::PinDir::Output)),
+        );
+        let _ = video_sm.start();
+        let to_dest = vid_tx.transfer_size(hal::dma::HalfWord);
+
+        Self {
+            output: DmaAudioOutput::new(
+                sample_rate,
+                resampler,
+                (channel, channel2),
+                to_dest,
+                buffer,
+                0,
+                volume,
+                stats,
+                audio_master,
+            ),
+        }
+    }
+}
+
+impl gb_core::hardware::sound::AudioPlayer for I2sPioInterface {
+    fn play(&mut self, output_buffer: &[u16]) {
+        // The PIO program sends the signed samples as they are
+        self.output.play(output_buffer, |_| {});
+    }
+
+    fn samples_rate(&self) -> u32 {
+        self.output.sample_rate()
+    }
+
+    fn underflowed(&self) -> bool {
+        self.output.underflowed()
+    }
+}
@@ -0,0 +1,132 @@
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+
+use gb_core::hardware::sound::AudioPlayer;
+
+/// How the two channels of gb-core are sent to the output.
+#[derive(Clone, Copy, PartialEq, Eq, Default, defmt::Format)]
+pub enum OutputMode {
+    #[default]
+    Stereo,
+    /// Both channels mixed together, for builds with a single speaker.
+    Mono,
+    /// Left and right exchanged.
+    Swapped,
+}
+
+const OUTPUT_MODES: [OutputMode; 3] = [OutputMode::Stereo, OutputMode::Mono, OutputMode::Swapped];
+
+impl OutputMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputMode::Stereo => "Stereo",
+            OutputMode::Mono => "Mono",
+            OutputMode::Swapped => "Swapped",
+        }
+    }
+
+    /// Parses the value used in the settings file (`STEREO`, `MONO` or `SWAPPED`).
+    pub fn from_config(value: &str) -> Option<Self> {
+        OUTPUT_MODES
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(value))
+    }
+
+    /// Next mode in the settings menu, `step` is `1` or `-1`.
+    pub fn cycle(self, step: i8) -> Self {
+        let index = OUTPUT_MODES.iter().position(|mode| *mode == self).unwrap();
+        let count = OUTPUT_MODES.len() as i8;
+        OUTPUT_MODES[(index as i8 + step).rem_euclid(count) as usize]
+    }
+
+    /// Mixes interleaved stereo frames in place.
+    fn apply(self, samples: &mut [u16]) {
+        match self {
+            OutputMode::Stereo => {}
+            OutputMode::Mono => {
+                for frame in samples.chunks_exact_mut(2) {
+                    let mono = (frame[0] as i16 as i32 + frame[1] as i16 as i32) / 2;
+                    frame[0] = mono as i16 as u16;
+                    frame[1] = mono as i16 as u16;
+                }
+            }
+            OutputMode::Swapped => {
+                for frame in samples.chunks_exact_mut(2) {
+                    frame.swap(0, 1);
+                }
+            }
+        }
+    }
+}
+
+/// Output mode shared between the mixer and the settings.
+pub type SharedOutputMode = Rc<Cell<OutputMode>>;
+
+/// Copy of the mixed samples for a recording, `None` while nothing is recorded. The owner of the
+/// recording drains it.
+pub type RecordingTap = Rc<RefCell<Option<Vec<u16>>>>;
+
+/// Set while the emulation is fast-forwarded, the samples are dropped so the audio pacing
+/// doesn't hold the emulation back to real time.
+pub type SharedFastForward = Rc<Cell<bool>>;
+
+/// Mixing stage in front of an audio output, gb-core hands its samples to it instead of the
+/// output.
+///
+/// gb-core only passes the already mixed stereo stream to the player, the APU channels can't be
+/// told apart here. Muting or soloing the square, wave and noise channels needs gb-core to
+/// either enable them one by one or hand them to the player separately.
+pub struct Mixer<P: AudioPlayer> {
+    player: P,
+    output_mode: SharedOutputMode,
+    recording: RecordingTap,
+    fast_forward: SharedFastForward,
+    buffer: Vec<u16>,
+}
+
+impl<P: AudioPlayer> Mixer<P> {
+    pub fn new(
+        player: P,
+        output_mode: SharedOutputMode,
+        recording: RecordingTap,
+        fast_forward: SharedFastForward,
+    ) -> Self {
+        Self {
+            player,
+            output_mode,
+            recording,
+            fast_forward,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl<P: AudioPlayer> AudioPlayer for Mixer<P> {
+    fn play(&mut self, output_buffer: &[u16]) {
+        if self.fast_forward.get() {
+            return;
+        }
+        let mode = self.output_mode.get();
+        let samples = if mode == OutputMode::Stereo {
+            output_buffer
+        } else {
+            self.buffer.clear();
+            self.buffer.extend_from_slice(output_buffer);
+            mode.apply(&mut self.buffer);
+            &self.buffer
+        };
+        if let Some(recording) = self.recording.borrow_mut().as_mut() {
+            recording.extend_from_slice(samples);
+        }
+        self.player.play(samples);
+    }
+
+    fn samples_rate(&self) -> u32 {
+        self.player.samples_rate()
+    }
+
+    fn underflowed(&self) -> bool {
+        self.player.underflowed()
+    }
+}
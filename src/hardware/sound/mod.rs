@@ -0,0 +1,94 @@
+use alloc::rc::Rc;
+use core::cell::Cell;
+use num_traits::Float;
+
+mod dma_output;
+#[cfg(not(feature = "audio_pwm"))]
+mod i2s;
+mod mixer;
+#[cfg(feature = "audio_pwm")]
+mod pwm;
+mod ring;
+
+pub use gb_rp2350_logic::resampler::{Resampler, ResamplerQuality};
+#[cfg(not(feature = "audio_pwm"))]
+pub use i2s::I2sPioInterface;
+pub use mixer::{Mixer, OutputMode, RecordingTap, SharedFastForward, SharedOutputMode};
+#[cfg(feature = "audio_pwm")]
+pub use pwm::PwmAudioInterface;
+
+/// Samples in each of the two chunks the DMA alternates between, the audio buffer of an output
+/// holds two of them.
+pub const DMA_CHUNK_SAMPLES: usize = 512;
+
+/// Number of volume steps above silence.
+pub const VOLUME_STEPS: u8 = 16;
+/// Attenuation between two volume steps, the lowest step is 37.5dB below full volume.
+const VOLUME_STEP_DB: f32 = 2.5;
+/// Gain that leaves the samples unchanged, gains are fixed point with 15 fractional bits.
+const UNITY_GAIN: u16 = 1 << 15;
+
+/// Software volume applied to the samples before they are sent to the amplifier.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Volume {
+    /// 0 is silent, `VOLUME_STEPS` plays the samples unchanged.
+    pub level: u8,
+    pub muted: bool,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self {
+            level: VOLUME_STEPS,
+            muted: false,
+        }
+    }
+}
+
+impl Volume {
+    pub fn up(&mut self) {
+        self.level = (self.level + 1).min(VOLUME_STEPS);
+        self.muted = false;
+    }
+
+    pub fn down(&mut self) {
+        self.level = self.level.saturating_sub(1);
+        self.muted = false;
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+    }
+
+    pub fn percent(&self) -> u8 {
+        (self.level as u16 * 100 / VOLUME_STEPS as u16) as u8
+    }
+
+    /// Steps are evenly spaced in decibels so every step sounds like the same change.
+    fn gain(&self) -> u16 {
+        if self.muted || self.level == 0 {
+            return 0;
+        }
+        let level = self.level.min(VOLUME_STEPS);
+        let db = (level as f32 - VOLUME_STEPS as f32) * VOLUME_STEP_DB;
+        (10f32.powf(db / 20.0) * UNITY_GAIN as f32) as u16
+    }
+}
+
+/// Volume shared between the audio output and the controls that change it.
+pub type SharedVolume = Rc<Cell<Volume>>;
+
+/// State of the audio output, updated every time samples are played.
+#[derive(Clone, Copy, Default, defmt::Format)]
+pub struct AudioStats {
+    /// Stereo frames still queued in the ring and the DMA when the last samples arrived.
+    pub buffered_frames: u32,
+    /// DMA chunks the ring couldn't fill, the missing samples were played as silence.
+    pub underruns: u32,
+    /// Frames thrown away because the ring was full, only when the emulation is not paced by
+    /// the audio.
+    pub dropped_frames: u32,
+}
+
+/// Audio stats shared with the emulation loop.
+pub type SharedAudioStats = Rc<Cell<AudioStats>>;
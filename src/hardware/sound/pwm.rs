@@ -0,0 +1,182 @@
+use crate::rp_hal::hal;
+
+use hal::dma::{EndlessWriteTarget, SingleChannel, WriteTarget};
+use hal::pac;
+
+use super::dma_output::DmaAudioOutput;
+use super::Resampler;
+use super::{SharedAudioStats, SharedVolume};
+
+const DREQ_PWM_WRAP0: u8 = 32;
+/// Largest quantisation error carried to the next sample by the noise shaping, in 1/65536 of a
+/// PWM level.
+const MAX_SHAPING_ERROR: i64 = 1 << 16;
+
+/// Plays the samples as the duty cycle of a PWM slice, the compare register is written by DMA
+/// every time the counter wraps so the PWM frequency is the output sample rate. Meant for a
+/// piezo or a speaker/headphones behind an RC filter.
+///
+pub struct PwmAudioInterface {
+    output: DmaAudioOutput<u32>,
+    encoder: PwmEncoder,
+}
+
+impl PwmAudioInterface {
+    /// `sample_rate` is the rate gb-core generates samples at, `resampler` converts them to
+    /// `output_rate`. `buffer` has to be 4 byte aligned, every frame is sent as one word. See
+    /// [`DmaAudioOutput::new`] for `audio_master`.
+    ///
+    /// Stereo uses both channels of `SLICE`, the left one on channel A. In mono both channels
+    /// are mixed and only channel A needs a pin.
+    pub fn new<CH1, CH2, const SLICE: u8>(
+        pwm: pac::PWM,
+        resets: &mut pac::RESETS,
+        stereo: bool,
+        system_clock_hz: u32,
+        output_rate: u32,
+        sample_rate: u32,
+        resampler: Resampler,
+        channels: (CH1, CH2),
+        buffer: &'static mut [u16],
+        noise_shaping: bool,
+        volume: SharedVolume,
+        stats: SharedAudioStats,
+        audio_master: bool,
+    ) -> Self
+    where
+        CH1: SingleChannel + Send + 'static,
+        CH2: SingleChannel + Send + 'static,
+    {
+        resets.reset().modify(|_, w| w.pwm().clear_bit());
+        while resets.reset_done().read().pwm().bit_is_clear() {
+            hal::arch::nop();
+        }
+
+        let top = (system_clock_hz / output_rate - 1).min(u16::MAX as u32) as u16;
+        defmt::info!(
+            "PWM audio on slice {}, {} levels at {} Hz",
+            SLICE,
+            top as u32 + 1,
+            system_clock_hz / (top as u32 + 1)
+        );
+        let silence = top / 2;
+        let slice = pwm.ch(SLICE as usize);
+        slice.top().write(|w| unsafe { w.top().bits(top) });
+        slice
+            .cc()
+            .write(|w| unsafe { w.a().bits(silence).b().bits(silence) });
+        // Integer divider of 1, the counter runs at the system clock
+        slice
+            .div()
+            .write(|w| unsafe { w.int().bits(1).frac().bits(0) });
+        slice.csr().write(|w| w.en().set_bit());
+        let compare = PwmCompare::<SLICE> {
+            address: slice.cc().as_ptr() as u32,
+        };
+
+        let encoder = PwmEncoder {
+            top,
+            stereo,
+            noise_shaping,
+            error: [0; 2],
+        };
+        Self {
+            output: DmaAudioOutput::new(
+                sample_rate,
+                resampler,
+                channels,
+                compare,
+                buffer,
+                silence,
+                volume,
+                stats,
+                audio_master,
+            ),
+            encoder,
+        }
+    }
+}
+
+impl gb_core::hardware::sound::AudioPlayer for PwmAudioInterface {
+    fn play(&mut self, output_buffer: &[u16]) {
+        let encoder = &mut self.encoder;
+        self.output
+            .play(output_buffer, |samples| encoder.encode(samples));
+    }
+
+    fn samples_rate(&self) -> u32 {
+        self.output.sample_rate()
+    }
+
+    fn underflowed(&self) -> bool {
+        self.output.underflowed()
+    }
+}
+
+/// Turns signed samples into compare levels between 0 and `top`.
+struct PwmEncoder {
+    top: u16,
+    stereo: bool,
+    /// Carries the quantisation error of every sample into the next one, moving the noise of
+    /// the few PWM levels above the audible range.
+    noise_shaping: bool,
+    error: [i64; 2],
+}
+
+impl PwmEncoder {
+    /// Frames end up as one word, the left level in the low half for channel A and the right
+    /// one in the high half for channel B.
+    fn encode(&mut self, samples: &mut [u16]) {
+        for frame in samples.chunks_exact_mut(2) {
+            let left = frame[0] as i16 as i32;
+            let right = frame[1] as i16 as i32;
+            let input = if self.stereo {
+                [left, right]
+            } else {
+                let mono = (left + right) / 2;
+                [mono, mono]
+            };
+            for (channel, sample) in input.into_iter().enumerate() {
+                frame[channel] = self.level(channel, sample);
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn level(&mut self, channel: usize, sample: i32) -> u16 {
+        // Level with 16 fractional bits
+        let mut target = (sample + 32768) as i64 * (self.top as i64 + 1);
+        if self.noise_shaping {
+            target += self.error[channel];
+        }
+        let level = (target >> 16).clamp(0, self.top as i64);
+        if self.noise_shaping {
+            self.error[channel] =
+                (target - (level << 16)).clamp(-MAX_SHAPING_ERROR, MAX_SHAPING_ERROR);
+        }
+        level as u16
+    }
+}
+
+/// Compare register of a PWM slice, paced by the wrap of its counter.
+struct PwmCompare<const SLICE: u8> {
+    address: u32,
+}
+
+unsafe impl<const SLICE: u8> WriteTarget for PwmCompare<SLICE> {
+    type TransmittedWord = u32;
+
+    fn tx_treq() -> Option<u8> {
+        Some(DREQ_PWM_WRAP0 + SLICE)
+    }
+
+    fn tx_address_count(&mut self) -> (u32, u32) {
+        (self.address, u32::MAX)
+    }
+
+    fn tx_increment(&self) -> bool {
+        false
+    }
+}
+
+impl<const SLICE: u8> EndlessWriteTarget for PwmCompare<SLICE> {}
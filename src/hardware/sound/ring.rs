@@ -0,0 +1,57 @@
+use core::sync::atomic::{AtomicU16, AtomicUsize, Ordering};
+
+/// Single producer, single consumer queue of samples. The emulation pushes and the DMA
+/// interrupt pops, neither side ever waits for the other.
+///
+/// `N` has to be a power of two, the positions are free running counters.
+pub struct AudioRing<const N: usize> {
+    samples: [AtomicU16; N],
+    /// Samples pushed so far, only written by the producer.
+    head: AtomicUsize,
+    /// Samples popped so far, only written by the consumer.
+    tail: AtomicUsize,
+}
+
+impl<const N: usize> AudioRing<N> {
+    pub const fn new() -> Self {
+        assert!(N.is_power_of_two());
+        Self {
+            samples: [const { AtomicU16::new(0) }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Samples waiting to be popped.
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+        head.wrapping_sub(tail)
+    }
+
+    /// Copies as many whole stereo frames of `samples` as fit, returns the number of samples
+    /// pushed.
+    pub fn push(&self, samples: &[u16]) -> usize {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        let free = N - head.wrapping_sub(tail);
+        let count = samples.len().min(free) & !1;
+        for (offset, sample) in samples[..count].iter().enumerate() {
+            self.samples[head.wrapping_add(offset) & (N - 1)].store(*sample, Ordering::Relaxed);
+        }
+        self.head.store(head.wrapping_add(count), Ordering::Release);
+        count
+    }
+
+    /// Fills `output` with the oldest samples, returns how many there were.
+    pub fn pop_into(&self, output: &mut [u16]) -> usize {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+        let count = output.len().min(head.wrapping_sub(tail));
+        for (offset, sample) in output[..count].iter_mut().enumerate() {
+            *sample = self.samples[tail.wrapping_add(offset) & (N - 1)].load(Ordering::Relaxed);
+        }
+        self.tail.store(tail.wrapping_add(count), Ordering::Release);
+        count
+    }
+}
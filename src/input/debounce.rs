@@ -0,0 +1,74 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use gb_rp2350_logic::input::debounce::DebounceFilter;
+use rp235x_hal::timer::TimerDevice;
+
+use super::{InputSampler, InputSource};
+
+/// Time an input is left alone after it changed, the contacts of a tact switch bounce for a
+/// few milliseconds.
+#[const_env::from_env]
+const INPUT_DEBOUNCE_MS: u64 = 10;
+
+/// Merges the input sources and debounces them, what the game and the menus read.
+///
+/// A change is taken on its first edge so presses don't lag, the bouncing that follows is
+/// ignored for `INPUT_DEBOUNCE_MS`.
+pub struct Debouncer<D: TimerDevice> {
+    sources: Vec<PolledSource>,
+    timer: crate::hal::Timer<D>,
+    filter: DebounceFilter,
+}
+
+impl<D: TimerDevice> Debouncer<D> {
+    pub fn new(timer: crate::hal::Timer<D>) -> Self {
+        Self {
+            sources: Vec::new(),
+            timer,
+            filter: DebounceFilter::new(INPUT_DEBOUNCE_MS),
+        }
+    }
+
+    pub fn add_source(&mut self, source: Box<dyn InputSource>) {
+        self.sources.push(PolledSource {
+            source,
+            read_at: None,
+            inputs: 0,
+        });
+    }
+}
+
+struct PolledSource {
+    source: Box<dyn InputSource>,
+    /// When the source was last read, in microseconds.
+    read_at: Option<u64>,
+    inputs: u32,
+}
+
+impl PolledSource {
+    #[inline(always)]
+    fn read(&mut self, now: u64) -> u32 {
+        let interval = self.source.poll_interval_us();
+        let due = match self.read_at {
+            Some(read_at) => now - read_at >= interval,
+            None => true,
+        };
+        if due {
+            self.inputs = self.source.read();
+            self.read_at = Some(now);
+        }
+        self.inputs
+    }
+}
+
+impl<D: TimerDevice> InputSampler for Debouncer<D> {
+    #[inline(always)]
+    fn sample(&mut self) -> (u32, u64) {
+        let now = self.timer.get_counter().ticks();
+        let raw = self
+            .sources
+            .iter_mut()
+            .fold(0, |inputs, source| inputs | source.read(now));
+        (self.filter.update(raw, now), now)
+    }
+}
@@ -0,0 +1,76 @@
+use embedded_hal::i2c::I2c;
+
+use super::InputSource;
+
+/// Time between two reads, an I2C transfer takes long enough that it can't be done on every
+/// scanline.
+const POLL_INTERVAL_US: u64 = 4000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ExpanderKind {
+    /// 8 inputs, P0 to P7.
+    Pcf8574,
+    /// 16 inputs, GPA0 to GPA7 and then GPB0 to GPB7.
+    Mcp23017,
+}
+
+const MCP23017_GPPUA: u8 = 0x0C;
+const MCP23017_GPIOA: u8 = 0x12;
+
+/// Buttons on an I2C port expander, pin `n` of the expander is input `n`. The buttons pull
+/// the pins low.
+pub struct ExpanderInputs<I: I2c> {
+    i2c: I,
+    kind: ExpanderKind,
+    address: u8,
+    inputs: u32,
+}
+
+impl<I: I2c> ExpanderInputs<I> {
+    pub fn new(mut i2c: I, kind: ExpanderKind, address: u8) -> Self {
+        let result = match kind {
+            // Writing ones turns the quasi-bidirectional pins into inputs with a weak pull-up
+            ExpanderKind::Pcf8574 => i2c.write(address, &[0xFF]),
+            // The pins are inputs after a reset, they only need their pull-ups
+            ExpanderKind::Mcp23017 => i2c.write(address, &[MCP23017_GPPUA, 0xFF, 0xFF]),
+        };
+        if let Err(error) = result {
+            defmt::error!(
+                "Input expander at {=u8:#x} not found: {}",
+                address,
+                defmt::Debug2Format(&error)
+            );
+        }
+        Self {
+            i2c,
+            kind,
+            address,
+            inputs: 0,
+        }
+    }
+}
+
+impl<I: I2c> InputSource for ExpanderInputs<I> {
+    fn read(&mut self) -> u32 {
+        let mut pins = [0xFF; 2];
+        let result = match self.kind {
+            ExpanderKind::Pcf8574 => self.i2c.read(self.address, &mut pins[..1]),
+            ExpanderKind::Mcp23017 => {
+                self.i2c
+                    .write_read(self.address, &[MCP23017_GPIOA], &mut pins)
+            }
+        };
+        // A failed read keeps the last state instead of releasing every button
+        if result.is_ok() {
+            self.inputs = !u16::from_le_bytes(pins) as u32;
+            if self.kind == ExpanderKind::Pcf8574 {
+                self.inputs &= 0xFF;
+            }
+        }
+        self.inputs
+    }
+
+    fn poll_interval_us(&self) -> u64 {
+        POLL_INTERVAL_US
+    }
+}
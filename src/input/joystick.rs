@@ -0,0 +1,93 @@
+use alloc::rc::Rc;
+use core::cell::Cell;
+
+pub use gb_rp2350_logic::input::joystick::{JoystickCalibration, ADC_MAX, AXIS_X, AXIS_Y};
+
+#[cfg(feature = "joystick")]
+use super::InputSource;
+#[cfg(feature = "joystick")]
+use crate::hal::Adc;
+#[cfg(feature = "joystick")]
+use crate::hardware::SharedAdc;
+#[cfg(feature = "joystick")]
+use embedded_hal_0_2::adc::{Channel, OneShot};
+
+/// Swap the directions of an axis, for sticks mounted the other way around.
+#[cfg(feature = "joystick")]
+#[const_env::from_env]
+const JOYSTICK_INVERT_X: bool = false;
+#[cfg(feature = "joystick")]
+#[const_env::from_env]
+const JOYSTICK_INVERT_Y: bool = false;
+
+/// The calibration the stick is read with and its last position, shared between the input
+/// source, the settings and the calibration screen.
+pub struct JoystickState {
+    pub calibration: Cell<JoystickCalibration>,
+    /// Last readings of the axes, `None` until the stick was read.
+    pub position: Cell<Option<[u16; 2]>>,
+}
+
+pub type SharedJoystick = Rc<JoystickState>;
+
+impl JoystickState {
+    pub fn new(calibration: JoystickCalibration) -> Self {
+        Self {
+            calibration: Cell::new(calibration),
+            position: Cell::new(None),
+        }
+    }
+}
+
+/// A 2-axis analog stick on two ADC pins, read as the UP, DOWN, LEFT and RIGHT inputs.
+#[cfg(feature = "joystick")]
+pub struct JoystickInputs<X, Y> {
+    adc: SharedAdc,
+    x: X,
+    y: Y,
+    state: SharedJoystick,
+}
+
+#[cfg(feature = "joystick")]
+impl<X, Y> JoystickInputs<X, Y>
+where
+    X: Channel<Adc>,
+    Y: Channel<Adc>,
+    Adc: OneShot<Adc, u16, X> + OneShot<Adc, u16, Y>,
+{
+    pub fn new(adc: SharedAdc, x: X, y: Y, state: SharedJoystick) -> Self {
+        Self { adc, x, y, state }
+    }
+}
+
+#[cfg(feature = "joystick")]
+impl<X, Y> InputSource for JoystickInputs<X, Y>
+where
+    X: Channel<Adc>,
+    Y: Channel<Adc>,
+    Adc: OneShot<Adc, u16, X> + OneShot<Adc, u16, Y>,
+{
+    fn read(&mut self) -> u32 {
+        let mut adc = self.adc.borrow_mut();
+        let x = OneShot::<Adc, u16, X>::read(&mut *adc, &mut self.x).ok();
+        let y = OneShot::<Adc, u16, Y>::read(&mut *adc, &mut self.y).ok();
+        drop(adc);
+        let position = match (x, y) {
+            (Some(x), Some(y)) => [
+                if JOYSTICK_INVERT_X { ADC_MAX - x } else { x },
+                if JOYSTICK_INVERT_Y { ADC_MAX - y } else { y },
+            ],
+            // A failed conversion keeps the last position
+            _ => match self.state.position.get() {
+                Some(position) => position,
+                None => return 0,
+            },
+        };
+        self.state.position.set(Some(position));
+        self.state.calibration.get().directions(position)
+    }
+
+    fn poll_interval_us(&self) -> u64 {
+        1000
+    }
+}
@@ -0,0 +1,76 @@
+use core::convert::Infallible;
+
+use alloc::vec::Vec;
+use embedded_hal::digital::{InputPin, OutputPin};
+
+use super::{InputSource, INPUT_COUNT};
+
+/// Time between two scans, the rows need a moment to settle after they are switched.
+const POLL_INTERVAL_US: u64 = 1000;
+/// Cycles a row is given to settle before its columns are read, about 1us.
+const SETTLE_CYCLES: u32 = 150;
+
+/// Buttons wired as a matrix, the button between row `r` and column `c` is input
+/// `r * columns + c`. The rows are pulled low one at a time and the columns have pull-ups.
+///
+/// The rows have to be open drain so two buttons held in the same column can't short a low
+/// row to a high one.
+///
+/// Without a diode in series with every button, three buttons held on the corners of a
+/// rectangle make the fourth one read as pressed too, so only two buttons can be held at once
+/// reliably. Diodes (cathode towards the row) are needed to hold more.
+pub struct MatrixInputs<R: OutputPin<Error = Infallible>, C: InputPin<Error = Infallible>> {
+    rows: Vec<R>,
+    columns: Vec<C>,
+}
+
+impl<R: OutputPin<Error = Infallible>, C: InputPin<Error = Infallible>> Default
+    for MatrixInputs<R, C>
+{
+    fn default() -> Self {
+        Self {
+            rows: Vec::new(),
+            columns: Vec::new(),
+        }
+    }
+}
+
+impl<R: OutputPin<Error = Infallible>, C: InputPin<Error = Infallible>> MatrixInputs<R, C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_row(&mut self, mut row: R) {
+        row.set_high().unwrap();
+        self.rows.push(row);
+    }
+
+    pub fn add_column(&mut self, column: C) {
+        self.columns.push(column);
+    }
+}
+
+impl<R: OutputPin<Error = Infallible>, C: InputPin<Error = Infallible>> InputSource
+    for MatrixInputs<R, C>
+{
+    fn read(&mut self) -> u32 {
+        let mut inputs = 0;
+        let column_count = self.columns.len();
+        for (row_index, row) in self.rows.iter_mut().enumerate() {
+            row.set_low().unwrap();
+            cortex_m::asm::delay(SETTLE_CYCLES);
+            for (column_index, column) in self.columns.iter_mut().enumerate() {
+                let input = row_index * column_count + column_index;
+                if input < INPUT_COUNT && column.is_low().unwrap() {
+                    inputs |= 1 << input;
+                }
+            }
+            row.set_high().unwrap();
+        }
+        inputs
+    }
+
+    fn poll_interval_us(&self) -> u64 {
+        POLL_INTERVAL_US
+    }
+}
@@ -0,0 +1,71 @@
+use core::convert::Infallible;
+
+use alloc::vec::Vec;
+use embedded_hal::digital::InputPin;
+
+pub mod debounce;
+#[cfg(feature = "input_expander")]
+pub mod expander;
+pub mod joystick;
+#[cfg(feature = "input_matrix")]
+pub mod matrix;
+#[cfg(feature = "input_shift_register")]
+pub mod shift_register;
+
+pub use gb_rp2350_logic::input::{
+    events, hotkeys, input_from_name, mapping, turbo, INPUT_A, INPUT_B, INPUT_COUNT, INPUT_DOWN,
+    INPUT_EXTRA, INPUT_LEFT, INPUT_NAMES, INPUT_RIGHT, INPUT_SELECT, INPUT_START, INPUT_UP,
+};
+
+/// Anything the buttons can be read from.
+pub trait InputSource {
+    /// Returns the held inputs as a mask of `1 << INPUT_*` bits.
+    fn read(&mut self) -> u32;
+
+    /// Minimum time between two reads, sources that are slow to read are read less often
+    /// and their last inputs used in between.
+    fn poll_interval_us(&self) -> u64 {
+        0
+    }
+}
+
+/// Inputs as the game and the menus see them, debounced and merged from every source.
+pub trait InputSampler {
+    /// Returns the held inputs as a mask of `1 << INPUT_*` bits and the time they were
+    /// sampled at, in microseconds.
+    fn sample(&mut self) -> (u32, u64);
+}
+
+/// Buttons wired to their own GPIO, they are active low.
+pub struct GpioInputs<P: InputPin<Error = Infallible>> {
+    pins: Vec<(u8, P)>,
+}
+
+impl<P: InputPin<Error = Infallible>> Default for GpioInputs<P> {
+    fn default() -> Self {
+        Self { pins: Vec::new() }
+    }
+}
+
+impl<P: InputPin<Error = Infallible>> GpioInputs<P> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, input: u8, pin: P) {
+        self.pins.push((input, pin));
+    }
+}
+
+impl<P: InputPin<Error = Infallible>> InputSource for GpioInputs<P> {
+    #[inline(always)]
+    fn read(&mut self) -> u32 {
+        let mut mask = 0;
+        for (input, pin) in self.pins.iter_mut() {
+            if pin.is_low().unwrap() {
+                mask |= 1 << *input;
+            }
+        }
+        mask
+    }
+}
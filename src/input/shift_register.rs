@@ -0,0 +1,74 @@
+use crate::rp_hal::hal;
+
+use hal::pio::{PIOExt, Rx, StateMachineIndex, UninitStateMachine, PIO};
+
+use super::InputSource;
+
+/// Buttons read through a chain of 74HC165 shift registers by a PIO state machine, bit `n` of
+/// the chain (`H` of the first register first) is input `n`. The buttons pull their register
+/// input low.
+///
+/// The state machine scans the chain over and over and pushes every scan, reading just takes
+/// the latest one.
+pub struct ShiftRegisterInputs<P: PIOExt, SM: StateMachineIndex> {
+    rx: Rx<(P, SM)>,
+    /// Inputs in the chain, the bits clocked in past it are ignored.
+    mask: u32,
+    inputs: u32,
+}
+
+impl<P: PIOExt, SM: StateMachineIndex> ShiftRegisterInputs<P, SM> {
+    /// `load_pin` is SH/LD of the registers and CLK has to be the pin after it, `bits` is
+    /// the length of the chain, up to 16.
+    pub fn new(
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        load_pin: u8,
+        data_pin: u8,
+        bits: u8,
+    ) -> Self {
+        let program = pio_proc::pio_asm!(
+            ".side_set 2", // side 0bCL - C = CLK, L = SH/LD
+            ".wrap_target",
+            "    set x, 15       side 0b00", // latch the buttons
+            "    nop             side 0b01",
+            "bit:",
+            "    in pins, 1      side 0b01",
+            "    jmp x-- bit     side 0b11", // the rising edge shifts the next bit out
+            ".wrap",
+        );
+        let installed = pio.install(&program.program).unwrap();
+        // Around 2MHz, well within what the 74HC165 can do at 3.3V
+        let (mut sm, rx, _tx) = hal::pio::PIOBuilder::from_installed_program(installed)
+            .in_pin_base(data_pin)
+            .side_set_pin_base(load_pin)
+            .in_shift_direction(hal::pio::ShiftDirection::Right)
+            .autopush(true)
+            .push_threshold(16)
+            .buffers(hal::pio::Buffers::OnlyRx)
+            .clock_divisor_fixed_point(64, 0)
+            .build(sm);
+        sm.set_pindirs([
+            (load_pin, hal::pio::PinDir::Output),
+            (load_pin + 1, hal::pio::PinDir::Output),
+            (data_pin, hal::pio::PinDir::Input),
+        ]);
+        let _ = sm.start();
+        Self {
+            rx,
+            mask: (1 << bits.min(16)) - 1,
+            inputs: 0,
+        }
+    }
+}
+
+impl<P: PIOExt, SM: StateMachineIndex> InputSource for ShiftRegisterInputs<P, SM> {
+    #[inline(always)]
+    fn read(&mut self) -> u32 {
+        while let Some(scan) = self.rx.read() {
+            // The 16 bits are shifted in from the top, the first one ends up in bit 16
+            self.inputs = !(scan >> 16) & self.mask;
+        }
+        self.inputs
+    }
+}
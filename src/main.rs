@@ -5,42 +5,58 @@ mod clocks;
 
 mod gameboy;
 mod hardware;
+mod input;
 
 mod rp_hal;
+mod settings;
 mod ui;
 mod util;
 
 use alloc::boxed::Box;
 use alloc::rc::Rc;
 use alloc::string::{String, ToString};
-use core::cell::RefCell;
-
-use display_interface::WriteOnlyDataCommand;
+use core::cell::{Cell, RefCell};
 
 use embedded_graphics::pixelcolor::Rgb565;
 use embedded_graphics::prelude::*;
 use embedded_graphics::prelude::{DrawTarget, Point};
 
 use embedded_hal::digital::OutputPin;
-use ui::rom_select::select_rom;
+use ui::game_menu::{game_menu, GameMenuChoice};
+use ui::rom_select::{select_rom, RomSelection};
+use ui::settings::settings_menu;
+use ui::MenuButtons;
 
 use embedded_sdmmc::sdcard::AcquireOpts;
 use gb_core::hardware::boot_rom::Bootrom;
 use gb_core::hardware::cartridge::Cartridge;
-use mipidsi::models::Model;
-use mipidsi::options::{Orientation, Rotation};
-use mipidsi::Display;
 use panic_probe as _;
 use ui::loading::LoadingScreen;
+use ui::osd::{Osd, OsdIcon};
 extern crate alloc;
 
 use embedded_sdmmc::{SdCard, VolumeManager};
+use gameboy::audio::NullAudioPlayer;
 use gameboy::display::GameboyLineBufferDisplay;
-use gameboy::{GameEmulationHandler, GameboyButtonHandler, InputButtonMapper};
+use gameboy::recording::{RecordingRequest, WavRecording, BLOCK_SAMPLES, MAX_BLOCKS_PER_FRAME};
+use gameboy::screenshot::ScreenshotFormat;
+use gameboy::{
+    ChangedLinesIterator, EmulatorAction, GameEmulationHandler, GameboyButtonHandler,
+    InputButtonMapper,
+};
 use gb_core::gameboy::GameBoy;
+use gb_core::hardware::sound::AudioPlayer;
 use hal::fugit::RateExtU32;
 
-use hardware::display::ScreenScaler;
+use hardware::display::{FrameSync, LineChangeTracker, PixelSink, ScreenScaler};
+use hardware::sdcard::SdStorage;
+use hardware::sound::{
+    Mixer, RecordingTap, SharedAudioStats, SharedFastForward, SharedOutputMode, SharedVolume,
+};
+use input::debounce::Debouncer;
+use input::joystick::{JoystickState, SharedJoystick};
+use input::GpioInputs;
+use settings::Settings;
 
 use rp235x_hal::timer::TimerDevice;
 use rp235x_hal::uart::{DataBits, StopBits, UartConfig};
@@ -54,6 +70,7 @@ use rp_hal::hal;
 use embedded_alloc::LlffHeap as Heap;
 
 //Include selected display driver
+#[cfg(not(feature = "dvi_output"))]
 include!(concat!(env!("OUT_DIR"), "/generated_display_driver.rs"));
 
 /// Tell the Boot ROM about our application
@@ -97,40 +114,78 @@ const GAMEBOY_RENDER_WIDTH: u16 = 320;
 const GAMEBOY_RENDER_HEIGHT: u16 = 240;
 #[const_env::from_env]
 const DISPLAY_ROTATION: u16 = 0;
+#[cfg(not(feature = "dvi_output"))]
 #[const_env::from_env]
 const DISPLAY_MIRRORED: bool = false;
+#[cfg(not(feature = "dvi_output"))]
 #[const_env::from_env]
 const DISPLAY_COLOR_INVERT: bool = false;
+#[cfg(not(feature = "dvi_output"))]
+#[const_env::from_env]
+const DISPLAY_RGB666: bool = false;
+#[const_env::from_env]
+const OSD_STATS: bool = false;
+#[const_env::from_env]
+const DISPLAY_SKIP_UNCHANGED_LINES: bool = true;
+#[const_env::from_env]
+const SCREENSHOT_PNG: bool = false;
+/// Rate of the audio output, the samples of gb-core are resampled to it.
+#[const_env::from_env]
+const AUDIO_SAMPLE_RATE: u32 = 32_000;
+#[const_env::from_env]
+const AUDIO_RESAMPLER_SINC: bool = true;
+/// Pace the emulation with the audio output clock instead of letting it run free and dropping samples.
+#[const_env::from_env]
+const AUDIO_MASTER: bool = true;
+/// Rate gb-core generates samples at, higher rates cost emulation time.
+const AUDIO_SOURCE_RATE: u32 = 16_000;
+#[cfg(feature = "audio_pwm")]
+#[const_env::from_env]
+const AUDIO_PWM_NOISE_SHAPING: bool = true;
+/// PWM slice of `PWM_AUDIO_L`, computed by the build script.
+#[cfg(feature = "audio_pwm")]
+#[const_env::from_env]
+const PWM_AUDIO_SLICE: u8 = 0;
+/// Length of the 74HC165 chain, from `SHIFT_BITS` in the pin mapping.
+#[cfg(feature = "input_shift_register")]
+#[const_env::from_env]
+const INPUT_SHIFT_REGISTER_BITS: u8 = 8;
+/// From `EXPANDER_ADDRESS` in the pin mapping.
+#[cfg(feature = "input_expander")]
+#[const_env::from_env]
+const INPUT_EXPANDER_ADDRESS: u8 = 0x20;
 
-const RENDER_WIDTH: u16 = if DISPLAY_ROTATION == 90 || DISPLAY_ROTATION == 270 {
-    DISPLAY_HEIGHT
-} else {
-    DISPLAY_WIDTH
-};
-const RENDER_HEIGHT: u16 = if DISPLAY_ROTATION == 90 || DISPLAY_ROTATION == 270 {
-    DISPLAY_WIDTH
-} else {
-    DISPLAY_HEIGHT
-};
+#[cfg(not(feature = "dvi_output"))]
+#[const_env::from_env]
+const DISPLAY_COLOR_BGR: bool = true;
+#[cfg(not(feature = "dvi_output"))]
+#[const_env::from_env]
+const DISPLAY_CLOCK_DIVIDER: u16 = 3;
+#[cfg(not(feature = "dvi_output"))]
+#[const_env::from_env]
+const DISPLAY_CLOCK_DIVIDER_FRAC: u8 = 0;
 
 #[hal::entry]
 fn main() -> ! {
     const {
+        let (render_width, render_height) = if DISPLAY_ROTATION == 90 || DISPLAY_ROTATION == 270 {
+            (DISPLAY_HEIGHT, DISPLAY_WIDTH)
+        } else {
+            (DISPLAY_WIDTH, DISPLAY_HEIGHT)
+        };
         assert!(
-            RENDER_WIDTH >= GAMEBOY_RENDER_WIDTH,
+            render_width >= GAMEBOY_RENDER_WIDTH,
             "Gameboy render width cannot be smaller than the width of the screen"
-        )
-    }
-    const {
+        );
         assert!(
-            RENDER_HEIGHT >= GAMEBOY_RENDER_HEIGHT,
+            render_height >= GAMEBOY_RENDER_HEIGHT,
             "Gameboy render height cannot be smaller than the width of the screen"
-        )
+        );
     }
     let mut pac = hal::pac::Peripherals::take().unwrap();
 
     // Grab our singleton objects
-    let sio = hal::Sio::new(pac.SIO);
+    let mut sio = hal::Sio::new(pac.SIO);
     let pins = hal::gpio::Pins::new(
         pac.IO_BANK0,
         pac.PADS_BANK0,
@@ -201,7 +256,7 @@ fn main() -> ! {
 
     let (mut pio_0, sm0_0, sm0_1, _, _) = pac.PIO0.split(&mut pac.RESETS);
 
-    let (mut pio_1, sm_1_0, _, _, _) = pac.PIO1.split(&mut pac.RESETS);
+    let (mut pio_1, sm_1_0, sm_1_1, _, _) = pac.PIO1.split(&mut pac.RESETS);
     let dma = pac.DMA.split(&mut pac.RESETS);
 
     ///////////////////////////////SD CARD
@@ -233,145 +288,528 @@ fn main() -> ! {
         },
     );
 
-    let mut volume_mgr = VolumeManager::new(sdcard, hardware::sdcard::DummyTimesource::default());
-    let mut volume0 = volume_mgr
-        .open_volume(embedded_sdmmc::VolumeIdx(0))
-        .unwrap();
+    let volume_mgr = VolumeManager::new(sdcard, hardware::sdcard::DummyTimesource::default());
+    let storage = Rc::new(RefCell::new(
+        SdStorage::new(volume_mgr, |bd: &mut SdCard<_, _>| {
+            bd.mark_card_uninit();
+        })
+        .unwrap(),
+    ));
 
-    let rom_list = Rc::new(RefCell::new(alloc::vec::Vec::<String>::new()));
-    let mut root_dir = volume0.open_root_dir().unwrap();
-    root_dir
-        .iterate_dir(|dir_entry| {
+    let mut rom_list = alloc::vec::Vec::<String>::new();
+    storage
+        .borrow_mut()
+        .iterate_dir(&[], |dir_entry| {
             let extension = String::from_utf8(dir_entry.name.extension().to_vec()).unwrap();
             if extension.eq("GB") {
                 let rom_name =
                     String::from_utf8(dir_entry.name.base_name().to_vec()).unwrap() + ".GB";
-                rom_list.borrow_mut().push(rom_name);
+                rom_list.push(rom_name);
             }
         })
         .unwrap();
-    let rom_list = rom_list.take();
-    root_dir.close().unwrap();
-    volume0.close().unwrap();
-    let boot_rom = load_boot_rom(&mut volume_mgr);
+    let boot_rom = load_boot_rom(&mut storage.borrow_mut());
+    let mut settings = Settings::load(&mut storage.borrow_mut());
+    #[cfg(not(feature = "dvi_output"))]
+    let display_config = load_display_config(&mut storage.borrow_mut());
 
     //////////////////////AUDIO SETUP
 
-    let sample_rate: u32 = 16_000;
-    let clock_divider: u32 = clocks.system_clock.freq().to_Hz() * 4 / sample_rate;
+    let volume: SharedVolume = Rc::new(Cell::new(settings.volume));
+    let output_mode: SharedOutputMode = Rc::new(Cell::new(settings.output_mode));
+    let audio_stats: SharedAudioStats = Rc::new(Cell::new(Default::default()));
+    #[cfg(feature = "audio_amp_sd")]
+    let mut amp_shutdown = {
+        // The amplifier stays off until a game with audio starts
+        let mut pin = pin_select!(pins, env!("PIN_AUDIO_AMP_SD")).into_push_pull_output();
+        pin.set_low().unwrap();
+        pin
+    };
+
+    let screen = GameboyLineBufferDisplay::new(timer);
 
-    let int_divider = (clock_divider >> 8) as u16;
-    let frak_divider = (clock_divider & 0xFF) as u8;
+    #[cfg(not(feature = "dvi_output"))]
+    let mut display = {
+        use hardware::display::PanelOptions;
 
-    let i2s_din = pin_select!(pins, env!("PIN_I2S_DIN")).into_function::<hal::gpio::FunctionPio1>();
-    let i2s_bclk =
-        pin_select!(pins, env!("PIN_I2S_BCLK")).into_function::<hal::gpio::FunctionPio1>();
-    let i2s_lrc = pin_select!(pins, env!("PIN_I2S_LRC")).into_function::<hal::gpio::FunctionPio1>();
-    let audio_buffer: &'static mut [u16] =
-        cortex_m::singleton!(: [u16; (2000 * 3) * 3]  = [0u16;  (2000 * 3) * 3 ])
+        let display_buffer: &'static mut [u16] =
+        cortex_m::singleton!(: [u16;(GAMEBOY_RENDER_WIDTH as usize) * 3]  = [0u16; (GAMEBOY_RENDER_WIDTH as usize ) * 3 ])
             .unwrap()
             .as_mut_slice();
-    let i2s_interface = hardware::sound::I2sPioInterface::new(
-        sample_rate,
-        dma.ch2,
-        dma.ch3,
-        (int_divider as u16, frak_divider as u8),
-        &mut pio_1,
-        sm_1_0,
-        (i2s_bclk.id().num, i2s_lrc.id().num),
-        i2s_din.id().num,
-        audio_buffer,
-    );
+        let mut screen_data_cs = pin_select!(pins, env!("PIN_SCREEN_CS")).into_push_pull_output();
+        screen_data_cs.set_low().unwrap();
+
+        let screen_data_command_pin =
+            pin_select!(pins, env!("PIN_SCREEN_DC")).into_push_pull_output();
+        let display_reset = pin_select!(pins, env!("PIN_SCREEN_RESET")).into_push_pull_output();
+        let bus_pixel_format = if DISPLAY_RGB666 {
+            hardware::display::BusPixelFormat::Rgb666
+        } else {
+            hardware::display::BusPixelFormat::Rgb565
+        };
+        let streamer = hardware::display::DmaStreamer::new(dma.ch0, dma.ch1, display_buffer);
+
+        #[cfg(not(feature = "screen_parallel"))]
+        let display_interface = {
+            let spi_sclk = pin_select!(pins, env!("PIN_SCREEN_SCLK"))
+                .into_function::<hal::gpio::FunctionPio0>();
+            let spi_mosi = pin_select!(pins, env!("PIN_SCREEN_MOSI"))
+                .into_function::<hal::gpio::FunctionPio0>();
+            hardware::display::SpiPioDmaInterface::new(
+                display_config.clock_divider,
+                screen_data_command_pin,
+                &mut pio_0,
+                sm0_1,
+                sm0_0,
+                spi_sclk.id().num,
+                spi_mosi.id().num,
+                streamer,
+                timer,
+                bus_pixel_format,
+            )
+        };
+        #[cfg(feature = "screen_parallel")]
+        let display_interface = {
+            let _ = sm0_1;
+            let screen_wr =
+                pin_select!(pins, env!("PIN_SCREEN_WR")).into_function::<hal::gpio::FunctionPio0>();
+            let data_first =
+                pin_select!(pins, env!("PIN_SCREEN_D0")).into_function::<hal::gpio::FunctionPio0>();
+            let _ =
+                pin_select!(pins, env!("PIN_SCREEN_D1")).into_function::<hal::gpio::FunctionPio0>();
+            let _ =
+                pin_select!(pins, env!("PIN_SCREEN_D2")).into_function::<hal::gpio::FunctionPio0>();
+            let _ =
+                pin_select!(pins, env!("PIN_SCREEN_D3")).into_function::<hal::gpio::FunctionPio0>();
+            let _ =
+                pin_select!(pins, env!("PIN_SCREEN_D4")).into_function::<hal::gpio::FunctionPio0>();
+            let _ =
+                pin_select!(pins, env!("PIN_SCREEN_D5")).into_function::<hal::gpio::FunctionPio0>();
+            let _ =
+                pin_select!(pins, env!("PIN_SCREEN_D6")).into_function::<hal::gpio::FunctionPio0>();
+            let data_last =
+                pin_select!(pins, env!("PIN_SCREEN_D7")).into_function::<hal::gpio::FunctionPio0>();
+            hardware::display::Parallel8BitDmaInterface::new(
+                display_config.clock_divider,
+                screen_data_command_pin,
+                &mut pio_0,
+                sm0_0,
+                screen_wr.id().num,
+                (data_first.id().num, data_last.id().num),
+                streamer,
+                timer,
+                bus_pixel_format,
+            )
+        };
 
-    let screen = GameboyLineBufferDisplay::new(timer);
+        let display_builder = mipidsi::Builder::new(DisplayDriver, display_interface)
+            .reset_pin(display_reset)
+            .display_size(display_config.width, display_config.height)
+            .color_order(display_config.color_order())
+            .invert_colors(display_config.color_inversion())
+            .orientation(display_config.orientation());
 
-    let display_buffer: &'static mut [u16] =
-    cortex_m::singleton!(: [u16;(GAMEBOY_RENDER_WIDTH as usize) * 3]  = [0u16; (GAMEBOY_RENDER_WIDTH as usize ) * 3 ])
+        display_builder.init(&mut timer).unwrap()
+    };
+    #[cfg(feature = "dvi_output")]
+    let mut display = {
+        let _ = (sm0_0, sm0_1, &mut pio_0);
+        let clock = (
+            pin_select!(pins, env!("PIN_DVI_CLK_P")).into_function::<hal::gpio::FunctionHstx>(),
+            pin_select!(pins, env!("PIN_DVI_CLK_N")).into_function::<hal::gpio::FunctionHstx>(),
+        );
+        let lane0 = (
+            pin_select!(pins, env!("PIN_DVI_D0_P")).into_function::<hal::gpio::FunctionHstx>(),
+            pin_select!(pins, env!("PIN_DVI_D0_N")).into_function::<hal::gpio::FunctionHstx>(),
+        );
+        let lane1 = (
+            pin_select!(pins, env!("PIN_DVI_D1_P")).into_function::<hal::gpio::FunctionHstx>(),
+            pin_select!(pins, env!("PIN_DVI_D1_N")).into_function::<hal::gpio::FunctionHstx>(),
+        );
+        let lane2 = (
+            pin_select!(pins, env!("PIN_DVI_D2_P")).into_function::<hal::gpio::FunctionHstx>(),
+            pin_select!(pins, env!("PIN_DVI_D2_N")).into_function::<hal::gpio::FunctionHstx>(),
+        );
+        let dvi_pins = hardware::display::DviPins {
+            clock: (clock.0.id().num, clock.1.id().num),
+            lanes: [
+                (lane0.0.id().num, lane0.1.id().num),
+                (lane1.0.id().num, lane1.1.id().num),
+                (lane2.0.id().num, lane2.1.id().num),
+            ],
+        };
+        let line_buffers: &'static mut [u32] = cortex_m::singleton!(
+            : [u32; hardware::display::DVI_LINE_BUFFER_WORDS] =
+                [0u32; hardware::display::DVI_LINE_BUFFER_WORDS])
         .unwrap()
         .as_mut_slice();
-    let mut screen_data_cs = pin_select!(pins, env!("PIN_SCREEN_CS")).into_push_pull_output();
-    screen_data_cs.set_low().unwrap();
-
-    let screen_data_command_pin = pin_select!(pins, env!("PIN_SCREEN_DC")).into_push_pull_output();
-    let display_reset = pin_select!(pins, env!("PIN_SCREEN_RESET")).into_push_pull_output();
-    let spi_sclk =
-        pin_select!(pins, env!("PIN_SCREEN_SCLK")).into_function::<hal::gpio::FunctionPio0>();
-    let spi_mosi =
-        pin_select!(pins, env!("PIN_SCREEN_MOSI")).into_function::<hal::gpio::FunctionPio0>();
-
-    let streamer = hardware::display::DmaStreamer::new(dma.ch0, dma.ch1, display_buffer);
-    let display_interface = hardware::display::SpiPioDmaInterface::new(
-        (3, 0),
-        screen_data_command_pin,
-        &mut pio_0,
-        sm0_1,
-        sm0_0,
-        spi_sclk.id().num,
-        spi_mosi.id().num,
-        streamer,
-        timer,
-    );
+        let mut multicore =
+            hal::multicore::Multicore::new(&mut pac.PSM, &mut pac.PPB, &mut sio.fifo);
+        let cores = multicore.cores();
+        hardware::display::DviOutput::start(
+            pac.HSTX_CTRL,
+            &mut pac.RESETS,
+            dvi_pins,
+            clocks.system_clock.freq().to_Hz(),
+            (dma.ch0, dma.ch1),
+            line_buffers,
+            &mut cores[1],
+        )
+    };
 
-    let display_builder = mipidsi::Builder::new(DisplayDriver, display_interface)
-        .reset_pin(display_reset)
-        .display_size(DISPLAY_WIDTH as u16, DISPLAY_HEIGHT as u16)
-        .color_order(mipidsi::options::ColorOrder::Bgr)
-        .invert_colors(if DISPLAY_COLOR_INVERT {
-            mipidsi::options::ColorInversion::Inverted
+    #[cfg(feature = "screen_te")]
+    let frame_sync = {
+        use hardware::display::TearingMode;
+        #[const_env::from_env]
+        const DISPLAY_TE_SCANLINE: i32 = -1;
+        let te_pin = pin_select!(pins, env!("PIN_SCREEN_TE")).into_pull_down_input();
+        let mut frame_sync = hardware::display::TearingEffectSync::new(te_pin, timer);
+        let mode = if DISPLAY_TE_SCANLINE < 0 {
+            TearingMode::VBlank
         } else {
-            mipidsi::options::ColorInversion::Normal
-        })
-        .orientation(Orientation {
-            rotation: match DISPLAY_ROTATION {
-                0 => Rotation::Deg0,
-                90 => Rotation::Deg90,
-                180 => Rotation::Deg180,
-                270 => Rotation::Deg270,
-                _ => unreachable!(),
-            },
-            mirrored: DISPLAY_MIRRORED,
-        });
-
-    let mut display = display_builder.init(&mut timer).unwrap();
+            TearingMode::Scanline(DISPLAY_TE_SCANLINE as u16)
+        };
+        frame_sync.enable(&mut display, mode).unwrap();
+        frame_sync
+    };
+    #[cfg(not(feature = "screen_te"))]
+    let frame_sync = hardware::display::NoFrameSync;
 
     ////////////////////// JOYPAD
-    let mut b_button = pin_select!(pins, env!("PIN_B_BUTTON"))
-        .into_pull_up_input()
-        .into_dyn_pin();
-    let mut a_button = pin_select!(pins, env!("PIN_A_BUTTON"))
-        .into_pull_up_input()
-        .into_dyn_pin();
-    let mut right_button = pin_select!(pins, env!("PIN_RIGHT_BUTTON"))
-        .into_pull_up_input()
-        .into_dyn_pin();
-    let mut down_button = pin_select!(pins, env!("PIN_DOWN_BUTTON"))
-        .into_pull_up_input()
-        .into_dyn_pin();
-    let mut left_button = pin_select!(pins, env!("PIN_LEFT_BUTTON"))
-        .into_pull_up_input()
-        .into_dyn_pin();
-    let mut up_button = pin_select!(pins, env!("PIN_UP_BUTTON"))
-        .into_pull_up_input()
-        .into_dyn_pin();
-    let mut select_button = pin_select!(pins, env!("PIN_SELECT_BUTTON"))
-        .into_pull_up_input()
-        .into_dyn_pin();
-    let mut start_button = pin_select!(pins, env!("PIN_START_BUTTON"))
-        .into_pull_up_input()
-        .into_dyn_pin();
-
-    let selected_rom = select_rom(
-        &mut display,
-        rom_list.as_slice(),
-        timer,
-        &mut up_button,
-        &mut down_button,
-        &mut a_button,
-    )
-    .unwrap();
+    let joystick: SharedJoystick = Rc::new(JoystickState::new(settings.joystick));
+    let mut gpio_inputs: GpioInputs<
+        hal::gpio::Pin<hal::gpio::DynPinId, hal::gpio::FunctionSioInput, hal::gpio::PullUp>,
+    > = GpioInputs::new();
+    #[cfg(feature = "gpio_buttons")]
+    {
+        gpio_inputs.add(
+            input::INPUT_A,
+            pin_select!(pins, env!("PIN_A_BUTTON"))
+                .into_pull_up_input()
+                .into_dyn_pin(),
+        );
+        gpio_inputs.add(
+            input::INPUT_B,
+            pin_select!(pins, env!("PIN_B_BUTTON"))
+                .into_pull_up_input()
+                .into_dyn_pin(),
+        );
+        gpio_inputs.add(
+            input::INPUT_SELECT,
+            pin_select!(pins, env!("PIN_SELECT_BUTTON"))
+                .into_pull_up_input()
+                .into_dyn_pin(),
+        );
+        gpio_inputs.add(
+            input::INPUT_START,
+            pin_select!(pins, env!("PIN_START_BUTTON"))
+                .into_pull_up_input()
+                .into_dyn_pin(),
+        );
+    }
+    #[cfg(feature = "gpio_dpad")]
+    {
+        gpio_inputs.add(
+            input::INPUT_UP,
+            pin_select!(pins, env!("PIN_UP_BUTTON"))
+                .into_pull_up_input()
+                .into_dyn_pin(),
+        );
+        gpio_inputs.add(
+            input::INPUT_DOWN,
+            pin_select!(pins, env!("PIN_DOWN_BUTTON"))
+                .into_pull_up_input()
+                .into_dyn_pin(),
+        );
+        gpio_inputs.add(
+            input::INPUT_LEFT,
+            pin_select!(pins, env!("PIN_LEFT_BUTTON"))
+                .into_pull_up_input()
+                .into_dyn_pin(),
+        );
+        gpio_inputs.add(
+            input::INPUT_RIGHT,
+            pin_select!(pins, env!("PIN_RIGHT_BUTTON"))
+                .into_pull_up_input()
+                .into_dyn_pin(),
+        );
+    }
+    #[cfg(feature = "extra_button_1")]
+    gpio_inputs.add(
+        input::INPUT_EXTRA,
+        pin_select!(pins, env!("PIN_EXTRA_BUTTON_1"))
+            .into_pull_up_input()
+            .into_dyn_pin(),
+    );
+    #[cfg(feature = "extra_button_2")]
+    gpio_inputs.add(
+        input::INPUT_EXTRA + 1,
+        pin_select!(pins, env!("PIN_EXTRA_BUTTON_2"))
+            .into_pull_up_input()
+            .into_dyn_pin(),
+    );
+    #[cfg(feature = "extra_button_3")]
+    gpio_inputs.add(
+        input::INPUT_EXTRA + 2,
+        pin_select!(pins, env!("PIN_EXTRA_BUTTON_3"))
+            .into_pull_up_input()
+            .into_dyn_pin(),
+    );
+    #[cfg(feature = "extra_button_4")]
+    gpio_inputs.add(
+        input::INPUT_EXTRA + 3,
+        pin_select!(pins, env!("PIN_EXTRA_BUTTON_4"))
+            .into_pull_up_input()
+            .into_dyn_pin(),
+    );
+    let mut inputs = Debouncer::new(timer);
+    inputs.add_source(Box::new(gpio_inputs));
+
+    #[cfg(feature = "input_shift_register")]
+    {
+        let shift_load =
+            pin_select!(pins, env!("PIN_SHIFT_LOAD")).into_function::<hal::gpio::FunctionPio1>();
+        let _shift_clk =
+            pin_select!(pins, env!("PIN_SHIFT_CLK")).into_function::<hal::gpio::FunctionPio1>();
+        let shift_data =
+            pin_select!(pins, env!("PIN_SHIFT_DATA")).into_function::<hal::gpio::FunctionPio1>();
+        inputs.add_source(Box::new(input::shift_register::ShiftRegisterInputs::new(
+            &mut pio_1,
+            sm_1_1,
+            shift_load.id().num,
+            shift_data.id().num,
+            INPUT_SHIFT_REGISTER_BITS,
+        )));
+    }
+    #[cfg(not(feature = "input_shift_register"))]
+    let _ = sm_1_1;
 
+    #[cfg(feature = "input_expander")]
+    {
+        let sda: hal::gpio::Pin<_, hal::gpio::FunctionI2C, hal::gpio::PullUp> =
+            pin_select!(pins, env!("PIN_EXPANDER_SDA")).reconfigure();
+        let scl: hal::gpio::Pin<_, hal::gpio::FunctionI2C, hal::gpio::PullUp> =
+            pin_select!(pins, env!("PIN_EXPANDER_SCL")).reconfigure();
+        #[cfg(not(feature = "input_expander_i2c1"))]
+        let i2c_block = pac.I2C0;
+        #[cfg(feature = "input_expander_i2c1")]
+        let i2c_block = pac.I2C1;
+        let i2c = hal::I2C::new_controller(
+            i2c_block,
+            sda,
+            scl,
+            400.kHz(),
+            &mut pac.RESETS,
+            clocks.system_clock.freq(),
+        );
+        #[cfg(not(feature = "input_expander_mcp23017"))]
+        let kind = input::expander::ExpanderKind::Pcf8574;
+        #[cfg(feature = "input_expander_mcp23017")]
+        let kind = input::expander::ExpanderKind::Mcp23017;
+        inputs.add_source(Box::new(input::expander::ExpanderInputs::new(
+            i2c,
+            kind,
+            INPUT_EXPANDER_ADDRESS,
+        )));
+    }
+
+    #[cfg(feature = "input_matrix")]
+    {
+        let mut matrix = input::matrix::MatrixInputs::new();
+        // The rows only ever drive low, the pull-up holds an idle row high. The pull-down they
+        // come out of reset with would read as presses on every column
+        matrix.add_row(hal::gpio::InOutPin::new(
+            pin_select!(pins, env!("PIN_MATRIX_ROW_1"))
+                .into_pull_type::<hal::gpio::PullUp>()
+                .into_dyn_pin(),
+        ));
+        #[cfg(feature = "matrix_row_2")]
+        matrix.add_row(hal::gpio::InOutPin::new(
+            pin_select!(pins, env!("PIN_MATRIX_ROW_2"))
+                .into_pull_type::<hal::gpio::PullUp>()
+                .into_dyn_pin(),
+        ));
+        #[cfg(feature = "matrix_row_3")]
+        matrix.add_row(hal::gpio::InOutPin::new(
+            pin_select!(pins, env!("PIN_MATRIX_ROW_3"))
+                .into_pull_type::<hal::gpio::PullUp>()
+                .into_dyn_pin(),
+        ));
+        #[cfg(feature = "matrix_row_4")]
+        matrix.add_row(hal::gpio::InOutPin::new(
+            pin_select!(pins, env!("PIN_MATRIX_ROW_4"))
+                .into_pull_type::<hal::gpio::PullUp>()
+                .into_dyn_pin(),
+        ));
+        matrix.add_column(
+            pin_select!(pins, env!("PIN_MATRIX_COL_1"))
+                .into_pull_up_input()
+                .into_dyn_pin(),
+        );
+        #[cfg(feature = "matrix_col_2")]
+        matrix.add_column(
+            pin_select!(pins, env!("PIN_MATRIX_COL_2"))
+                .into_pull_up_input()
+                .into_dyn_pin(),
+        );
+        #[cfg(feature = "matrix_col_3")]
+        matrix.add_column(
+            pin_select!(pins, env!("PIN_MATRIX_COL_3"))
+                .into_pull_up_input()
+                .into_dyn_pin(),
+        );
+        #[cfg(feature = "matrix_col_4")]
+        matrix.add_column(
+            pin_select!(pins, env!("PIN_MATRIX_COL_4"))
+                .into_pull_up_input()
+                .into_dyn_pin(),
+        );
+        inputs.add_source(Box::new(matrix));
+    }
+
+    #[cfg(any(feature = "joystick", feature = "battery"))]
+    let adc: hardware::SharedAdc = Rc::new(RefCell::new(hal::Adc::new(pac.ADC, &mut pac.RESETS)));
+
+    #[cfg(feature = "joystick")]
+    {
+        let x =
+            hal::adc::AdcPin::new(pin_select!(pins, env!("PIN_JOYSTICK_X")).into_floating_input())
+                .unwrap();
+        let y =
+            hal::adc::AdcPin::new(pin_select!(pins, env!("PIN_JOYSTICK_Y")).into_floating_input())
+                .unwrap();
+        inputs.add_source(Box::new(input::joystick::JoystickInputs::new(
+            adc.clone(),
+            x,
+            y,
+            joystick.clone(),
+        )));
+    }
+
+    #[cfg(feature = "battery")]
+    let mut battery = hardware::battery::Battery::new(
+        adc,
+        hal::adc::AdcPin::new(pin_select!(pins, env!("PIN_BATTERY_ADC")).into_floating_input())
+            .unwrap(),
+    );
+
+    let selected_rom = loop {
+        let mut buttons = MenuButtons::new(&mut inputs);
+        let selection = select_rom(&mut display, rom_list.as_slice(), timer, &mut buttons).unwrap();
+        match selection {
+            RomSelection::Rom(selected_rom) => break selected_rom,
+            RomSelection::Settings => {
+                if settings_menu(&mut display, &mut settings, &joystick, &mut buttons).unwrap() {
+                    if let Err(error) = settings.save(&mut storage.borrow_mut()) {
+                        defmt::error!("Failed to save settings: {}", defmt::Debug2Format(&error));
+                    }
+                }
+            }
+        }
+    };
     let name = rom_list[selected_rom].clone();
     defmt::info!("Menu END: {}", defmt::Display2Format(&name));
+    let game_settings = settings.for_game(&mut storage.borrow_mut(), &name);
+    volume.set(game_settings.volume);
+    output_mode.set(game_settings.output_mode);
+    joystick.calibration.set(game_settings.joystick);
+
+    let recording_tap: RecordingTap = Rc::new(RefCell::new(None));
+    let fast_forward: SharedFastForward = Rc::new(Cell::new(false));
+    let audio_player: Box<dyn AudioPlayer> = if game_settings.audio_enabled {
+        defmt::info!("Audio output: {}", game_settings.output_mode);
+        let resampler_quality = if AUDIO_RESAMPLER_SINC {
+            hardware::sound::ResamplerQuality::Sinc
+        } else {
+            hardware::sound::ResamplerQuality::Linear
+        };
+        defmt::info!(
+            "Audio: {} Hz resampled to {} Hz ({})",
+            AUDIO_SOURCE_RATE,
+            AUDIO_SAMPLE_RATE,
+            resampler_quality
+        );
+        let resampler = hardware::sound::Resampler::new(
+            AUDIO_SOURCE_RATE,
+            AUDIO_SAMPLE_RATE,
+            resampler_quality,
+        );
+        // PWM output moves every frame as one word
+        #[repr(align(4))]
+        struct AudioBuffer([u16; 2 * hardware::sound::DMA_CHUNK_SAMPLES]);
+        let audio_buffer: &'static mut [u16] = &mut cortex_m::singleton!(
+            : AudioBuffer = AudioBuffer([0u16; 2 * hardware::sound::DMA_CHUNK_SAMPLES]))
+        .unwrap()
+        .0;
+
+        #[cfg(not(feature = "audio_pwm"))]
+        let audio_output = {
+            // Each stereo frame takes 64 PIO cycles, the divider has 8 fractional bits
+            let clock_divider: u32 = clocks.system_clock.freq().to_Hz() * 4 / AUDIO_SAMPLE_RATE;
+
+            let int_divider = (clock_divider >> 8) as u16;
+            let frak_divider = (clock_divider & 0xFF) as u8;
+
+            let i2s_din =
+                pin_select!(pins, env!("PIN_I2S_DIN")).into_function::<hal::gpio::FunctionPio1>();
+            let i2s_bclk =
+                pin_select!(pins, env!("PIN_I2S_BCLK")).into_function::<hal::gpio::FunctionPio1>();
+            let i2s_lrc =
+                pin_select!(pins, env!("PIN_I2S_LRC")).into_function::<hal::gpio::FunctionPio1>();
+            hardware::sound::I2sPioInterface::new(
+                AUDIO_SOURCE_RATE,
+                resampler,
+                dma.ch2,
+                dma.ch3,
+                (int_divider as u16, frak_divider as u8),
+                &mut pio_1,
+                sm_1_0,
+                (i2s_bclk.id().num, i2s_lrc.id().num),
+                i2s_din.id().num,
+                audio_buffer,
+                volume.clone(),
+                audio_stats.clone(),
+                AUDIO_MASTER,
+            )
+        };
+        #[cfg(feature = "audio_pwm")]
+        let audio_output = {
+            let _ = (&mut pio_1, sm_1_0);
+            let _pwm_left = pin_select!(pins, env!("PIN_PWM_AUDIO_L"))
+                .into_function::<hal::gpio::FunctionPwm>();
+            #[cfg(feature = "audio_pwm_stereo")]
+            let _pwm_right = pin_select!(pins, env!("PIN_PWM_AUDIO_R"))
+                .into_function::<hal::gpio::FunctionPwm>();
+            hardware::sound::PwmAudioInterface::new::<_, _, PWM_AUDIO_SLICE>(
+                pac.PWM,
+                &mut pac.RESETS,
+                cfg!(feature = "audio_pwm_stereo"),
+                clocks.system_clock.freq().to_Hz(),
+                AUDIO_SAMPLE_RATE,
+                AUDIO_SOURCE_RATE,
+                resampler,
+                (dma.ch2, dma.ch3),
+                audio_buffer,
+                AUDIO_PWM_NOISE_SHAPING,
+                volume.clone(),
+                audio_stats.clone(),
+                AUDIO_MASTER,
+            )
+        };
+
+        #[cfg(feature = "audio_amp_sd")]
+        amp_shutdown.set_high().unwrap();
+        Box::new(Mixer::new(
+            audio_output,
+            output_mode,
+            recording_tap.clone(),
+            fast_forward.clone(),
+        ))
+    } else {
+        // The PIO state machine, DMA and amplifier are never started
+        defmt::info!("Audio disabled");
+        Box::new(NullAudioPlayer)
+    };
 
     #[cfg(feature = "psram_rom")]
     let cartridge = {
@@ -393,68 +831,343 @@ fn main() -> ! {
             slice
         };
 
-        let cartridge = load_rom_to_psram(&mut display, volume_mgr, timer, &name, psram, |db| {
-            db.mark_card_uninit();
-        });
+        let cartridge = load_rom_to_psram(&mut display, storage.clone(), timer, &name, psram);
         cartridge
     };
     #[cfg(not(feature = "psram_rom"))]
-    let cartridge = load_rom(&mut display, volume_mgr, &name, timer, |bd| {
-        bd.mark_card_uninit();
-    });
+    let cartridge = load_rom(&mut display, storage.clone(), &name, timer);
 
-    let gameboy = GameBoy::create(screen, cartridge, boot_rom, Box::new(i2s_interface));
-
-    let button_handler = InputButtonMapper::new(
-        &mut a_button,
-        &mut b_button,
-        &mut start_button,
-        &mut select_button,
-        &mut up_button,
-        &mut down_button,
-        &mut left_button,
-        &mut right_button,
-    );
+    let gameboy = GameBoy::create(screen, cartridge, boot_rom, audio_player);
+
+    let button_handler =
+        InputButtonMapper::new(&mut inputs, game_settings.mapping, game_settings.turbo);
     led_pin.set_high().unwrap();
 
+    let mut osd = Osd::new(GAMEBOY_RENDER_WIDTH, GAMEBOY_RENDER_HEIGHT);
+    osd.show_toast(Some(OsdIcon::Cartridge), &name, timer.get_counter());
+
+    let screenshot_format = if SCREENSHOT_PNG {
+        ScreenshotFormat::Png
+    } else {
+        ScreenshotFormat::Bmp
+    };
+    let save_screenshot = |frame: &[Rgb565]| {
+        gameboy::screenshot::save_screenshot(
+            &mut storage.borrow_mut(),
+            &name,
+            frame,
+            (
+                GAMEBOY_RENDER_WIDTH as usize,
+                GAMEBOY_RENDER_HEIGHT as usize,
+            ),
+            screenshot_format,
+        )
+        .map_err(|error| {
+            defmt::error!("Failed to save screenshot: {}", defmt::Debug2Format(&error));
+        })
+        .ok()
+    };
+
+    // Called every frame once the picture was sent, writes the whole blocks recorded so far so
+    // the cost of the SD card is spread evenly over the frames
+    let mut recording: Option<WavRecording> = None;
+    let update_recording = |request: RecordingRequest| -> Option<String> {
+        let mut storage = storage.borrow_mut();
+        if request == RecordingRequest::Toggle && recording.is_none() {
+            if !game_settings.audio_enabled {
+                return Some("Audio is disabled".to_string());
+            }
+            return match WavRecording::start(&mut storage, &name, AUDIO_SOURCE_RATE) {
+                Ok(started) => {
+                    let message = alloc::format!("Recording {}", started.file_name());
+                    recording = Some(started);
+                    *recording_tap.borrow_mut() = Some(alloc::vec::Vec::new());
+                    Some(message)
+                }
+                Err(error) => {
+                    defmt::error!("Failed to start recording: {}", defmt::Debug2Format(&error));
+                    Some("Recording failed".to_string())
+                }
+            };
+        }
+        let active = recording.as_mut()?;
+        let stop = request != RecordingRequest::Continue;
+        let written = {
+            let mut tap = recording_tap.borrow_mut();
+            let samples = tap.as_mut().unwrap();
+            let count = if stop {
+                samples.len()
+            } else {
+                (samples.len() / BLOCK_SAMPLES).min(MAX_BLOCKS_PER_FRAME) * BLOCK_SAMPLES
+            };
+            if count == 0 && !stop {
+                return None;
+            }
+            let written = active.write(&mut storage, &samples[..count]);
+            samples.drain(..count);
+            written
+        };
+        if let Err(error) = &written {
+            defmt::error!("Failed to write recording: {}", defmt::Debug2Format(error));
+        } else if !stop {
+            return None;
+        }
+        *recording_tap.borrow_mut() = None;
+        let finished = recording.take().unwrap().finish(&mut storage);
+        match (finished, written) {
+            (Ok(file_name), Ok(_)) => Some(alloc::format!("Saved {}", file_name)),
+            (Err(error), _) => {
+                defmt::error!(
+                    "Failed to finish recording: {}",
+                    defmt::Debug2Format(&error)
+                );
+                Some("Recording failed".to_string())
+            }
+            (Ok(_), Err(_)) => Some("Recording failed".to_string()),
+        }
+    };
+
+    // Only what changed while playing is taken over, the per game overrides are not saved
+    let save_settings = |before: &Settings, after: &Settings| {
+        settings.merge_changes(before, after);
+        if let Err(error) = settings.save(&mut storage.borrow_mut()) {
+            defmt::error!("Failed to save settings: {}", defmt::Debug2Format(&error));
+        }
+    };
+
+    // Charge level for the stats corner, `None` without a battery pin
+    #[cfg(feature = "battery")]
+    let read_battery = || battery.read_percent();
+    #[cfg(not(feature = "battery"))]
+    let read_battery = || None;
+
     display.clear(Rgb565::BLACK).unwrap();
-    run_game_boy(gameboy, display, button_handler, timer);
+    run_game_boy(
+        gameboy,
+        display,
+        button_handler,
+        osd,
+        frame_sync,
+        fast_forward,
+        save_screenshot,
+        update_recording,
+        volume,
+        output_mode,
+        joystick,
+        game_settings,
+        save_settings,
+        read_battery,
+        audio_stats,
+        timer,
+    );
     loop {
         crate::hal::arch::nop();
     }
 }
 
 #[inline(never)]
-pub fn run_game_boy<'a, D: TimerDevice, DI, M, RST, BH: GameboyButtonHandler<'a>>(
+pub fn run_game_boy<
+    'a,
+    D: TimerDevice,
+    DS: PixelSink,
+    BH: GameboyButtonHandler<'a>,
+    FS: FrameSync,
+    SS: FnMut(&[Rgb565]) -> Option<String>,
+    RC: FnMut(RecordingRequest) -> Option<String>,
+    SV: FnMut(&Settings, &Settings),
+    RB: FnMut() -> Option<u8>,
+>(
     mut gameboy: GameBoy<'a, GameboyLineBufferDisplay<D>>,
-    mut display: Display<DI, M, RST>,
+    mut display: DS,
     mut button_handler: BH,
+    mut osd: Osd,
+    mut frame_sync: FS,
+    fast_forward: SharedFastForward,
+    mut save_screenshot: SS,
+    mut update_recording: RC,
+    volume: SharedVolume,
+    output_mode: SharedOutputMode,
+    joystick: SharedJoystick,
+    mut game_settings: Settings,
+    mut save_settings: SV,
+    mut read_battery: RB,
+    audio_stats: SharedAudioStats,
     timer: crate::hal::Timer<D>,
-) where
-    DI: WriteOnlyDataCommand,
-    M: Model<ColorFormat = Rgb565>,
-    RST: OutputPin,
-{
-    const MIDDLE_HEIGHT: u16 = (RENDER_HEIGHT - GAMEBOY_RENDER_HEIGHT) / 2;
-    const MIDDLE_WIDTH: u16 = (RENDER_WIDTH - GAMEBOY_RENDER_WIDTH) / 2;
+) {
+    // Volume changes are saved once the hotkeys are left alone, not on every step
+    const VOLUME_SAVE_DELAY: fugit::MicrosDurationU64 = fugit::MicrosDurationU64::millis(3000);
+    let render_size = display.bounding_box().size;
+    let middle_height = (render_size.height as u16 - GAMEBOY_RENDER_HEIGHT) / 2;
+    let middle_width = (render_size.width as u16 - GAMEBOY_RENDER_WIDTH) / 2;
+    const GAMEBOY_LINES: usize = 144 - 1;
     let scaler: ScreenScaler<
-        { 144 - 1 },
+        GAMEBOY_LINES,
         160,
         { GAMEBOY_RENDER_HEIGHT as usize },
         { GAMEBOY_RENDER_WIDTH as usize },
     > = ScreenScaler::new();
+    const FRAME_PIXELS: usize = GAMEBOY_RENDER_WIDTH as usize * GAMEBOY_RENDER_HEIGHT as usize;
     let mut loop_counter: usize = 0;
+    let mut stats_start = timer.get_counter();
+    let mut stats_frames: u32 = 0;
+    let mut line_tracker: LineChangeTracker<GAMEBOY_LINES> = LineChangeTracker::new();
+    let mut volume_changed_at = None;
+    let mut reported_audio = audio_stats.get();
     loop {
         let start_time = timer.get_counter();
-        display
-            .set_pixels(
-                MIDDLE_WIDTH,
-                MIDDLE_HEIGHT,
-                (GAMEBOY_RENDER_WIDTH - 1) as u16 + MIDDLE_WIDTH,
-                (GAMEBOY_RENDER_HEIGHT - 1) as u16 + MIDDLE_HEIGHT,
-                scaler.scale_iterator(GameEmulationHandler::new(&mut gameboy, &mut button_handler)),
-            )
-            .unwrap();
+        osd.update(start_time);
+        button_handler.next_frame();
+        // Fast-forward runs the frames back to back, without waiting for the screen or the audio
+        fast_forward.set(button_handler.fast_forward());
+        if !fast_forward.get() {
+            frame_sync.wait_for_frame_start();
+        }
+        // Only the lines under a changed overlay are sent again, the tracker hashes the Game
+        // Boy lines before the OSD is blended in
+        if let Some(rows) = osd.take_changed() {
+            line_tracker.invalidate_rows(rows, |line| scaler.output_row(line));
+        }
+        if DISPLAY_SKIP_UNCHANGED_LINES {
+            let mut emulation = GameEmulationHandler::new(&mut gameboy, &mut button_handler);
+            let mut line = 0;
+            while line < GAMEBOY_LINES && emulation.emulate_line() {
+                if !line_tracker.update(line, emulation.line()) {
+                    emulation.finish_line();
+                    line += 1;
+                    continue;
+                }
+                // The address window runs to the bottom of the image, the run of changed
+                // lines simply stops writing early
+                let start_row = scaler.output_row(line);
+                let changed_lines = scaler.scale_iterator_from(
+                    line,
+                    ChangedLinesIterator::new(&mut emulation, &mut line_tracker, &mut line),
+                );
+                display
+                    .set_pixels(
+                        middle_width,
+                        middle_height + start_row,
+                        (GAMEBOY_RENDER_WIDTH - 1) as u16 + middle_width,
+                        (GAMEBOY_RENDER_HEIGHT - 1) as u16 + middle_height,
+                        osd.overlay_from(start_row, changed_lines),
+                    )
+                    .unwrap();
+            }
+        } else {
+            let frame = scaler
+                .scale_iterator(GameEmulationHandler::new(&mut gameboy, &mut button_handler))
+                .take(FRAME_PIXELS);
+            display
+                .set_pixels(
+                    middle_width,
+                    middle_height,
+                    (GAMEBOY_RENDER_WIDTH - 1) as u16 + middle_width,
+                    (GAMEBOY_RENDER_HEIGHT - 1) as u16 + middle_height,
+                    osd.overlay(frame),
+                )
+                .unwrap();
+        }
+
+        // The Game Boy is in its vertical blanking and the screen has the whole frame, the
+        // recording is flushed now rather than in the middle of the next frame
+        if let Some(message) = update_recording(RecordingRequest::Continue) {
+            osd.show_toast(Some(OsdIcon::Record), &message, timer.get_counter());
+        }
+
+        let mut recording_request = RecordingRequest::Continue;
+        match button_handler.take_action() {
+            Some(EmulatorAction::Screenshot) => gameboy.get_screen().capture_next_frame(),
+            Some(EmulatorAction::ToggleRecording) => recording_request = RecordingRequest::Toggle,
+            Some(EmulatorAction::OpenMenu) => {
+                let choice = game_menu(&mut display, &mut button_handler.menu_buttons()).unwrap();
+                match choice {
+                    GameMenuChoice::Resume => {}
+                    GameMenuChoice::Settings => {
+                        // The menu starts from the volume the hotkeys left
+                        if volume_changed_at.take().is_some() {
+                            let before = game_settings;
+                            game_settings.volume = volume.get();
+                            save_settings(&before, &game_settings);
+                        }
+                        let before = game_settings;
+                        let changed = settings_menu(
+                            &mut display,
+                            &mut game_settings,
+                            &joystick,
+                            &mut button_handler.menu_buttons(),
+                        )
+                        .unwrap();
+                        if changed {
+                            volume.set(game_settings.volume);
+                            output_mode.set(game_settings.output_mode);
+                            button_handler.set_controls(game_settings.mapping, game_settings.turbo);
+                            save_settings(&before, &game_settings);
+                            // gb-core keeps the audio player it was started with
+                            if game_settings.audio_enabled != before.audio_enabled {
+                                let now = timer.get_counter();
+                                osd.show_toast(None, "Audio applies on restart", now);
+                            }
+                        }
+                    }
+                    GameMenuChoice::Quit => {
+                        // The ram banks are saved as the game writes them, what is left are
+                        // the recording and the volume
+                        update_recording(RecordingRequest::Stop);
+                        if volume_changed_at.is_some() {
+                            let before = game_settings;
+                            game_settings.volume = volume.get();
+                            save_settings(&before, &game_settings);
+                        }
+                        defmt::info!("Leaving the game");
+                        cortex_m::peripheral::SCB::sys_reset();
+                    }
+                }
+                display.clear(Rgb565::BLACK).unwrap();
+                line_tracker.invalidate();
+            }
+            Some(
+                action @ (EmulatorAction::VolumeUp
+                | EmulatorAction::VolumeDown
+                | EmulatorAction::ToggleMute),
+            ) => {
+                let mut new_volume = volume.get();
+                match action {
+                    EmulatorAction::VolumeUp => new_volume.up(),
+                    EmulatorAction::VolumeDown => new_volume.down(),
+                    _ => new_volume.toggle_mute(),
+                }
+                volume.set(new_volume);
+                let now = timer.get_counter();
+                if new_volume.muted || new_volume.level == 0 {
+                    osd.show_toast(Some(OsdIcon::SpeakerMuted), "Muted", now);
+                } else {
+                    let message = alloc::format!("Volume {}%", new_volume.percent());
+                    osd.show_toast(Some(OsdIcon::Speaker), &message, now);
+                }
+                volume_changed_at = Some(now);
+            }
+            None => {}
+        }
+        if let Some(changed_at) = volume_changed_at {
+            if timer.get_counter() - changed_at >= VOLUME_SAVE_DELAY {
+                let before = game_settings;
+                game_settings.volume = volume.get();
+                save_settings(&before, &game_settings);
+                volume_changed_at = None;
+            }
+        }
+        if recording_request != RecordingRequest::Continue {
+            if let Some(message) = update_recording(recording_request) {
+                osd.show_toast(Some(OsdIcon::Record), &message, timer.get_counter());
+            }
+        }
+        if let Some(frame) = gameboy.get_screen().take_capture() {
+            let message = match save_screenshot(&frame) {
+                Some(file_name) => alloc::format!("Saved {}", file_name),
+                None => "Screenshot failed".to_string(),
+            };
+            osd.show_toast(Some(OsdIcon::Camera), &message, timer.get_counter());
+        }
 
         let end_time: hal::fugit::Instant<u64, 1, 1000000> = timer.get_counter();
         let diff: fugit::Duration<u64, 1, 1000000> = end_time - start_time;
@@ -466,6 +1179,39 @@ pub fn run_game_boy<'a, D: TimerDevice, DI, M, RST, BH: GameboyButtonHandler<'a>
             milliseconds % 1000
         );
         loop_counter += 1;
+
+        stats_frames += 1;
+        let stats_elapsed = (end_time - stats_start).to_micros();
+        if stats_elapsed >= 1_000_000 {
+            let mut stats = String::new();
+            if OSD_STATS {
+                // The DMG refreshes at ~59.73Hz, speed is reported against that
+                let fps_tenths = stats_frames as u64 * 10_000_000 / stats_elapsed;
+                let speed = fps_tenths * 1000 / 5973;
+                stats = alloc::format!("{}.{}fps {}%", fps_tenths / 10, fps_tenths % 10, speed);
+            }
+            if let Some(battery) = read_battery() {
+                if !stats.is_empty() {
+                    stats.push(' ');
+                }
+                stats.push_str(&alloc::format!("B{}%", battery));
+            }
+            osd.set_stats(&stats);
+            let audio = audio_stats.get();
+            if audio.underruns != reported_audio.underruns
+                || audio.dropped_frames != reported_audio.dropped_frames
+            {
+                defmt::warn!(
+                    "Audio: {} underruns, {} frames dropped in the last second, {} frames buffered",
+                    audio.underruns - reported_audio.underruns,
+                    audio.dropped_frames - reported_audio.dropped_frames,
+                    audio.buffered_frames
+                );
+            }
+            reported_audio = audio;
+            stats_start = end_time;
+            stats_frames = 0;
+        }
     }
 }
 
@@ -503,68 +1249,57 @@ fn load_rom<
     const MAX_VOLUMES: usize,
 >(
     display: &mut DISPLAY,
-    mut volume_manager: embedded_sdmmc::VolumeManager<D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+    storage: Rc<RefCell<SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>>>,
     rom_name: &str,
     timer: crate::hal::Timer<DT>,
-    device_reset: DR,
 ) -> Box<dyn Cartridge + 'a> {
     use hardware::flash::FLASH_SECTOR_SIZE;
-    device_reset(volume_manager.device());
-    let mut volume = volume_manager
-        .open_volume(embedded_sdmmc::VolumeIdx(0))
-        .unwrap();
-    let mut root_dir = volume.open_root_dir().unwrap();
-    let mut rom_file = root_dir
-        .open_file_in_dir(rom_name, embedded_sdmmc::Mode::ReadOnly)
-        .unwrap();
-
-    if ROM_FLASH_SIZE < rom_file.length() as usize {
-        panic!(
-            "Ram size not bigh enough for Rom of size: {}",
-            rom_file.length()
-        )
-    }
-    defmt::info!("Loading rom into flash");
-    let mut offsets = rom_file.length() / FLASH_SECTOR_SIZE;
-    if rom_file.length() % FLASH_SECTOR_SIZE != 0 {
-        offsets += 1;
-    }
-
-    let mut buffer = [0u8; FLASH_SECTOR_SIZE as usize];
-
-    let mut loading_screen = LoadingScreen::new(
-        Point::new(0, 0),
-        Size::new(DISPLAY_HEIGHT as u32, DISPLAY_WIDTH as u32),
-        rom_name.to_string(),
-    );
-    if let Err(_) = loading_screen.draw(display, 0) {};
-
-    for x in 0..offsets {
-        defmt::info!("Loading rom into flash for offset: {}", x);
-        rom_file.seek_from_start(x * FLASH_SECTOR_SIZE).unwrap();
-        rom_file.read(&mut buffer).unwrap();
-        let write_result = unsafe { FLASH_ROM_DATA.write_flash(x, &mut buffer) };
-        let percent = (x as f32 / offsets as f32) * 100f32;
-        defmt::info!(
-            "Result from write into flash for offset: {}: {}, percent: {}",
-            x,
-            write_result,
-            percent
-        );
-        if let Err(_) = loading_screen.update_progress(display, percent as u8) {};
-    }
+    storage.borrow_mut().reset_device();
+    storage
+        .borrow_mut()
+        .with_file(&[], rom_name, embedded_sdmmc::Mode::ReadOnly, |rom_file| {
+            if ROM_FLASH_SIZE < rom_file.length() as usize {
+                panic!(
+                    "Ram size not bigh enough for Rom of size: {}",
+                    rom_file.length()
+                )
+            }
+            defmt::info!("Loading rom into flash");
+            let mut offsets = rom_file.length() / FLASH_SECTOR_SIZE;
+            if rom_file.length() % FLASH_SECTOR_SIZE != 0 {
+                offsets += 1;
+            }
 
-    rom_file.close().unwrap();
-    root_dir.close().unwrap();
-    volume.close().unwrap();
+            let mut buffer = [0u8; FLASH_SECTOR_SIZE as usize];
+
+            let mut loading_screen = LoadingScreen::new(
+                Point::new(0, 0),
+                display.bounding_box().size,
+                rom_name.to_string(),
+            );
+            if let Err(_) = loading_screen.draw(display, 0) {};
+
+            for x in 0..offsets {
+                defmt::info!("Loading rom into flash for offset: {}", x);
+                rom_file.seek_from_start(x * FLASH_SECTOR_SIZE).unwrap();
+                rom_file.read(&mut buffer).unwrap();
+                let write_result = unsafe { FLASH_ROM_DATA.write_flash(x, &mut buffer) };
+                let percent = (x as f32 / offsets as f32) * 100f32;
+                defmt::info!(
+                    "Result from write into flash for offset: {}: {}, percent: {}",
+                    x,
+                    write_result,
+                    percent
+                );
+                if let Err(_) = loading_screen.update_progress(display, percent as u8) {};
+            }
+            Ok(())
+        })
+        .unwrap();
     defmt::info!("Loading complete");
 
-    let rom_manager = gameboy::static_rom::StaticRomManager::new(
-        FLASH_ROM_DATA.read(),
-        volume_manager,
-        timer,
-        device_reset,
-    );
+    let rom_manager =
+        gameboy::static_rom::StaticRomManager::new(FLASH_ROM_DATA.read(), storage, timer);
     let gb_rom = gb_core::hardware::rom::Rom::from_bytes(rom_manager);
     gb_rom.into_cartridge()
 }
@@ -583,10 +1318,9 @@ fn load_rom<
     const MAX_VOLUMES: usize,
 >(
     display: &mut DISPLAY,
-    volume_manager: embedded_sdmmc::VolumeManager<D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+    storage: Rc<RefCell<SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>>>,
     rom_name: &str,
     timer: crate::hal::Timer<DT>,
-    device_reset: DR,
 ) -> Box<dyn Cartridge + 'a> {
     defmt::info!("Loading from SDCARD");
     #[const_env::from_env]
@@ -600,46 +1334,105 @@ fn load_rom<
         MAX_DIRS,
         MAX_FILES,
         MAX_VOLUMES,
-    > = gameboy::rom::SdRomManager::new(rom_name, volume_manager, timer, device_reset);
+    > = gameboy::rom::SdRomManager::new(rom_name, storage, timer);
     let gb_rom = gb_core::hardware::rom::Rom::from_bytes(rom_manager);
     gb_rom.into_cartridge()
 }
 
 #[inline(always)]
 fn load_boot_rom<
-    'a,
     D: embedded_sdmmc::BlockDevice,
     T: embedded_sdmmc::TimeSource,
+    DR: Fn(&mut D),
     const MAX_DIRS: usize,
     const MAX_FILES: usize,
     const MAX_VOLUMES: usize,
 >(
-    volume_manager: &'a mut embedded_sdmmc::VolumeManager<D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+    storage: &mut SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
 ) -> Bootrom {
     use gb_core::hardware::boot_rom::BootromData;
-    let mut volume0 = volume_manager
-        .open_volume(embedded_sdmmc::VolumeIdx(0))
-        .unwrap();
-    let mut root_dir = volume0.open_root_dir().unwrap();
-
-    if root_dir
-        .find_directory_entry(env!("BOOT_ROM_PATH"))
-        .is_err()
-    {
+    if !storage.exists(&[], env!("BOOT_ROM_PATH")).unwrap() {
         return Bootrom::new(None);
     }
 
     let dmg_boot_bin: &'static mut [u8] = cortex_m::singleton!(: [u8; 0x100]  = [0u8; 0x100 ])
         .unwrap()
         .as_mut_slice();
-    let mut boot_rom_file = root_dir
-        .open_file_in_dir(env!("BOOT_ROM_PATH"), embedded_sdmmc::Mode::ReadOnly)
+    storage
+        .with_file(
+            &[],
+            env!("BOOT_ROM_PATH"),
+            embedded_sdmmc::Mode::ReadOnly,
+            |boot_rom_file| boot_rom_file.read(&mut *dmg_boot_bin),
+        )
         .unwrap();
-
-    boot_rom_file.read(&mut *dmg_boot_bin).unwrap();
     Bootrom::new(Some(BootromData::from_bytes(dmg_boot_bin)))
 }
 
+/// Starts from the compiled display settings and applies `display.cfg` when the SD card has one.
+#[cfg(not(feature = "dvi_output"))]
+fn load_display_config<
+    D: embedded_sdmmc::BlockDevice,
+    T: embedded_sdmmc::TimeSource,
+    DR: Fn(&mut D),
+    const MAX_DIRS: usize,
+    const MAX_FILES: usize,
+    const MAX_VOLUMES: usize,
+>(
+    storage: &mut SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+) -> hardware::display::DisplayConfig {
+    use hardware::display::{apply_display_config, DisplayConfig, DISPLAY_CONFIG_FILE};
+
+    let default_config = DisplayConfig {
+        width: DISPLAY_WIDTH,
+        height: DISPLAY_HEIGHT,
+        rotation: DISPLAY_ROTATION,
+        mirrored: DISPLAY_MIRRORED,
+        invert_colors: DISPLAY_COLOR_INVERT,
+        bgr: DISPLAY_COLOR_BGR,
+        clock_divider: (DISPLAY_CLOCK_DIVIDER, DISPLAY_CLOCK_DIVIDER_FRAC),
+    };
+    let content = match storage.read_file(&[], DISPLAY_CONFIG_FILE) {
+        Ok(content) => content,
+        Err(embedded_sdmmc::Error::NotFound) => return default_config,
+        Err(error) => {
+            defmt::error!(
+                "Failed to read {}: {}",
+                DISPLAY_CONFIG_FILE,
+                defmt::Debug2Format(&error)
+            );
+            return default_config;
+        }
+    };
+    let Ok(text) = core::str::from_utf8(&content) else {
+        defmt::error!("{} is not valid UTF-8", DISPLAY_CONFIG_FILE);
+        return default_config;
+    };
+    let mut config = default_config;
+    apply_display_config(&mut config, text);
+
+    if !config.fits(GAMEBOY_RENDER_WIDTH, GAMEBOY_RENDER_HEIGHT) {
+        let (render_width, render_height) = config.render_size();
+        defmt::error!(
+            "{}: screen {}x{} is smaller than the Game Boy render size {}x{}, using the defaults",
+            DISPLAY_CONFIG_FILE,
+            render_width,
+            render_height,
+            GAMEBOY_RENDER_WIDTH,
+            GAMEBOY_RENDER_HEIGHT
+        );
+        return default_config;
+    }
+    defmt::info!(
+        "Loaded {}: {}x{}, rotation {}",
+        DISPLAY_CONFIG_FILE,
+        config.width,
+        config.height,
+        config.rotation
+    );
+    config
+}
+
 //#[cfg(feature = "psram_rom")]
 #[inline(always)]
 fn load_rom_to_psram<
@@ -654,67 +1447,60 @@ fn load_rom_to_psram<
     const MAX_VOLUMES: usize,
 >(
     display: &mut DISPLAY,
-    mut volume_manager: embedded_sdmmc::VolumeManager<D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+    storage: Rc<RefCell<SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>>>,
     timer: crate::hal::Timer<DT>,
     rom_name: &str,
     ram: &'static mut [u8],
-    device_reset: DR,
 ) -> Box<dyn Cartridge + 'a> {
     pub const ROM_READ_BUFFER_SIZE: u32 = 4096 * 4;
-    device_reset(volume_manager.device());
-    let mut volume = volume_manager
-        .open_volume(embedded_sdmmc::VolumeIdx(0))
-        .unwrap();
-    let mut root_dir = volume.open_root_dir().unwrap();
-    let mut rom_file = root_dir
-        .open_file_in_dir(rom_name, embedded_sdmmc::Mode::ReadOnly)
+    storage.borrow_mut().reset_device();
+    storage
+        .borrow_mut()
+        .with_file(&[], rom_name, embedded_sdmmc::Mode::ReadOnly, |rom_file| {
+            if ram.len() < rom_file.length() as usize {
+                panic!(
+                    "Ram size not bigh enough for Rom of size: {}",
+                    rom_file.length()
+                )
+            }
+            let mut offsets = rom_file.length() / ROM_READ_BUFFER_SIZE;
+            if rom_file.length() % ROM_READ_BUFFER_SIZE != 0 {
+                offsets += 1;
+            }
+            defmt::info!("Loading rom into psram");
+
+            let mut buffer = [0u8; ROM_READ_BUFFER_SIZE as usize];
+
+            let mut loading_screen = LoadingScreen::new(
+                Point::new(0, 0),
+                display.bounding_box().size,
+                rom_name.to_string(),
+            );
+            if let Err(_) = loading_screen.draw(display, 0) {};
+
+            rom_file.seek_from_start(0u32).unwrap();
+            for x in 0..offsets {
+                defmt::info!("Loading rom into psram for offset: {}", x);
+                rom_file.seek_from_start(x * ROM_READ_BUFFER_SIZE).unwrap();
+                rom_file.read(&mut buffer).unwrap();
+                // let write_result = unsafe { FLASH_ROM_DATA.write_flash(x, &mut buffer) };
+                let addr = ROM_READ_BUFFER_SIZE * x;
+                ram[addr as usize..addr as usize + ROM_READ_BUFFER_SIZE as usize]
+                    .copy_from_slice(&buffer);
+                let percent = (x as f32 / offsets as f32) * 100f32;
+                defmt::info!(
+                    "Result from write into psram for offset: {}, percent: {}",
+                    x,
+                    percent
+                );
+                if let Err(_) = loading_screen.update_progress(display, percent as u8) {};
+            }
+            Ok(())
+        })
         .unwrap();
-
-    if ram.len() < rom_file.length() as usize {
-        panic!(
-            "Ram size not bigh enough for Rom of size: {}",
-            rom_file.length()
-        )
-    }
-    let mut offsets = rom_file.length() / ROM_READ_BUFFER_SIZE;
-    if rom_file.length() % ROM_READ_BUFFER_SIZE != 0 {
-        offsets += 1;
-    }
-    defmt::info!("Loading rom into psram");
-
-    let mut buffer = [0u8; ROM_READ_BUFFER_SIZE as usize];
-
-    let mut loading_screen = LoadingScreen::new(
-        Point::new(0, 0),
-        Size::new(RENDER_WIDTH as u32, RENDER_HEIGHT as u32),
-        rom_name.to_string(),
-    );
-    if let Err(_) = loading_screen.draw(display, 0) {};
-
-    rom_file.seek_from_start(0u32).unwrap();
-    for x in 0..offsets {
-        defmt::info!("Loading rom into psram for offset: {}", x);
-        rom_file.seek_from_start(x * ROM_READ_BUFFER_SIZE).unwrap();
-        rom_file.read(&mut buffer).unwrap();
-        // let write_result = unsafe { FLASH_ROM_DATA.write_flash(x, &mut buffer) };
-        let addr = ROM_READ_BUFFER_SIZE * x;
-        ram[addr as usize..addr as usize + ROM_READ_BUFFER_SIZE as usize].copy_from_slice(&buffer);
-        let percent = (x as f32 / offsets as f32) * 100f32;
-        defmt::info!(
-            "Result from write into psram for offset: {}, percent: {}",
-            x,
-            percent
-        );
-        if let Err(_) = loading_screen.update_progress(display, percent as u8) {};
-    }
-
-    rom_file.close().unwrap();
-    root_dir.close().unwrap();
-    volume.close().unwrap();
     defmt::info!("Loading complete");
 
-    let rom_manager =
-        gameboy::static_rom::StaticRomManager::new(ram, volume_manager, timer, device_reset);
+    let rom_manager = gameboy::static_rom::StaticRomManager::new(ram, storage, timer);
     let gb_rom = gb_core::hardware::rom::Rom::from_bytes(rom_manager);
     gb_rom.into_cartridge()
 }
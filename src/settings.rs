@@ -0,0 +1,233 @@
+use alloc::string::String;
+use embedded_sdmmc::Mode;
+
+use crate::hardware::sdcard::SdStorage;
+use crate::hardware::sound::{OutputMode, Volume, VOLUME_STEPS};
+use crate::input::joystick::JoystickCalibration;
+use crate::input::mapping::ButtonMapping;
+use crate::input::turbo::{Turbo, TURBO_RATE_MAX, TURBO_RATE_MIN};
+use crate::util::{config_entries, parse_bool};
+
+/// Name of the file in the root of the SD card that keeps the settings changed at runtime.
+pub const SETTINGS_FILE: &str = "settings.cfg";
+/// Directory with the settings of single games, `<rom name>.cfg` with the keys of
+/// `settings.cfg` that should differ for that game.
+const GAME_SETTINGS_DIR: &str = "games";
+
+/// Settings changed through the menus and hotkeys, saved to the SD card so they survive a
+/// reboot.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    pub volume: Volume,
+    pub output_mode: OutputMode,
+    /// Without audio gb-core generates fewer samples and the audio output is never started,
+    /// leaving more time for the emulation.
+    pub audio_enabled: bool,
+    /// Stored as `MAP_<target>` keys, only the targets that differ from the default.
+    pub mapping: ButtonMapping,
+    pub turbo: Turbo,
+    /// Stored as `JOYSTICK_*` keys once it differs from the default.
+    pub joystick: JoystickCalibration,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            volume: Volume::default(),
+            output_mode: OutputMode::default(),
+            audio_enabled: true,
+            mapping: ButtonMapping::default(),
+            turbo: Turbo::default(),
+            joystick: JoystickCalibration::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Applies the `KEY=value` entries of a settings file, unknown keys and invalid values are
+    /// reported and ignored.
+    pub fn apply(&mut self, text: &str) {
+        for (key, value) in config_entries(text) {
+            if let Some(valid) = self
+                .mapping
+                .apply(key, value)
+                .or_else(|| self.joystick.apply(key, value))
+            {
+                if !valid {
+                    defmt::warn!("Invalid value for setting {}: {}", key, value);
+                }
+                continue;
+            }
+            let valid = match key {
+                "VOLUME" => match value.parse::<u8>() {
+                    Ok(level) if level <= VOLUME_STEPS => {
+                        self.volume.level = level;
+                        true
+                    }
+                    _ => false,
+                },
+                "MUTED" => parse_bool(value)
+                    .map(|muted| self.volume.muted = muted)
+                    .is_some(),
+                "AUDIO" => parse_bool(value)
+                    .map(|enabled| self.audio_enabled = enabled)
+                    .is_some(),
+                "TURBO_RATE" => match value.parse::<u8>() {
+                    Ok(rate) if (TURBO_RATE_MIN..=TURBO_RATE_MAX).contains(&rate) => {
+                        self.turbo.rate = rate;
+                        true
+                    }
+                    _ => false,
+                },
+                "TURBO_A" => parse_bool(value)
+                    .map(|turbo| self.turbo.a = turbo)
+                    .is_some(),
+                "TURBO_B" => parse_bool(value)
+                    .map(|turbo| self.turbo.b = turbo)
+                    .is_some(),
+                "AUDIO_OUTPUT" => OutputMode::from_config(value)
+                    .map(|mode| self.output_mode = mode)
+                    .is_some(),
+                _ => {
+                    defmt::warn!("Unknown setting: {}", key);
+                    continue;
+                }
+            };
+            if !valid {
+                defmt::warn!("Invalid value for setting {}: {}", key, value);
+            }
+        }
+    }
+
+    pub fn to_config(&self) -> String {
+        let mut config = alloc::format!(
+            "VOLUME={}\nMUTED={}\nAUDIO={}\nAUDIO_OUTPUT={}\nTURBO_RATE={}\nTURBO_A={}\nTURBO_B={}\n",
+            self.volume.level,
+            self.volume.muted,
+            self.audio_enabled,
+            self.output_mode.name().to_ascii_uppercase(),
+            self.turbo.rate,
+            self.turbo.a,
+            self.turbo.b
+        );
+        config += &self.mapping.to_config();
+        config += &self.joystick.to_config();
+        config
+    }
+
+    /// Reads `settings.cfg`, the defaults are used when it is missing or unreadable.
+    pub fn load<
+        D: embedded_sdmmc::BlockDevice,
+        T: embedded_sdmmc::TimeSource,
+        DR: Fn(&mut D),
+        const MAX_DIRS: usize,
+        const MAX_FILES: usize,
+        const MAX_VOLUMES: usize,
+    >(
+        storage: &mut SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+    ) -> Self {
+        let mut settings = Settings::default();
+        settings.apply_file(storage, &[], SETTINGS_FILE);
+        settings
+    }
+
+    /// These settings with the ones of `/games/<rom name>.cfg` applied on top, they are only
+    /// used while that game runs and never saved.
+    pub fn for_game<
+        D: embedded_sdmmc::BlockDevice,
+        T: embedded_sdmmc::TimeSource,
+        DR: Fn(&mut D),
+        const MAX_DIRS: usize,
+        const MAX_FILES: usize,
+        const MAX_VOLUMES: usize,
+    >(
+        &self,
+        storage: &mut SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+        rom_name: &str,
+    ) -> Self {
+        let mut settings = *self;
+        let base_name = rom_name.split('.').next().unwrap_or(rom_name);
+        let file_name = alloc::format!("{}.CFG", base_name);
+        settings.apply_file(storage, &[GAME_SETTINGS_DIR], &file_name);
+        settings
+    }
+
+    fn apply_file<
+        D: embedded_sdmmc::BlockDevice,
+        T: embedded_sdmmc::TimeSource,
+        DR: Fn(&mut D),
+        const MAX_DIRS: usize,
+        const MAX_FILES: usize,
+        const MAX_VOLUMES: usize,
+    >(
+        &mut self,
+        storage: &mut SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+        path: &[&str],
+        file_name: &str,
+    ) {
+        match storage.read_file(path, file_name) {
+            Ok(content) => match core::str::from_utf8(&content) {
+                Ok(text) => {
+                    defmt::info!(
+                        "Loading settings from {}",
+                        defmt::Display2Format(&file_name)
+                    );
+                    self.apply(text)
+                }
+                Err(_) => defmt::error!("{} is not valid UTF-8", file_name),
+            },
+            // No settings saved yet
+            Err(embedded_sdmmc::Error::NotFound) => {}
+            Err(error) => defmt::error!(
+                "Failed to read {}: {}",
+                file_name,
+                defmt::Debug2Format(&error)
+            ),
+        }
+    }
+
+    /// Takes over the entries that differ between `before` and `after`, so changes made to
+    /// the settings of a game are saved without its per game overrides.
+    pub fn merge_changes(&mut self, before: &Settings, after: &Settings) {
+        if before.volume != after.volume {
+            self.volume = after.volume;
+        }
+        if before.output_mode != after.output_mode {
+            self.output_mode = after.output_mode;
+        }
+        if before.audio_enabled != after.audio_enabled {
+            self.audio_enabled = after.audio_enabled;
+        }
+        if before.mapping != after.mapping {
+            self.mapping = after.mapping;
+        }
+        if before.turbo != after.turbo {
+            self.turbo = after.turbo;
+        }
+        if before.joystick != after.joystick {
+            self.joystick = after.joystick;
+        }
+    }
+
+    pub fn save<
+        D: embedded_sdmmc::BlockDevice,
+        T: embedded_sdmmc::TimeSource,
+        DR: Fn(&mut D),
+        const MAX_DIRS: usize,
+        const MAX_FILES: usize,
+        const MAX_VOLUMES: usize,
+    >(
+        &self,
+        storage: &mut SdStorage<D, T, DR, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+    ) -> Result<(), embedded_sdmmc::Error<D::Error>> {
+        defmt::info!("Saving {}", SETTINGS_FILE);
+        storage.reset_device();
+        let config = self.to_config();
+        storage.with_file(
+            &[],
+            SETTINGS_FILE,
+            Mode::ReadWriteCreateOrTruncate,
+            |file| file.write(config.as_bytes()),
+        )
+    }
+}
@@ -0,0 +1,134 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use embedded_graphics::{
+    mono_font::MonoTextStyleBuilder,
+    pixelcolor::Rgb565,
+    prelude::*,
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+};
+
+use crate::input::mapping::{ButtonMapping, TARGETS};
+
+use super::{
+    ListDisplay, MenuButtons, UiLayout, BUTTON_BACK, BUTTON_CONFIRM, BUTTON_DOWN, BUTTON_LEFT,
+    BUTTON_RIGHT, BUTTON_UP,
+};
+
+/// Lets the user pick the inputs of every Game Boy button and emulator action. A on an entry
+/// waits for an input and adds it, or removes it when it was already bound, RIGHT unbinds the
+/// entry and LEFT restores its default. Returns when B is pressed.
+pub fn controls_menu<D: DrawTarget<Color = Rgb565>>(
+    display: &mut D,
+    mapping: &mut ButtonMapping,
+    buttons: &mut MenuButtons,
+) -> Result<(), D::Error> {
+    display.clear(Rgb565::CSS_GRAY)?;
+    let size = display.bounding_box().size;
+    let layout = UiLayout::for_size(size);
+
+    let title_style = MonoTextStyleBuilder::new()
+        .font(layout.title_font)
+        .text_color(Rgb565::WHITE)
+        .build();
+    let title_height = layout.title_font.character_size.height as i32 + 8;
+    Text::with_baseline(
+        "Controls:",
+        Point::new(0, title_height / 2),
+        title_style,
+        Baseline::Middle,
+    )
+    .draw(display)?;
+    let hint_style = MonoTextStyleBuilder::new()
+        .font(layout.text_font)
+        .text_color(Rgb565::WHITE)
+        .build();
+    Text::with_text_style(
+        "A: Bind B: Back",
+        Point::new(size.width as i32 - 2, title_height / 2),
+        hint_style,
+        TextStyleBuilder::new()
+            .alignment(Alignment::Right)
+            .baseline(Baseline::Middle)
+            .build(),
+    )
+    .draw(display)?;
+
+    let item_size = layout.list_item_height + layout.list_item_padding;
+    let list = ListDisplay::new(
+        Point::new(0, title_height),
+        size.width as i32,
+        layout.list_item_height,
+        layout.list_item_padding,
+        layout.list_font,
+    );
+    let visible = (((size.height as i32 - title_height) / item_size) as usize).min(TARGETS.len());
+    let mut selected = 0usize;
+    let mut first = 0usize;
+    let mut waiting = false;
+    buttons.wait_for_release();
+    let mut redraw = true;
+    loop {
+        if redraw {
+            let labels: Vec<String> = TARGETS
+                .iter()
+                .enumerate()
+                .map(|(index, target)| {
+                    if waiting && index == selected {
+                        alloc::format!("{:<11}Press an input", target.label())
+                    } else {
+                        alloc::format!("{:<11}{}", target.label(), mapping.describe(*target))
+                    }
+                })
+                .collect();
+            list.draw(
+                labels[first..first + visible].iter(),
+                (selected - first) as u8,
+                display,
+            )?;
+            redraw = false;
+        }
+
+        if waiting {
+            let input = buttons.wait_for_input();
+            mapping.toggle(TARGETS[selected], input);
+            buttons.wait_for_release();
+            waiting = false;
+            redraw = true;
+            continue;
+        }
+
+        let events = buttons.poll();
+        let clicked = events.pressed;
+        if clicked & BUTTON_BACK != 0 {
+            break;
+        }
+        if events.repeated & BUTTON_UP != 0 && selected > 0 {
+            selected -= 1;
+            first = first.min(selected);
+            redraw = true;
+        }
+        if events.repeated & BUTTON_DOWN != 0 && selected + 1 < TARGETS.len() {
+            selected += 1;
+            if selected >= first + visible {
+                first = selected + 1 - visible;
+            }
+            redraw = true;
+        }
+        if clicked & BUTTON_LEFT != 0 {
+            mapping.reset(TARGETS[selected]);
+            redraw = true;
+        }
+        if clicked & BUTTON_RIGHT != 0 {
+            mapping.clear(TARGETS[selected]);
+            redraw = true;
+        }
+        if clicked & BUTTON_CONFIRM != 0 {
+            // The press of A must end before the input to bind is read
+            buttons.wait_for_release();
+            waiting = true;
+            redraw = true;
+        }
+    }
+    buttons.wait_for_release();
+    Ok(())
+}
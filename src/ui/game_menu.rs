@@ -0,0 +1,88 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use embedded_graphics::{
+    mono_font::MonoTextStyleBuilder,
+    pixelcolor::Rgb565,
+    prelude::*,
+    text::{Baseline, Text},
+};
+
+use super::{
+    ListDisplay, MenuButtons, UiLayout, BUTTON_BACK, BUTTON_CONFIRM, BUTTON_DOWN, BUTTON_UP,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GameMenuChoice {
+    Resume,
+    /// Change the settings without leaving the game.
+    Settings,
+    /// Leave the game and go back to the rom selection.
+    Quit,
+}
+
+const ITEMS: [(GameMenuChoice, &str); 3] = [
+    (GameMenuChoice::Resume, "Resume"),
+    (GameMenuChoice::Settings, "Settings"),
+    (GameMenuChoice::Quit, "Quit game"),
+];
+
+/// Menu shown while the game is paused, B resumes the game.
+pub fn game_menu<D: DrawTarget<Color = Rgb565>>(
+    display: &mut D,
+    buttons: &mut MenuButtons,
+) -> Result<GameMenuChoice, D::Error> {
+    display.clear(Rgb565::CSS_GRAY)?;
+    let size = display.bounding_box().size;
+    let layout = UiLayout::for_size(size);
+
+    let title_style = MonoTextStyleBuilder::new()
+        .font(layout.title_font)
+        .text_color(Rgb565::WHITE)
+        .build();
+    let title_height = layout.title_font.character_size.height as i32 + 8;
+    Text::with_baseline(
+        "Paused",
+        Point::new(0, title_height / 2),
+        title_style,
+        Baseline::Middle,
+    )
+    .draw(display)?;
+
+    let list = ListDisplay::new(
+        Point::new(0, title_height),
+        size.width as i32,
+        layout.list_item_height,
+        layout.list_item_padding,
+        layout.list_font,
+    );
+    // The hotkey that opened the menu is still held
+    buttons.wait_for_release();
+    let labels: Vec<String> = ITEMS.iter().map(|(_, label)| (*label).into()).collect();
+    let mut selected = 0usize;
+    let mut redraw = true;
+    let choice = loop {
+        if redraw {
+            list.draw(labels.iter(), selected as u8, display)?;
+            redraw = false;
+        }
+
+        let events = buttons.poll();
+        let clicked = events.pressed;
+        if clicked & BUTTON_BACK != 0 {
+            break GameMenuChoice::Resume;
+        }
+        if clicked & BUTTON_CONFIRM != 0 {
+            break ITEMS[selected].0;
+        }
+        if events.repeated & BUTTON_UP != 0 && selected > 0 {
+            selected -= 1;
+            redraw = true;
+        }
+        if events.repeated & BUTTON_DOWN != 0 && selected + 1 < ITEMS.len() {
+            selected += 1;
+            redraw = true;
+        }
+    };
+    buttons.wait_for_release();
+    Ok(choice)
+}
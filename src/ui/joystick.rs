@@ -0,0 +1,155 @@
+use embedded_graphics::{
+    mono_font::MonoTextStyleBuilder,
+    pixelcolor::Rgb565,
+    prelude::*,
+    text::{Baseline, Text},
+};
+
+use crate::input::joystick::{JoystickCalibration, JoystickState, AXIS_X, AXIS_Y};
+
+use super::{MenuButtons, UiLayout, BUTTON_BACK, BUTTON_CONFIRM};
+
+/// Calibrates the analog stick: it is left in the centre and A pressed, then moved to every
+/// edge and A pressed again. B cancels, a stick that barely moved keeps the old calibration.
+pub fn calibrate_joystick<D: DrawTarget<Color = Rgb565>>(
+    display: &mut D,
+    calibration: &mut JoystickCalibration,
+    joystick: &JoystickState,
+    buttons: &mut MenuButtons,
+) -> Result<(), D::Error> {
+    display.clear(Rgb565::CSS_GRAY)?;
+    let layout = UiLayout::for_size(display.bounding_box().size);
+    let title_style = MonoTextStyleBuilder::new()
+        .font(layout.title_font)
+        .text_color(Rgb565::WHITE)
+        .build();
+    let title_height = layout.title_font.character_size.height as i32 + 8;
+    Text::with_baseline(
+        "Calibrate stick:",
+        Point::new(0, title_height / 2),
+        title_style,
+        Baseline::Middle,
+    )
+    .draw(display)?;
+    let line_height = layout.text_font.character_size.height as i32 + 4;
+    let prompt_position = Point::new(2, title_height + line_height);
+    let readings_position = prompt_position + Point::new(0, line_height * 2);
+
+    buttons.wait_for_release();
+    let mut new = *calibration;
+    draw_line(
+        display,
+        &layout,
+        prompt_position,
+        "Centre the stick, press A",
+    )?;
+    let Some(center) = track_stick(
+        display,
+        &layout,
+        readings_position,
+        joystick,
+        buttons,
+        |_| {},
+    )?
+    else {
+        return Ok(());
+    };
+    new.center = center;
+    new.min = center;
+    new.max = center;
+
+    buttons.wait_for_release();
+    draw_line(
+        display,
+        &layout,
+        prompt_position,
+        "Move it to every edge, press A",
+    )?;
+    let moved = |position: [u16; 2]| {
+        for axis in [AXIS_X, AXIS_Y] {
+            new.min[axis] = new.min[axis].min(position[axis]);
+            new.max[axis] = new.max[axis].max(position[axis]);
+        }
+    };
+    if track_stick(
+        display,
+        &layout,
+        readings_position,
+        joystick,
+        buttons,
+        moved,
+    )?
+    .is_none()
+    {
+        return Ok(());
+    }
+    buttons.wait_for_release();
+    if new.is_valid() {
+        *calibration = new;
+        return Ok(());
+    }
+
+    draw_line(
+        display,
+        &layout,
+        prompt_position,
+        "Stick barely moved, not saved",
+    )?;
+    loop {
+        if buttons.poll().pressed & (BUTTON_CONFIRM | BUTTON_BACK) != 0 {
+            break;
+        }
+    }
+    buttons.wait_for_release();
+    Ok(())
+}
+
+/// Shows the readings of the stick until A or B is pressed, `moved` gets every new position.
+/// Returns the position A was pressed at or `None` for B.
+fn track_stick<D: DrawTarget<Color = Rgb565>>(
+    display: &mut D,
+    layout: &UiLayout,
+    position: Point,
+    joystick: &JoystickState,
+    buttons: &mut MenuButtons,
+    mut moved: impl FnMut([u16; 2]),
+) -> Result<Option<[u16; 2]>, D::Error> {
+    let mut shown = None;
+    loop {
+        let clicked = buttons.poll().pressed;
+        let stick = joystick.position.get();
+        if let Some(stick) = stick {
+            moved(stick);
+            if shown != Some(stick) {
+                let readings = alloc::format!("X {:>4}  Y {:>4}", stick[AXIS_X], stick[AXIS_Y]);
+                draw_line(display, layout, position, &readings)?;
+                shown = Some(stick);
+            }
+        }
+        if clicked & BUTTON_BACK != 0 {
+            return Ok(None);
+        }
+        if clicked & BUTTON_CONFIRM != 0 && stick.is_some() {
+            return Ok(stick);
+        }
+    }
+}
+
+/// Draws a line of text over whatever line was there before.
+fn draw_line<D: DrawTarget<Color = Rgb565>>(
+    display: &mut D,
+    layout: &UiLayout,
+    position: Point,
+    text: &str,
+) -> Result<(), D::Error> {
+    let style = MonoTextStyleBuilder::new()
+        .font(layout.text_font)
+        .text_color(Rgb565::WHITE)
+        .background_color(Rgb565::CSS_GRAY)
+        .build();
+    let width =
+        display.bounding_box().size.width as usize / layout.text_font.character_size.width as usize;
+    let text = alloc::format!("{:<width$}", text, width = width);
+    Text::with_baseline(&text, position, style, Baseline::Middle).draw(display)?;
+    Ok(())
+}
@@ -3,16 +3,18 @@ use embedded_graphics::{
     pixelcolor::Rgb565,
     prelude::*,
     primitives::{PrimitiveStyleBuilder, Rectangle},
-    text::Text,
+    text::{Alignment, Text},
 };
 
 use embedded_graphics::{
     draw_target::DrawTarget,
     geometry::{Point, Size},
-    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    mono_font::MonoTextStyle,
     Drawable,
 };
 
+use super::UiLayout;
+
 pub struct LoadingScreen {
     position: Point,
     size: Size,
@@ -57,23 +59,26 @@ impl LoadingScreen {
             .draw(display)?;
 
         // "Loading..." text
-        let text_style = MonoTextStyle::new(&FONT_6X10, Rgb565::new(31, 31, 31)); // White text
+        let layout = UiLayout::for_size(self.size);
+        let text_style = MonoTextStyle::new(layout.text_font, Rgb565::new(31, 31, 31)); // White text
+        let line_height = layout.text_font.character_size.height as i32;
         Text::new(
             "Loading...",
-            Point::new(self.position.x + 10, self.position.y + 20),
+            Point::new(self.position.x + 10, self.position.y + 10 + line_height),
             text_style,
         )
         .draw(display)?;
 
         self.draw_progress_bar(display)?;
 
-        Text::new(
+        Text::with_alignment(
             &self.rom_name,
             Point::new(
-                self.position.x + (self.size.width as i32 / 2) - 15, // Centered horizontally
-                self.position.y + (self.size.height as i32 / 2) + 25, // Below progress bar
+                self.position.x + (self.size.width as i32 / 2), // Centered horizontally
+                self.position.y + (self.size.height as i32 / 2) + 15 + line_height, // Below progress bar
             ),
             text_style,
+            Alignment::Center,
         )
         .draw(display)?;
 
@@ -1,6 +1,6 @@
 use alloc::string::String;
-use embedded_graphics::mono_font::ascii::FONT_6X9;
-use embedded_graphics::mono_font::MonoTextStyleBuilder;
+use embedded_graphics::mono_font::ascii::{FONT_10X20, FONT_6X10, FONT_6X12, FONT_6X9};
+use embedded_graphics::mono_font::{MonoFont, MonoTextStyleBuilder};
 use embedded_graphics::prelude::{DrawTarget, Point, Primitive, Size};
 use embedded_graphics::text::{Baseline, Text};
 use embedded_graphics::{
@@ -8,22 +8,148 @@ use embedded_graphics::{
     prelude::*,
     primitives::{PrimitiveStyle, Rectangle},
 };
+
+use crate::input::events::{ButtonEvents, ButtonTracker};
+use crate::input::{
+    InputSampler, INPUT_A, INPUT_B, INPUT_DOWN, INPUT_LEFT, INPUT_RIGHT, INPUT_START, INPUT_UP,
+};
+
+pub mod controls;
+pub mod game_menu;
+pub mod joystick;
 pub mod loading;
+pub mod osd;
 pub mod rom_select;
+pub mod settings;
+
+pub const BUTTON_UP: u32 = 1 << 0;
+pub const BUTTON_DOWN: u32 = 1 << 1;
+pub const BUTTON_LEFT: u32 = 1 << 2;
+pub const BUTTON_RIGHT: u32 = 1 << 3;
+pub const BUTTON_CONFIRM: u32 = 1 << 4;
+pub const BUTTON_BACK: u32 = 1 << 5;
+pub const BUTTON_START: u32 = 1 << 6;
+
+/// Buttons used to navigate the menus. They are always the physical inputs named after the
+/// Game Boy buttons so a broken mapping can't lock the menus, A confirms and B goes back.
+pub struct MenuButtons<'a> {
+    inputs: &'a mut dyn InputSampler,
+    tracker: ButtonTracker,
+}
+
+impl<'a> MenuButtons<'a> {
+    pub fn new(inputs: &'a mut dyn InputSampler) -> Self {
+        let mut buttons = Self {
+            inputs,
+            tracker: ButtonTracker::default(),
+        };
+        // Buttons already held when the screen opens are not presses
+        buttons.poll();
+        buttons
+    }
+
+    /// Returns the events of the buttons as masks of the `BUTTON_*` bits.
+    pub fn poll(&mut self) -> ButtonEvents {
+        let (inputs, now) = self.inputs.sample();
+        self.tracker.update(menu_buttons(inputs), now)
+    }
+
+    /// Blocks until every input is released, so a press that closes a menu is not seen by
+    /// the next screen.
+    pub fn wait_for_release(&mut self) {
+        loop {
+            let (inputs, now) = self.inputs.sample();
+            if inputs == 0 {
+                self.tracker.update(0, now);
+                return;
+            }
+        }
+    }
+
+    /// Blocks until an input is pressed and returns its `INPUT_*` number.
+    pub fn wait_for_input(&mut self) -> u8 {
+        loop {
+            let (inputs, _) = self.inputs.sample();
+            if inputs != 0 {
+                return inputs.trailing_zeros() as u8;
+            }
+        }
+    }
+}
+
+fn menu_buttons(inputs: u32) -> u32 {
+    let buttons: [(u8, u32); 7] = [
+        (INPUT_UP, BUTTON_UP),
+        (INPUT_DOWN, BUTTON_DOWN),
+        (INPUT_LEFT, BUTTON_LEFT),
+        (INPUT_RIGHT, BUTTON_RIGHT),
+        (INPUT_A, BUTTON_CONFIRM),
+        (INPUT_B, BUTTON_BACK),
+        (INPUT_START, BUTTON_START),
+    ];
+    let mut mask = 0;
+    for (input, bit) in buttons {
+        if inputs & (1 << input) != 0 {
+            mask |= bit;
+        }
+    }
+    mask
+}
+
+/// Fonts and spacing of the menus, screens of 320 pixels or more on their short side
+/// (such as the 320x480 ILI9488) get larger ones so the text stays readable.
+pub struct UiLayout {
+    pub title_font: &'static MonoFont<'static>,
+    pub text_font: &'static MonoFont<'static>,
+    pub list_font: &'static MonoFont<'static>,
+    pub list_item_height: i32,
+    pub list_item_padding: i32,
+}
+
+impl UiLayout {
+    pub fn for_size(size: Size) -> Self {
+        if size.width >= 320 && size.height >= 320 {
+            UiLayout {
+                title_font: &FONT_10X20,
+                text_font: &FONT_10X20,
+                list_font: &FONT_10X20,
+                list_item_height: 30,
+                list_item_padding: 6,
+            }
+        } else {
+            UiLayout {
+                title_font: &FONT_6X12,
+                text_font: &FONT_6X10,
+                list_font: &FONT_6X9,
+                list_item_height: 20,
+                list_item_padding: 5,
+            }
+        }
+    }
+}
+
 pub struct ListDisplay {
     position: Point,
     item_height: i32,
     item_padding: i32,
     width: i32,
+    font: &'static MonoFont<'static>,
 }
 
 impl ListDisplay {
-    pub fn new(position: Point, width: i32, item_height: i32, item_padding: i32) -> Self {
+    pub fn new(
+        position: Point,
+        width: i32,
+        item_height: i32,
+        item_padding: i32,
+        font: &'static MonoFont<'static>,
+    ) -> Self {
         ListDisplay {
             position,
             item_height: item_height,   // Height for each item
             item_padding: item_padding, // Padding between items
             width,
+            font,
         }
     }
 
@@ -55,12 +181,12 @@ impl ListDisplay {
 
             let text_style = if index == selected as usize {
                 MonoTextStyleBuilder::new()
-                    .font(&FONT_6X9)
+                    .font(self.font)
                     .text_color(Rgb565::WHITE)
                     .build()
             } else {
                 MonoTextStyleBuilder::new()
-                    .font(&FONT_6X9)
+                    .font(self.font)
                     .text_color(Rgb565::BLACK)
                     .build()
             };
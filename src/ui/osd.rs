@@ -0,0 +1,320 @@
+use core::convert::Infallible;
+use core::ops::Range;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use embedded_graphics::{
+    image::{Image, ImageRaw},
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::{raw::RawU16, BinaryColor, Rgb565},
+    prelude::*,
+    text::{Baseline, Text},
+};
+
+use crate::hal::timer::Instant;
+
+const TOAST_DURATION: fugit::MicrosDurationU64 = fugit::MicrosDurationU64::millis(2000);
+const TOAST_HEIGHT: u16 = 14;
+const TOAST_MARGIN: u16 = 4;
+/// Room for "59.7fps 100%", and " B100%" behind it with a battery pin.
+const STATS_CHARS: u16 = if cfg!(feature = "battery") { 18 } else { 12 };
+const STATS_HEIGHT: u16 = 12;
+const STATS_MARGIN: u16 = 2;
+const CHAR_WIDTH: u16 = 6;
+const ICON_SIZE: u32 = 8;
+
+const FOREGROUND: Rgb565 = Rgb565::WHITE;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum OsdIcon {
+    Cartridge,
+    Camera,
+    Speaker,
+    SpeakerMuted,
+    Record,
+}
+
+impl OsdIcon {
+    fn bitmap(self) -> &'static [u8; 8] {
+        match self {
+            OsdIcon::Cartridge => &[
+                0b11111100, 0b10000110, 0b10111010, 0b10111010, 0b10000010, 0b10011010, 0b10000010,
+                0b11111110,
+            ],
+            OsdIcon::Camera => &[
+                0b00000000, 0b00111000, 0b11111110, 0b10000010, 0b10111010, 0b10111010, 0b10000010,
+                0b11111110,
+            ],
+            OsdIcon::Speaker => &[
+                0b00010000, 0b00110010, 0b11110001, 0b11110101, 0b11110101, 0b11110001, 0b00110010,
+                0b00010000,
+            ],
+            OsdIcon::SpeakerMuted => &[
+                0b00010000, 0b00110000, 0b11110101, 0b11110010, 0b11110101, 0b11110000, 0b00110000,
+                0b00010000,
+            ],
+            OsdIcon::Record => &[
+                0b00000000, 0b00111000, 0b01111100, 0b11111110, 0b11111110, 0b11111110, 0b01111100,
+                0b00111000,
+            ],
+        }
+    }
+}
+
+/// On-screen display blended into the scaled Game Boy frame while it is being streamed.
+///
+/// Layers are rendered once into 1-bit masks whenever their content changes, streaming a
+/// pixel only costs a row check and, inside a layer, a bit lookup.
+pub struct Osd {
+    width: u16,
+    stats: OsdLayer,
+    toast: OsdLayer,
+    stats_text: String,
+    toast_expires: Option<Instant>,
+    /// Rows of the layers whose content or visibility changed since `take_changed`.
+    changed: Option<Range<u16>>,
+}
+
+impl Osd {
+    pub fn new(width: u16, height: u16) -> Self {
+        let toast_width = width.min(160) - (TOAST_MARGIN * 2);
+        let stats_width = STATS_CHARS * CHAR_WIDTH + 4;
+        Self {
+            width,
+            stats: OsdLayer::new(
+                width.saturating_sub(stats_width + STATS_MARGIN),
+                STATS_MARGIN,
+                stats_width.min(width),
+                STATS_HEIGHT,
+            ),
+            toast: OsdLayer::new(
+                (width - toast_width) / 2,
+                height.saturating_sub(TOAST_HEIGHT + TOAST_MARGIN),
+                toast_width,
+                TOAST_HEIGHT,
+            ),
+            stats_text: String::new(),
+            toast_expires: None,
+            changed: None,
+        }
+    }
+
+    /// Shows a short message at the bottom of the screen until it times out.
+    pub fn show_toast(&mut self, icon: Option<OsdIcon>, message: &str, now: Instant) {
+        self.toast.clear();
+        let mut text_x = 3;
+        if let Some(icon) = icon {
+            let raw: ImageRaw<BinaryColor> = ImageRaw::new(icon.bitmap(), ICON_SIZE);
+            let _ = Image::new(&raw, Point::new(3, 3)).draw(&mut self.toast);
+            text_x += ICON_SIZE as i32 + 3;
+        }
+        let max_chars = (self.toast.width as usize - text_x as usize) / CHAR_WIDTH as usize;
+        let message = match message.char_indices().nth(max_chars) {
+            Some((end, _)) => &message[..end],
+            None => message,
+        };
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let _ = Text::with_baseline(message, Point::new(text_x, 2), style, Baseline::Top)
+            .draw(&mut self.toast);
+        self.toast.visible = true;
+        self.toast_expires = Some(now + TOAST_DURATION);
+        self.mark_changed(self.toast.rows());
+    }
+
+    /// Replaces the text of the persistent stats corner, an empty text hides it.
+    pub fn set_stats(&mut self, text: &str) {
+        if self.stats_text == text {
+            return;
+        }
+        self.stats_text.clear();
+        self.stats_text.push_str(text);
+        self.stats.clear();
+        self.stats.visible = !text.is_empty();
+        self.mark_changed(self.stats.rows());
+        // Right aligned so the text stays in the corner when it gets shorter
+        let text_width = text.chars().count() as u16 * CHAR_WIDTH;
+        let x = self.stats.width.saturating_sub(text_width + 2).max(2);
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let _ = Text::with_baseline(text, Point::new(x as i32, 1), style, Baseline::Top)
+            .draw(&mut self.stats);
+    }
+
+    /// Expires timed out toasts, call once per frame before streaming it.
+    pub fn update(&mut self, now: Instant) {
+        if let Some(expires) = self.toast_expires {
+            if now >= expires {
+                self.toast.visible = false;
+                self.toast_expires = None;
+                self.mark_changed(self.toast.rows());
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub fn overlay<'a, I>(&'a self, iterator: I) -> OsdIterator<'a, I>
+    where
+        I: Iterator<Item = Rgb565>,
+    {
+        self.overlay_from(0, iterator)
+    }
+
+    /// Overlays an iterator whose first pixel is at the start of the given row.
+    #[inline(always)]
+    pub fn overlay_from<'a, I>(&'a self, row: u16, iterator: I) -> OsdIterator<'a, I>
+    where
+        I: Iterator<Item = Rgb565>,
+    {
+        OsdIterator {
+            iterator,
+            osd: self,
+            x: 0,
+            y: row,
+            row_active: self.covers_row(row),
+        }
+    }
+
+    /// Returns the rows whose overlay changed once after the content or visibility of a layer
+    /// changed, the Game Boy lines under them have to be sent again.
+    pub fn take_changed(&mut self) -> Option<Range<u16>> {
+        self.changed.take()
+    }
+
+    fn mark_changed(&mut self, rows: Range<u16>) {
+        self.changed = Some(match self.changed.take() {
+            Some(changed) => changed.start.min(rows.start)..changed.end.max(rows.end),
+            None => rows,
+        });
+    }
+
+    #[inline(always)]
+    fn covers_row(&self, y: u16) -> bool {
+        self.stats.covers_row(y) || self.toast.covers_row(y)
+    }
+
+    #[inline(always)]
+    fn blend(&self, x: u16, y: u16, pixel: Rgb565) -> Rgb565 {
+        match self.toast.sample(x, y).or_else(|| self.stats.sample(x, y)) {
+            Some(true) => FOREGROUND,
+            Some(false) => {
+                // Halve every channel so the text stays readable on bright backgrounds
+                let raw = (pixel.into_storage() >> 1) & 0b01111_011111_01111;
+                Rgb565::from(RawU16::new(raw))
+            }
+            None => pixel,
+        }
+    }
+}
+
+pub struct OsdIterator<'a, I> {
+    iterator: I,
+    osd: &'a Osd,
+    x: u16,
+    y: u16,
+    row_active: bool,
+}
+
+impl<'a, I> Iterator for OsdIterator<'a, I>
+where
+    I: Iterator<Item = Rgb565>,
+{
+    type Item = Rgb565;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let pixel = self.iterator.next()?;
+        let pixel = if self.row_active {
+            self.osd.blend(self.x, self.y, pixel)
+        } else {
+            pixel
+        };
+        self.x += 1;
+        if self.x == self.osd.width {
+            self.x = 0;
+            self.y += 1;
+            self.row_active = self.osd.covers_row(self.y);
+        }
+        Some(pixel)
+    }
+}
+
+struct OsdLayer {
+    x: u16,
+    y: u16,
+    width: u16,
+    height: u16,
+    words_per_row: usize,
+    mask: Vec<u32>,
+    visible: bool,
+}
+
+impl OsdLayer {
+    fn new(x: u16, y: u16, width: u16, height: u16) -> Self {
+        let words_per_row = (width as usize + 31) / 32;
+        Self {
+            x,
+            y,
+            width,
+            height,
+            words_per_row,
+            mask: alloc::vec![0u32; words_per_row * height as usize],
+            visible: false,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.mask.fill(0);
+    }
+
+    fn rows(&self) -> Range<u16> {
+        self.y..self.y + self.height
+    }
+
+    #[inline(always)]
+    fn covers_row(&self, y: u16) -> bool {
+        self.visible && y >= self.y && y < self.y + self.height
+    }
+
+    #[inline(always)]
+    fn sample(&self, x: u16, y: u16) -> Option<bool> {
+        if !self.covers_row(y) || x < self.x || x >= self.x + self.width {
+            return None;
+        }
+        let column = (x - self.x) as usize;
+        let word = self.mask[(y - self.y) as usize * self.words_per_row + column / 32];
+        Some(word & (1 << (column % 32)) != 0)
+    }
+}
+
+impl OriginDimensions for OsdLayer {
+    fn size(&self) -> Size {
+        Size::new(self.width as u32, self.height as u32)
+    }
+}
+
+impl DrawTarget for OsdLayer {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if point.x < 0
+                || point.y < 0
+                || point.x >= self.width as i32
+                || point.y >= self.height as i32
+            {
+                continue;
+            }
+            let column = point.x as usize;
+            let index = point.y as usize * self.words_per_row + column / 32;
+            let bit = 1 << (column % 32);
+            match color {
+                BinaryColor::On => self.mask[index] |= bit,
+                BinaryColor::Off => self.mask[index] &= !bit,
+            }
+        }
+        Ok(())
+    }
+}
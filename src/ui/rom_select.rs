@@ -1,58 +1,80 @@
-use core::convert::Infallible;
-
 use alloc::string::String;
 use embedded_graphics::{
-    mono_font::{ascii::FONT_6X12, MonoTextStyleBuilder},
+    mono_font::MonoTextStyleBuilder,
     pixelcolor::Rgb565,
     prelude::*,
     prelude::{DrawTarget, Point, RgbColor, WebColors},
-    text::{Baseline, Text},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
 };
-use embedded_hal::digital::InputPin;
 use rp235x_hal::timer::TimerDevice;
 
-use crate::{util::LimitedViewList, RENDER_HEIGHT, RENDER_WIDTH};
+use crate::util::LimitedViewList;
 
-use super::ListDisplay;
+use super::{
+    ListDisplay, MenuButtons, UiLayout, BUTTON_CONFIRM, BUTTON_DOWN, BUTTON_START, BUTTON_UP,
+};
+
+pub enum RomSelection {
+    Rom(usize),
+    /// START was pressed to open the settings menu.
+    Settings,
+}
 
 #[inline(always)]
 pub fn select_rom<'a, D: DrawTarget<Color = Rgb565>, TD: TimerDevice>(
     display: &mut D,
     rom_list: &[String],
     mut _timer: crate::hal::Timer<TD>,
-    up_button: &'a mut dyn InputPin<Error = Infallible>,
-    down_button: &'a mut dyn InputPin<Error = Infallible>,
-    select_button: &'a mut dyn InputPin<Error = Infallible>,
-) -> Result<usize, D::Error> {
+    buttons: &mut MenuButtons<'a>,
+) -> Result<RomSelection, D::Error> {
     let mut selected_rom = 0u8;
-    let mut button_clicked = false;
 
     display.clear(Rgb565::CSS_GRAY)?;
+    let size = display.bounding_box().size;
+    let layout = UiLayout::for_size(size);
 
     let title_style = MonoTextStyleBuilder::new()
-        .font(&FONT_6X12)
+        .font(layout.title_font)
         .text_color(Rgb565::WHITE)
         .build();
 
+    let title_height = layout.title_font.character_size.height as i32 + 8;
     Text::with_baseline(
         "Select Rom:",
-        Point::new(0, 7),
+        Point::new(0, title_height / 2),
         title_style,
         Baseline::Middle,
     )
     .draw(display)?;
+    let hint_style = MonoTextStyleBuilder::new()
+        .font(layout.text_font)
+        .text_color(Rgb565::WHITE)
+        .build();
+    Text::with_text_style(
+        "START: Settings",
+        Point::new(size.width as i32 - 2, title_height / 2),
+        hint_style,
+        TextStyleBuilder::new()
+            .alignment(Alignment::Right)
+            .baseline(Baseline::Middle)
+            .build(),
+    )
+    .draw(display)?;
 
+    let item_size = layout.list_item_height + layout.list_item_padding;
     let list = ListDisplay::new(
-        Point::new(0, 20),   // Starting position
-        RENDER_WIDTH as i32, // Width in pixels
-        20,
-        5,
+        Point::new(0, title_height), // Starting position
+        size.width as i32,           // Width in pixels
+        layout.list_item_height,
+        layout.list_item_padding,
+        layout.list_font,
     );
-    let max_items_to_display = ((RENDER_HEIGHT / (20 + 5)) as usize) - 1;
+    let max_items_to_display = ((size.height as i32 - title_height) / item_size) as usize;
     let mut items = LimitedViewList::new(rom_list, max_items_to_display);
     list.draw(items.iter(), 0, display)?;
     loop {
-        if up_button.is_low().unwrap() && !button_clicked {
+        let events = buttons.poll();
+        if events.repeated & BUTTON_UP != 0 {
             if selected_rom != 0 {
                 selected_rom = selected_rom - 1;
                 defmt::info!("up_button Start redraw: {}", selected_rom);
@@ -61,9 +83,8 @@ pub fn select_rom<'a, D: DrawTarget<Color = Rgb565>, TD: TimerDevice>(
                 items.prev();
                 list.draw(items.iter(), selected_rom, display)?;
             }
-            button_clicked = true;
         }
-        if down_button.is_low().unwrap() && !button_clicked {
+        if events.repeated & BUTTON_DOWN != 0 {
             if selected_rom + 1 < items.max() as u8 {
                 selected_rom = selected_rom + 1;
                 defmt::info!("down_button Start redraw: {}", selected_rom);
@@ -72,14 +93,15 @@ pub fn select_rom<'a, D: DrawTarget<Color = Rgb565>, TD: TimerDevice>(
                 items.next();
                 list.draw(items.iter(), selected_rom, display)?;
             }
-            button_clicked = true;
         }
-        if select_button.is_low().unwrap() {
-            return Ok(items.current_cursor() + selected_rom as usize);
+        if events.pressed & BUTTON_CONFIRM != 0 {
+            return Ok(RomSelection::Rom(
+                items.current_cursor() + selected_rom as usize,
+            ));
         }
-
-        if down_button.is_high().unwrap() && up_button.is_high().unwrap() {
-            button_clicked = false;
+        if events.pressed & BUTTON_START != 0 {
+            buttons.wait_for_release();
+            return Ok(RomSelection::Settings);
         }
     }
 }
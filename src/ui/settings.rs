@@ -0,0 +1,239 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use embedded_graphics::{
+    mono_font::MonoTextStyleBuilder,
+    pixelcolor::Rgb565,
+    prelude::*,
+    text::{Baseline, Text},
+};
+
+use crate::hardware::sound::VOLUME_STEPS;
+use crate::input::joystick::JoystickState;
+use crate::settings::Settings;
+
+use super::{
+    controls::controls_menu, joystick::calibrate_joystick, ListDisplay, MenuButtons, UiLayout,
+    BUTTON_BACK, BUTTON_CONFIRM, BUTTON_DOWN, BUTTON_LEFT, BUTTON_RIGHT, BUTTON_UP,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SettingsItem {
+    Volume,
+    Mute,
+    AudioOutput,
+    Audio,
+    TurboRate,
+    TurboA,
+    TurboB,
+    Controls,
+    StickDeadzone,
+    StickDiagonals,
+    CalibrateStick,
+    Back,
+}
+
+const ITEMS: [SettingsItem; 12] = [
+    SettingsItem::Volume,
+    SettingsItem::Mute,
+    SettingsItem::AudioOutput,
+    SettingsItem::Audio,
+    SettingsItem::TurboRate,
+    SettingsItem::TurboA,
+    SettingsItem::TurboB,
+    SettingsItem::Controls,
+    SettingsItem::StickDeadzone,
+    SettingsItem::StickDiagonals,
+    SettingsItem::CalibrateStick,
+    SettingsItem::Back,
+];
+
+impl SettingsItem {
+    /// The stick entries are only shown when the pin mapping has an analog stick.
+    fn available(self) -> bool {
+        match self {
+            SettingsItem::StickDeadzone
+            | SettingsItem::StickDiagonals
+            | SettingsItem::CalibrateStick => cfg!(feature = "joystick"),
+            _ => true,
+        }
+    }
+
+    fn label(self, settings: &Settings) -> String {
+        match self {
+            SettingsItem::Volume => {
+                alloc::format!("Volume      < {}/{} >", settings.volume.level, VOLUME_STEPS)
+            }
+            SettingsItem::Mute => alloc::format!(
+                "Mute        {}",
+                if settings.volume.muted { "On" } else { "Off" }
+            ),
+            SettingsItem::AudioOutput => {
+                alloc::format!("Output      < {} >", settings.output_mode.name())
+            }
+            SettingsItem::Audio => alloc::format!(
+                "Audio       {}",
+                if settings.audio_enabled { "On" } else { "Off" }
+            ),
+            SettingsItem::TurboRate => {
+                alloc::format!("Turbo rate  < {}Hz >", settings.turbo.rate)
+            }
+            SettingsItem::TurboA => alloc::format!(
+                "Turbo A     {}",
+                if settings.turbo.a { "On" } else { "Off" }
+            ),
+            SettingsItem::TurboB => alloc::format!(
+                "Turbo B     {}",
+                if settings.turbo.b { "On" } else { "Off" }
+            ),
+            SettingsItem::Controls => "Controls    >".into(),
+            SettingsItem::StickDeadzone => {
+                alloc::format!("Deadzone    < {}% >", settings.joystick.deadzone)
+            }
+            SettingsItem::StickDiagonals => alloc::format!(
+                "Diagonals   {}",
+                if settings.joystick.diagonals {
+                    "On"
+                } else {
+                    "Off"
+                }
+            ),
+            SettingsItem::CalibrateStick => "Calibrate   >".into(),
+            SettingsItem::Back => "Back".into(),
+        }
+    }
+
+    /// Changes the value with LEFT (`-1`), RIGHT (`1`) or A (`0`), returns true when the item
+    /// closes the menu.
+    fn change(self, settings: &mut Settings, direction: i8) -> bool {
+        match self {
+            SettingsItem::Volume if direction < 0 => settings.volume.down(),
+            SettingsItem::Volume if direction > 0 => settings.volume.up(),
+            SettingsItem::Volume => {}
+            SettingsItem::Mute => settings.volume.toggle_mute(),
+            SettingsItem::AudioOutput => {
+                let step = if direction < 0 { -1 } else { 1 };
+                settings.output_mode = settings.output_mode.cycle(step);
+            }
+            SettingsItem::Audio => settings.audio_enabled = !settings.audio_enabled,
+            SettingsItem::TurboRate if direction < 0 => settings.turbo.slower(),
+            SettingsItem::TurboRate => settings.turbo.faster(),
+            SettingsItem::TurboA => settings.turbo.a = !settings.turbo.a,
+            SettingsItem::TurboB => settings.turbo.b = !settings.turbo.b,
+            SettingsItem::StickDeadzone if direction < 0 => settings.joystick.narrower_deadzone(),
+            SettingsItem::StickDeadzone => settings.joystick.wider_deadzone(),
+            SettingsItem::StickDiagonals => {
+                settings.joystick.diagonals = !settings.joystick.diagonals
+            }
+            // Opened by the menu itself, they need the display and the buttons
+            SettingsItem::Controls | SettingsItem::CalibrateStick => {}
+            SettingsItem::Back => return direction == 0,
+        }
+        false
+    }
+}
+
+/// Lets the user change the settings, UP/DOWN select an entry and LEFT/RIGHT/A change it.
+/// Returns once "Back" or B is pressed, true if anything changed. The stick is read with the
+/// new calibration right away.
+pub fn settings_menu<D: DrawTarget<Color = Rgb565>>(
+    display: &mut D,
+    settings: &mut Settings,
+    joystick: &JoystickState,
+    buttons: &mut MenuButtons,
+) -> Result<bool, D::Error> {
+    let initial_settings = *settings;
+    let items: Vec<SettingsItem> = ITEMS.into_iter().filter(|item| item.available()).collect();
+    let size = display.bounding_box().size;
+    let layout = UiLayout::for_size(size);
+    let title_height = layout.title_font.character_size.height as i32 + 8;
+    draw_title(display, &layout, title_height)?;
+
+    let list = ListDisplay::new(
+        Point::new(0, title_height),
+        size.width as i32,
+        layout.list_item_height,
+        layout.list_item_padding,
+        layout.list_font,
+    );
+    let item_size = layout.list_item_height + layout.list_item_padding;
+    let visible = (((size.height as i32 - title_height) / item_size) as usize).min(items.len());
+    let mut selected = 0usize;
+    let mut first = 0usize;
+    let mut redraw = true;
+    loop {
+        if redraw {
+            let labels: Vec<String> = items.iter().map(|item| item.label(settings)).collect();
+            list.draw(
+                labels[first..first + visible].iter(),
+                (selected - first) as u8,
+                display,
+            )?;
+            redraw = false;
+        }
+
+        let events = buttons.poll();
+        let clicked = events.pressed;
+        if clicked & BUTTON_BACK != 0 {
+            break;
+        }
+        if events.repeated & BUTTON_UP != 0 && selected > 0 {
+            selected -= 1;
+            first = first.min(selected);
+            redraw = true;
+        }
+        if events.repeated & BUTTON_DOWN != 0 && selected + 1 < items.len() {
+            selected += 1;
+            if selected >= first + visible {
+                first = selected + 1 - visible;
+            }
+            redraw = true;
+        }
+        let direction = if events.repeated & BUTTON_LEFT != 0 {
+            Some(-1)
+        } else if events.repeated & BUTTON_RIGHT != 0 {
+            Some(1)
+        } else if clicked & BUTTON_CONFIRM != 0 {
+            Some(0)
+        } else {
+            None
+        };
+        if direction.is_some() && items[selected] == SettingsItem::Controls {
+            controls_menu(display, &mut settings.mapping, buttons)?;
+            draw_title(display, &layout, title_height)?;
+            redraw = true;
+        } else if direction.is_some() && items[selected] == SettingsItem::CalibrateStick {
+            calibrate_joystick(display, &mut settings.joystick, joystick, buttons)?;
+            joystick.calibration.set(settings.joystick);
+            draw_title(display, &layout, title_height)?;
+            redraw = true;
+        } else if let Some(direction) = direction {
+            if items[selected].change(settings, direction) {
+                break;
+            }
+            joystick.calibration.set(settings.joystick);
+            redraw = true;
+        }
+    }
+    buttons.wait_for_release();
+    Ok(*settings != initial_settings)
+}
+
+fn draw_title<D: DrawTarget<Color = Rgb565>>(
+    display: &mut D,
+    layout: &UiLayout,
+    title_height: i32,
+) -> Result<(), D::Error> {
+    display.clear(Rgb565::CSS_GRAY)?;
+    let title_style = MonoTextStyleBuilder::new()
+        .font(layout.title_font)
+        .text_color(Rgb565::WHITE)
+        .build();
+    Text::with_baseline(
+        "Settings:",
+        Point::new(0, title_height / 2),
+        title_style,
+        Baseline::Middle,
+    )
+    .draw(display)?;
+    Ok(())
+}
@@ -62,6 +62,8 @@ impl<'a, T> LimitedViewList<'a, T> {
     }
 }
 
+pub use gb_rp2350_logic::config::{config_entries, parse_bool};
+
 #[macro_export]
 macro_rules! pin_select {
     ($pins:expr, $pin_num:expr) => {{